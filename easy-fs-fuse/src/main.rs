@@ -1,8 +1,8 @@
-use clap::{App, Arg};
+use clap::{App, Arg, ArgMatches};
 use colored::Colorize;
-use easy_fs::{BlockDevice, EasyFileSystem};
+use easy_fs::{BlockDevice, EasyFileSystem, Inode};
 use std::fs::{read_dir, File, OpenOptions};
-use std::io::{Read, Seek, SeekFrom, Write};
+use std::io::{Error, ErrorKind, Read, Seek, SeekFrom, Write};
 use std::sync::Arc;
 use std::sync::Mutex;
 
@@ -24,6 +24,10 @@ impl BlockDevice for BlockFile {
             .expect("Error when seeking!");
         assert_eq!(file.write(buf).unwrap(), BLOCK_SZ, "Not a complete block!");
     }
+
+    fn flush(&self) -> bool {
+        self.0.lock().unwrap().sync_data().is_ok()
+    }
 }
 
 fn main() {
@@ -46,9 +50,94 @@ fn easy_fs_pack() -> std::io::Result<()> {
                 .takes_value(true)
                 .help("Executable target dir(with backslash)"),
         )
+        .arg(
+            Arg::with_name("checksum")
+                .short("c")
+                .long("checksum")
+                .help("Keep a CRC32 checksum for every data block"),
+        )
+        .arg(
+            Arg::with_name("xattrs")
+                .short("x")
+                .long("xattrs")
+                .takes_value(true)
+                .value_name("FILE")
+                .help("Extended attributes to set, one \"app name value\" per line"),
+        )
+        .arg(
+            Arg::with_name("size")
+                .long("size")
+                .takes_value(true)
+                .value_name("MIB")
+                .default_value("16")
+                .help("Image size in MiB"),
+        )
+        .arg(
+            Arg::with_name("inode-bitmap-blocks")
+                .long("inode-bitmap-blocks")
+                .takes_value(true)
+                .value_name("N")
+                .default_value("1")
+                .help("Inode bitmap blocks, each one holds 4096 inodes"),
+        )
+        .arg(
+            Arg::with_name("grow")
+                .long("grow")
+                .takes_value(true)
+                .value_name("MIB")
+                .help("Grow the existing image to this size in MiB"),
+        )
+        .arg(
+            Arg::with_name("grow-inode-bitmap-blocks")
+                .long("grow-inode-bitmap-blocks")
+                .takes_value(true)
+                .value_name("N")
+                .default_value("0")
+                .help("Inode bitmap blocks to add when growing"),
+        )
+        .arg(
+            Arg::with_name("snapshot")
+                .long("snapshot")
+                .takes_value(true)
+                .value_name("NAME")
+                .help("Take a snapshot of the existing image"),
+        )
+        .arg(
+            Arg::with_name("rollback")
+                .long("rollback")
+                .takes_value(true)
+                .value_name("NAME")
+                .help("Roll the existing image back to a snapshot"),
+        )
+        .arg(
+            Arg::with_name("delete-snapshot")
+                .long("delete-snapshot")
+                .takes_value(true)
+                .value_name("NAME")
+                .help("Delete a snapshot of the existing image"),
+        )
+        .arg(
+            Arg::with_name("list-snapshots")
+                .long("list-snapshots")
+                .help("List snapshots of the existing image"),
+        )
         .get_matches();
-    let src_path = matches.value_of("source").unwrap();
     let target_path = matches.value_of("target").unwrap();
+    if [
+        "grow",
+        "snapshot",
+        "rollback",
+        "delete-snapshot",
+        "list-snapshots",
+    ]
+    .iter()
+    .any(|arg| matches.is_present(arg))
+    {
+        return easy_fs_update(&matches, &format!("{}{}", target_path, "fs.img"));
+    }
+    let total_blocks = mib_to_blocks(matches.value_of("size").unwrap())?;
+    let inode_bitmap_blocks = parse_arg(matches.value_of("inode-bitmap-blocks").unwrap())?;
+    let src_path = matches.value_of("source").unwrap();
     println!("src_path = {}\ntarget_path = {}", src_path, target_path);
     let block_file = Arc::new(BlockFile(Mutex::new({
         let f = OpenOptions::new()
@@ -56,11 +145,16 @@ fn easy_fs_pack() -> std::io::Result<()> {
             .write(true)
             .create(true)
             .open(format!("{}{}", target_path, "fs.img"))?;
-        f.set_len(16 * 2048 * 512).unwrap();
+        f.set_len(total_blocks as u64 * BLOCK_SZ as u64).unwrap();
         f
     })));
-    // 16MiB, at most 4095 files
-    let efs = EasyFileSystem::create(block_file, 16 * 2048, 1);
+    // 默认16MiB, at most 4095 files
+    let efs = EasyFileSystem::create(
+        block_file,
+        total_blocks,
+        inode_bitmap_blocks,
+        matches.is_present("checksum"),
+    );
     let root_inode = Arc::new(EasyFileSystem::root_inode(&efs));
     let apps: Vec<_> = read_dir(src_path)
         .unwrap()
@@ -79,17 +173,126 @@ fn easy_fs_pack() -> std::io::Result<()> {
         // create a file in easy-fs
         let inode = root_inode.create(app.as_str()).unwrap();
         // write data to easy-fs
-        inode.write_at(0, all_data.as_slice());
+        inode
+            .write_at(0, all_data.as_slice())
+            .map_err(to_io_error)?;
+    }
+    if let Some(path) = matches.value_of("xattrs") {
+        set_xattrs(&root_inode, path)?;
     }
     // list apps
-    for app in root_inode.ls() {
+    for app in root_inode.ls().map_err(to_io_error)? {
         println!("{}", app);
     }
     Ok(())
 }
 
+/// 块缓存是全局的 测试需要串行执行
+#[cfg(test)]
+static TEST_LOCK: Mutex<()> = Mutex::new(());
+
+fn parse_arg(value: &str) -> std::io::Result<u32> {
+    value
+        .parse()
+        .map_err(|_| Error::new(ErrorKind::InvalidInput, format!("invalid number {}", value)))
+}
+
+fn to_io_error(err: easy_fs::FsError) -> Error {
+    Error::new(ErrorKind::Other, format!("{:?}", err))
+}
+
+fn mib_to_blocks(value: &str) -> std::io::Result<u32> {
+    parse_arg(value)?
+        .checked_mul(1024 * 1024 / BLOCK_SZ as u32)
+        .ok_or_else(|| Error::new(ErrorKind::InvalidInput, format!("size too large {}", value)))
+}
+
+/// 按清单设置扩展属性 每行为 文件名 属性名 属性值 #开头为注释
+fn set_xattrs(root_inode: &Inode, path: &str) -> std::io::Result<()> {
+    let mut manifest = String::new();
+    File::open(path)?.read_to_string(&mut manifest)?;
+    for line in manifest.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields: Vec<_> = line.splitn(3, char::is_whitespace).collect();
+        if fields.len() != 3 {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("invalid xattr line: {}", line),
+            ));
+        }
+        let inode = match root_inode.find(fields[0]).map_err(to_io_error)? {
+            Some(inode) => inode,
+            None => {
+                println!("skip xattrs of missing app {}", fields[0]);
+                continue;
+            }
+        };
+        inode
+            .set_xattr(fields[1], fields[2].trim().as_bytes())
+            .map_err(to_io_error)?;
+    }
+    Ok(())
+}
+
+/// 在已有镜像上扩容或操作快照 不重新打包
+fn easy_fs_update(matches: &ArgMatches, image_path: &str) -> std::io::Result<()> {
+    let block_file = Arc::new(BlockFile(Mutex::new(
+        OpenOptions::new().read(true).write(true).open(image_path)?,
+    )));
+    let efs = EasyFileSystem::open(block_file.clone());
+    let mut efs = efs.lock();
+    if let Some(size) = matches.value_of("grow") {
+        let total_blocks = mib_to_blocks(size)?;
+        let inode_bitmap_blocks = parse_arg(matches.value_of("grow-inode-bitmap-blocks").unwrap())?;
+        let len = block_file.0.lock().unwrap().metadata()?.len();
+        if (total_blocks as u64 * BLOCK_SZ as u64) < len {
+            return Err(Error::new(ErrorKind::InvalidInput, "cannot shrink image"));
+        }
+        // 先检查再扩展镜像文件 失败时镜像保持原样
+        efs.check_grow(total_blocks, inode_bitmap_blocks)
+            .map_err(to_io_error)?;
+        block_file
+            .0
+            .lock()
+            .unwrap()
+            .set_len(total_blocks as u64 * BLOCK_SZ as u64)?;
+        if let Err(err) = efs.grow(total_blocks, inode_bitmap_blocks) {
+            block_file.0.lock().unwrap().set_len(len)?;
+            return Err(to_io_error(err));
+        }
+        println!("image grown to {} MiB", size);
+    }
+    let find = |efs: &EasyFileSystem, name: &str| {
+        efs.find_snapshot(name)
+            .ok_or_else(|| Error::new(ErrorKind::NotFound, format!("snapshot {} not found", name)))
+    };
+    if let Some(name) = matches.value_of("snapshot") {
+        let id = efs.snapshot(name).map_err(to_io_error)?;
+        println!("snapshot {} taken (id {})", name, id);
+    }
+    if let Some(name) = matches.value_of("rollback") {
+        let id = find(&efs, name)?;
+        efs.rollback(id).map_err(to_io_error)?;
+        println!("rolled back to {}", name);
+    }
+    if let Some(name) = matches.value_of("delete-snapshot") {
+        let id = find(&efs, name)?;
+        efs.delete_snapshot(id).map_err(to_io_error)?;
+        println!("snapshot {} deleted", name);
+    }
+    if matches.is_present("list-snapshots") {
+        for (id, name) in efs.snapshots() {
+            println!("{}\t{}", id, name);
+        }
+    }
+    Ok(())
+}
+
 #[test]
 fn efs_test() -> std::io::Result<()> {
+    let _guard = TEST_LOCK.lock().unwrap();
     let block_file = Arc::new(BlockFile(Mutex::new({
         let f = OpenOptions::new()
             .read(true)
@@ -99,21 +302,40 @@ fn efs_test() -> std::io::Result<()> {
         f.set_len(8192 * 512).unwrap();
         f
     })));
-    EasyFileSystem::create(block_file.clone(), 4096, 1);
+    EasyFileSystem::create(block_file.clone(), 4096, 1, false);
     let efs = EasyFileSystem::open(block_file.clone());
     // 根目录的inode
     let root_inode = EasyFileSystem::root_inode(&efs);
     root_inode.create("filea");
     root_inode.create("fileb");
-    for name in root_inode.ls() {
+    for name in root_inode.ls().unwrap() {
         println!("{}", name);
     }
-    let filea = root_inode.find("filea").unwrap();
+    // 子目录
+    let dir = root_inode.mkdir("dir").unwrap();
+    assert!(dir.is_dir());
+    assert!(root_inode.mkdir("dir").is_none());
+    dir.create("filec").unwrap();
+    assert_eq!(dir.ls().unwrap(), vec![String::from("filec")]);
+    assert!(root_inode
+        .find("dir")
+        .unwrap()
+        .unwrap()
+        .find("filec")
+        .unwrap()
+        .is_some());
+    assert!(root_inode.find("filec").unwrap().is_none());
+    // 命名管道
+    let fifo = dir.mkfifo("fifo").unwrap();
+    assert!(fifo.is_fifo() && !fifo.is_dir());
+    assert!(dir.find("fifo").unwrap().unwrap().is_fifo());
+    assert!(!dir.find("filec").unwrap().unwrap().is_fifo());
+    let filea = root_inode.find("filea").unwrap().unwrap();
     let greet_str = "Hello, world!";
-    filea.write_at(0, greet_str.as_bytes());
+    filea.write_at(0, greet_str.as_bytes()).unwrap();
     //let mut buffer = [0u8; 512];
     let mut buffer = [0u8; 233];
-    let len = filea.read_at(0, &mut buffer);
+    let len = filea.read_at(0, &mut buffer).unwrap();
     assert_eq!(greet_str, core::str::from_utf8(&buffer[..len]).unwrap(),);
 
     let mut random_str_test = |len: usize| {
@@ -125,7 +347,7 @@ fn efs_test() -> std::io::Result<()> {
         );
         filea.clear();
         println!("file clear");
-        assert_eq!(filea.read_at(0, &mut buffer), 0);
+        assert_eq!(filea.read_at(0, &mut buffer), Ok(0));
         let mut str = String::new();
         use rand;
         // random digit
@@ -133,12 +355,14 @@ fn efs_test() -> std::io::Result<()> {
             str.push(char::from('0' as u8 + rand::random::<u8>() % 10));
         }
         println!("str len: {}", str.len());
-        filea.write_at(0, str.as_bytes());
+        filea.write_at(0, str.as_bytes()).unwrap();
+        // 写回数据块和各级索引块
+        assert!(filea.fsync());
         let mut read_buffer = [0u8; 127];
         let mut offset = 0usize;
         let mut read_str = String::new();
         loop {
-            let len = filea.read_at(offset, &mut read_buffer);
+            let len = filea.read_at(offset, &mut read_buffer).unwrap();
             println!("offset = {}, len = {}", offset, len);
             if len == 0 {
                 break;
@@ -175,6 +399,284 @@ fn efs_test() -> std::io::Result<()> {
     random_str_test(400 * BLOCK_SZ);
     random_str_test(1000 * BLOCK_SZ);
     random_str_test(2000 * BLOCK_SZ);
+    assert!(root_inode.sync_fs());
+
+    Ok(())
+}
+
+#[test]
+fn efs_bitmap_test() -> std::io::Result<()> {
+    let _guard = TEST_LOCK.lock().unwrap();
+    let block_file = Arc::new(BlockFile(Mutex::new({
+        let f = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open("fs-bitmap.img")?;
+        f.set_len(2048 * 512).unwrap();
+        f
+    })));
+    EasyFileSystem::create(block_file.clone(), 2048, 1, false);
+    let efs = EasyFileSystem::open(block_file.clone());
+    let start = efs.lock().alloc_data_contiguous(4).unwrap();
+    assert_eq!(efs.lock().alloc_data(), start + 4);
+    // next-fit 释放的块在游标之前 不会被立即复用
+    for block_id in start..start + 4 {
+        efs.lock().dealloc_data(block_id);
+    }
+    assert_eq!(efs.lock().alloc_data(), start + 5);
+
+    // 游标保存在超级块中 重新打开后从上次的位置继续
+    let efs = EasyFileSystem::open(block_file.clone());
+    assert_eq!(efs.lock().alloc_data(), start + 6);
+
+    // 占满之后 游标之前释放的连续块要回绕查找
+    while efs.lock().alloc_data_contiguous(1).is_some() {}
+    for block_id in start + 1..start + 4 {
+        efs.lock().dealloc_data(block_id);
+    }
+    assert_eq!(efs.lock().alloc_data_contiguous(4), None);
+    assert_eq!(efs.lock().alloc_data_contiguous(3), Some(start + 1));
+    assert_eq!(efs.lock().alloc_data_contiguous(1), None);
+    std::fs::remove_file("fs-bitmap.img")?;
+    Ok(())
+}
+
+#[test]
+fn efs_checksum_test() -> std::io::Result<()> {
+    use easy_fs::FsError;
+    let _guard = TEST_LOCK.lock().unwrap();
+    let block_file = Arc::new(BlockFile(Mutex::new({
+        let f = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open("fs-checksum.img")?;
+        f.set_len(4096 * 512).unwrap();
+        f
+    })));
+    EasyFileSystem::create(block_file.clone(), 4096, 1, true);
+    let efs = EasyFileSystem::open(block_file.clone());
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    let dir = root_inode.mkdir("dir").unwrap();
+    dir.create("dirent-marker!").unwrap();
+    let filea = root_inode.create("filea").unwrap();
+    // 写入超过块缓存容量的数据 让第一块被换出
+    let mut data = vec![0u8; 64 * BLOCK_SZ];
+    data[..16].copy_from_slice(b"checksum-marker!");
+    filea.write_at(0, &data).unwrap();
+    let mut buffer = [0u8; BLOCK_SZ];
+    assert_eq!(filea.read_at(BLOCK_SZ, &mut buffer), Ok(BLOCK_SZ));
+
+    // 直接在镜像中翻转以marker开头的块的一个字节
+    let corrupt = |marker: &[u8]| {
+        let block_id = (0..4096)
+            .find(|block_id| {
+                let mut block = [0u8; BLOCK_SZ];
+                block_file.read_block(*block_id, &mut block);
+                block.starts_with(marker)
+            })
+            .unwrap();
+        let mut block = [0u8; BLOCK_SZ];
+        block_file.read_block(block_id, &mut block);
+        block[0] ^= 1;
+        block_file.write_block(block_id, &block);
+    };
+    corrupt(b"checksum-marker!");
+    corrupt(b"dirent-marker!");
+
+    assert_eq!(filea.read_at(0, &mut buffer), Err(FsError::Corrupted));
+    assert_eq!(filea.read_at(BLOCK_SZ, &mut buffer), Ok(BLOCK_SZ));
+    // 目录项损坏不能当作找不到
+    assert_eq!(dir.find("dirent-marker!").err(), Some(FsError::Corrupted));
+    assert_eq!(dir.ls(), Err(FsError::Corrupted));
+
+    // 损坏的块拒绝部分修改 写回和换出后仍然报错
+    assert_eq!(filea.write_at(1, b"x"), Err(FsError::Corrupted));
+    assert!(root_inode.sync_fs());
+    for i in 1..32 {
+        assert_eq!(filea.read_at(i * BLOCK_SZ, &mut buffer), Ok(BLOCK_SZ));
+    }
+    assert_eq!(filea.read_at(0, &mut buffer), Err(FsError::Corrupted));
+    // 整块覆盖后恢复正常
+    assert_eq!(filea.write_at(0, &[1u8; BLOCK_SZ]), Ok(BLOCK_SZ));
+    assert_eq!(filea.read_at(0, &mut buffer), Ok(BLOCK_SZ));
+    assert_eq!(buffer, [1u8; BLOCK_SZ]);
+    std::fs::remove_file("fs-checksum.img")?;
+    Ok(())
+}
+
+#[test]
+fn efs_snapshot_test() -> std::io::Result<()> {
+    let _guard = TEST_LOCK.lock().unwrap();
+    let block_file = Arc::new(BlockFile(Mutex::new({
+        let f = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open("fs-snapshot.img")?;
+        f.set_len(4096 * 512).unwrap();
+        f
+    })));
+    EasyFileSystem::create(block_file.clone(), 4096, 1, false);
+    let efs = EasyFileSystem::open(block_file.clone());
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    // 跨过一级和二级索引的文件
+    let origin: Vec<u8> = (0..200 * BLOCK_SZ).map(|i| (i % 251) as u8).collect();
+    let filea = root_inode.create("filea").unwrap();
+    filea.write_at(0, &origin).unwrap();
+    let id = efs.lock().snapshot("base").unwrap();
+    assert_eq!(efs.lock().snapshots(), vec![(id, String::from("base"))]);
+
+    // 快照之后的修改
+    filea.write_at(BLOCK_SZ * 150 + 3, b"changed").unwrap();
+    filea.write_at(origin.len(), b"appended").unwrap();
+    root_inode.create("fileb").unwrap();
+
+    efs.lock().rollback(id).unwrap();
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    assert!(root_inode.find("fileb").unwrap().is_none());
+    let filea = root_inode.find("filea").unwrap().unwrap();
+    let mut buffer = vec![0u8; origin.len() + BLOCK_SZ];
+    assert_eq!(filea.read_at(0, &mut buffer), Ok(origin.len()));
+    assert_eq!(&buffer[..origin.len()], origin.as_slice());
+
+    efs.lock().delete_snapshot(id).unwrap();
+    assert!(efs.lock().snapshots().is_empty());
+    // 删除快照后释放的块可以重新使用
+    for i in 0..8 {
+        let file = root_inode.create(&format!("file{}", i)).unwrap();
+        file.write_at(0, &origin).unwrap();
+    }
+    assert_eq!(filea.read_at(0, &mut buffer), Ok(origin.len()));
+    assert_eq!(&buffer[..origin.len()], origin.as_slice());
+    std::fs::remove_file("fs-snapshot.img")?;
+    Ok(())
+}
+
+#[test]
+fn efs_grow_test() -> std::io::Result<()> {
+    let _guard = TEST_LOCK.lock().unwrap();
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open("fs-grow.img")?;
+    file.set_len(2048 * 512).unwrap();
+    let block_file = Arc::new(BlockFile(Mutex::new(file)));
+    EasyFileSystem::create(block_file.clone(), 2048, 1, true);
+    let efs = EasyFileSystem::open(block_file.clone());
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    let origin: Vec<u8> = (0..600 * BLOCK_SZ).map(|i| (i % 253) as u8).collect();
+    let filea = root_inode.create("filea").unwrap();
+    filea.write_at(0, &origin).unwrap();
+
+    // 原有的数据区已经放不下第二个文件
+    block_file.0.lock().unwrap().set_len(8192 * 512)?;
+    efs.lock().grow(8192, 1).unwrap();
+    let fileb = root_inode.create("fileb").unwrap();
+    fileb.write_at(0, &origin).unwrap();
+    fileb.write_at(origin.len(), &origin).unwrap();
+
+    let efs = EasyFileSystem::open(block_file.clone());
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    let mut buffer = vec![0u8; origin.len()];
+    let filea = root_inode.find("filea").unwrap().unwrap();
+    assert_eq!(filea.read_at(0, &mut buffer), Ok(origin.len()));
+    assert_eq!(buffer, origin);
+    let fileb = root_inode.find("fileb").unwrap().unwrap();
+    for offset in [0, origin.len()] {
+        assert_eq!(fileb.read_at(offset, &mut buffer), Ok(origin.len()));
+        assert_eq!(buffer, origin);
+    }
+    // 有快照时不能扩容
+    efs.lock().snapshot("base").unwrap();
+    assert_eq!(efs.lock().check_grow(16384, 0), Err(easy_fs::FsError::Busy));
+    assert_eq!(efs.lock().grow(16384, 0), Err(easy_fs::FsError::Busy));
+    assert!(mib_to_blocks("2097151").is_ok());
+    assert!(mib_to_blocks("2097152").is_err());
+    std::fs::remove_file("fs-grow.img")?;
+    Ok(())
+}
+
+#[test]
+fn efs_xattr_test() -> std::io::Result<()> {
+    use easy_fs::FsError;
+    let _guard = TEST_LOCK.lock().unwrap();
+    let block_file = Arc::new(BlockFile(Mutex::new({
+        let f = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open("fs-xattr.img")?;
+        f.set_len(4096 * 512).unwrap();
+        f
+    })));
+    EasyFileSystem::create(block_file.clone(), 4096, 1, false);
+    let efs = EasyFileSystem::open(block_file.clone());
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    let filea = root_inode.create("filea").unwrap();
+    filea.write_at(0, b"data").unwrap();
+    assert_eq!(filea.get_xattr("user.exit_code"), Err(FsError::NotFound));
+    assert!(filea.list_xattr().unwrap().is_empty());
+
+    filea.set_xattr("user.exit_code", b"-11").unwrap();
+    filea.set_xattr("user.timeout", b"1000").unwrap();
+    filea.set_xattr("user.exit_code", b"-6").unwrap();
+    assert_eq!(filea.get_xattr("user.exit_code"), Ok(b"-6".to_vec()));
+    assert_eq!(
+        filea.list_xattr().unwrap(),
+        vec![String::from("user.exit_code"), String::from("user.timeout")]
+    );
+    assert_eq!(
+        filea.set_xattr("user.big", &[0u8; BLOCK_SZ]),
+        Err(FsError::NoSpace)
+    );
+    assert_eq!(filea.set_xattr("", b""), Err(FsError::NameTooLong));
+
+    // 重新打开后属性仍在 文件内容不受影响
+    drop(efs);
+    let efs = EasyFileSystem::open(block_file.clone());
+    let filea = EasyFileSystem::root_inode(&efs)
+        .find("filea")
+        .unwrap()
+        .unwrap();
+    assert_eq!(filea.get_xattr("user.timeout"), Ok(b"1000".to_vec()));
+    let mut buffer = [0u8; 8];
+    assert_eq!(filea.read_at(0, &mut buffer), Ok(4));
+    assert_eq!(
+        filea.update_xattr("user.timeout", b"1", true, false),
+        Err(FsError::AlreadyExists)
+    );
+    assert_eq!(
+        filea.update_xattr("user.missing", b"1", false, true),
+        Err(FsError::NotFound)
+    );
+
+    // 快照之后的修改在回滚后消失
+    let id = efs.lock().snapshot("base").unwrap();
+    filea.set_xattr("user.timeout", b"2000").unwrap();
+    let fileb = EasyFileSystem::root_inode(&efs).create("fileb").unwrap();
+    fileb.set_xattr("user.exit_code", b"0").unwrap();
+    efs.lock().rollback(id).unwrap();
+    efs.lock().delete_snapshot(id).unwrap();
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    assert!(root_inode.find("fileb").unwrap().is_none());
+    let filea = root_inode.find("filea").unwrap().unwrap();
+    assert_eq!(filea.get_xattr("user.timeout"), Ok(b"1000".to_vec()));
+    // 回滚释放的inode重新分配后不会带上旧的属性
+    let fileb = root_inode.create("fileb").unwrap();
+    assert!(fileb.list_xattr().unwrap().is_empty());
 
+    filea.remove_xattr("user.timeout").unwrap();
+    assert_eq!(filea.remove_xattr("user.timeout"), Err(FsError::NotFound));
+    filea.remove_xattr("user.exit_code").unwrap();
+    assert!(filea.list_xattr().unwrap().is_empty());
+    std::fs::remove_file("fs-xattr.img")?;
     Ok(())
 }
@@ -1,4 +1,4 @@
-use crate::{block_cache::get_block_cache, block_dev::BlockDevice, BLOCK_SZ};
+use crate::{block_cache::get_block_cache, block_dev::BlockDevice, layout::SuperBlock, BLOCK_SZ};
 use alloc::sync::Arc;
 
 /// 512字节的比特数
@@ -11,6 +11,10 @@ pub struct Bitmap {
     start_block_id: usize,
     /// 位图所占的块数
     blocks: usize,
+    /// next-fit游标 下一次申请从这里开始查找
+    next: usize,
+    /// 游标保存在超级块bitmap_cursors中的位置 None表示不保存
+    cursor_slot: Option<usize>,
 }
 
 impl Bitmap {
@@ -19,30 +23,117 @@ impl Bitmap {
         Self {
             start_block_id,
             blocks,
+            next: 0,
+            cursor_slot: None,
         }
     }
 
+    /// 新建游标保存在超级块中的位图 next为超级块中记录的游标
+    pub fn with_cursor(start_block_id: usize, blocks: usize, slot: usize, next: usize) -> Self {
+        let mut bitmap = Self::new(start_block_id, blocks);
+        bitmap.next = next % bitmap.maximum().max(1);
+        bitmap.cursor_slot = Some(slot);
+        bitmap
+    }
+
     /// 位图申请bit 返回bit位置
-    pub fn alloc(&self, block_device: &Arc<dyn BlockDevice>) -> Option<usize> {
-        for block_id in 0..self.blocks {
-            let pos = get_block_cache(block_id + self.start_block_id, Arc::clone(block_device))
+    pub fn alloc(&mut self, block_device: &Arc<dyn BlockDevice>) -> Option<usize> {
+        self.alloc_contiguous(block_device, 1)
+    }
+
+    /// 申请连续的n个bit 返回第一个bit位置
+    ///
+    /// 从游标处开始查找 找不到再从头查找到游标处
+    pub fn alloc_contiguous(
+        &mut self,
+        block_device: &Arc<dyn BlockDevice>,
+        n: usize,
+    ) -> Option<usize> {
+        self.alloc_contiguous_except(block_device, n, |_| false)
+    }
+
+    /// 申请连续的n个bit 跳过reserved返回true的bit
+    pub fn alloc_contiguous_except(
+        &mut self,
+        block_device: &Arc<dyn BlockDevice>,
+        n: usize,
+        reserved: impl Fn(usize) -> bool,
+    ) -> Option<usize> {
+        let maximum = self.maximum();
+        if n == 0 || n > maximum {
+            return None;
+        }
+        let start = self
+            .find_free_run(block_device, self.next, maximum, n, &reserved)
+            .or_else(|| {
+                self.find_free_run(
+                    block_device,
+                    0,
+                    (self.next + n - 1).min(maximum),
+                    n,
+                    &reserved,
+                )
+            })?;
+        for bit in start..start + n {
+            self.set(block_device, bit);
+        }
+        self.next = (start + n) % maximum;
+        if let Some(slot) = self.cursor_slot {
+            let next = self.next as u32;
+            get_block_cache(0, Arc::clone(block_device)).lock().modify(
+                0,
+                |super_block: &mut SuperBlock| {
+                    super_block.bitmap_cursors[slot] = next;
+                },
+            );
+        }
+        Some(start)
+    }
+
+    /// 在[from, to)中查找连续n个空闲bit
+    fn find_free_run(
+        &self,
+        block_device: &Arc<dyn BlockDevice>,
+        from: usize,
+        to: usize,
+        n: usize,
+        reserved: &impl Fn(usize) -> bool,
+    ) -> Option<usize> {
+        let mut run_start = from;
+        let mut run_len = 0;
+        let mut bit = from;
+        while bit < to {
+            let block_pos = bit / BLOCK_BITS;
+            let block_end = ((block_pos + 1) * BLOCK_BITS).min(to);
+            let found = get_block_cache(block_pos + self.start_block_id, Arc::clone(block_device))
                 .lock()
-                .modify(0, |bitmap_block: &mut BitmapBlock| {
-                    //  512字节的块被解析为BitmapBlock格式
-                    if let Some((bits64_pos, inner_pos)) = bitmap_block
-                        .iter()
-                        .enumerate()
-                        .find(|(_, bits64)| **bits64 != u64::MAX)
-                        .map(|(bits64_pos, bits64)| (bits64_pos, bits64.trailing_ones() as usize))
-                    {
-                        bitmap_block[bits64_pos] |= 1u64 << inner_pos;
-                        Some(block_id * BLOCK_BITS + bits64_pos * 64 + inner_pos as usize)
-                    } else {
-                        None
+                .read(0, |bitmap_block: &BitmapBlock| {
+                    while bit < block_end {
+                        let (_, bits64_pos, inner_pos) = decomposition(bit);
+                        // 整个u64都已占用 直接跳过
+                        if inner_pos == 0 && bitmap_block[bits64_pos] == u64::MAX {
+                            run_len = 0;
+                            bit += 64;
+                            run_start = bit;
+                            continue;
+                        }
+                        let used =
+                            bitmap_block[bits64_pos] & (1u64 << inner_pos) > 0 || reserved(bit);
+                        bit += 1;
+                        if used {
+                            run_len = 0;
+                            run_start = bit;
+                        } else {
+                            run_len += 1;
+                            if run_len == n {
+                                return true;
+                            }
+                        }
                     }
+                    false
                 });
-            if pos.is_some() {
-                return pos;
+            if found {
+                return Some(run_start);
             }
         }
         None
@@ -58,6 +149,26 @@ impl Bitmap {
             });
     }
 
+    /// 判断bit是否已被占用
+    pub fn test(&self, block_device: &Arc<dyn BlockDevice>, bit: usize) -> bool {
+        let (block_pos, bits64_pos, inner_pos) = decomposition(bit);
+        get_block_cache(block_pos + self.start_block_id, Arc::clone(block_device))
+            .lock()
+            .read(0, |bitmap_block: &BitmapBlock| {
+                bitmap_block[bits64_pos] & (1u64 << inner_pos) > 0
+            })
+    }
+
+    /// 直接标记bit为占用
+    pub fn set(&self, block_device: &Arc<dyn BlockDevice>, bit: usize) {
+        let (block_pos, bits64_pos, inner_pos) = decomposition(bit);
+        get_block_cache(block_pos + self.start_block_id, Arc::clone(block_device))
+            .lock()
+            .modify(0, |bitmap_block: &mut BitmapBlock| {
+                bitmap_block[bits64_pos] |= 1u64 << inner_pos;
+            });
+    }
+
     /// Get the max number of allocatable blocks
     pub fn maximum(&self) -> usize {
         self.blocks * BLOCK_BITS
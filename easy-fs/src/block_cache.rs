@@ -1,4 +1,4 @@
-use crate::{block_dev::BlockDevice, BLOCK_SZ};
+use crate::{block_dev::BlockDevice, checksum::ChecksumArea, error::FsError, BLOCK_SZ};
 use alloc::collections::VecDeque;
 use alloc::sync::Arc;
 use alloc::vec;
@@ -23,15 +23,30 @@ pub fn get_block_cache(
         .get_block_cache(block_id, block_device)
 }
 
+/// 经过块缓存读取整块 块设备文件和已挂载的文件系统看到一致的内容
+pub fn block_cache_read(block_id: usize, block_device: Arc<dyn BlockDevice>, buf: &mut [u8]) {
+    get_block_cache(block_id, block_device)
+        .lock()
+        .read(0, |block: &[u8; BLOCK_SZ]| buf.copy_from_slice(block));
+}
+
+/// 设置各数据块组的校验区 为空表示不做校验
+pub fn set_checksum_areas(checksum: Vec<ChecksumArea>) {
+    BLOCK_CACHE_MANAGER.lock().checksum = checksum;
+}
+
 pub struct BlockCacheManager {
     /// 块编号 块缓冲区
     queue: VecDeque<(usize, Arc<Mutex<BlockCache>>)>,
+    /// 数据块校验区
+    checksum: Vec<ChecksumArea>,
 }
 
 impl BlockCacheManager {
     pub fn new() -> Self {
         Self {
             queue: VecDeque::new(),
+            checksum: Vec::new(),
         }
     }
 
@@ -59,6 +74,10 @@ impl BlockCacheManager {
             let block_cache = Arc::new(Mutex::new(BlockCache::new(
                 block_id,
                 Arc::clone(&block_device),
+                self.checksum
+                    .iter()
+                    .find(|checksum| checksum.contains(block_id))
+                    .copied(),
             )));
             self.queue.push_back((block_id, Arc::clone(&block_cache)));
             block_cache
@@ -74,18 +93,31 @@ pub struct BlockCache {
     block_device: Arc<dyn BlockDevice>,
     /// 是否修改过
     modified: bool,
+    /// 数据块校验区
+    checksum: Option<ChecksumArea>,
+    /// 加载时校验和不匹配 写回时不更新校验和 保持损坏状态
+    corrupted: bool,
 }
 
 impl BlockCache {
     /// 从磁盘加载一块内存到缓冲区
-    pub fn new(block_id: usize, block_device: Arc<dyn BlockDevice>) -> Self {
+    pub fn new(
+        block_id: usize,
+        block_device: Arc<dyn BlockDevice>,
+        checksum: Option<ChecksumArea>,
+    ) -> Self {
         let mut cache = vec![0u8; BLOCK_SZ];
         block_device.read_block(block_id, &mut cache);
+        let corrupted = checksum.map_or(false, |checksum| {
+            !checksum.verify(block_id, &cache, &block_device)
+        });
         Self {
             cache,
             block_id,
             block_device,
             modified: false,
+            checksum,
+            corrupted,
         }
     }
 
@@ -93,10 +125,44 @@ impl BlockCache {
         f(self.get_ref(offset))
     }
 
+    /// 读取前检查校验和
+    pub fn checked_read<T, V>(&self, offset: usize, f: impl FnOnce(&T) -> V) -> Result<V, FsError> {
+        if self.corrupted {
+            return Err(FsError::Corrupted);
+        }
+        Ok(self.read(offset, f))
+    }
+
     pub fn modify<T, V>(&mut self, offset: usize, f: impl FnOnce(&mut T) -> V) -> V {
         f(self.get_mut(offset))
     }
 
+    /// 修改前检查校验和 损坏的块拒绝修改
+    pub fn checked_modify<T, V>(
+        &mut self,
+        offset: usize,
+        f: impl FnOnce(&mut T) -> V,
+    ) -> Result<V, FsError> {
+        if self.corrupted {
+            return Err(FsError::Corrupted);
+        }
+        Ok(self.modify(offset, f))
+    }
+
+    /// 整块重写 旧内容不再有用 损坏标记随之清除
+    pub fn overwrite(&mut self, data: &[u8]) {
+        self.cache.copy_from_slice(data);
+        self.modified = true;
+        self.corrupted = false;
+    }
+
+    /// 复制另一块的内容 损坏标记一起复制
+    pub fn copy_from(&mut self, src: &BlockCache) {
+        self.cache.copy_from_slice(&src.cache);
+        self.modified = true;
+        self.corrupted = src.corrupted;
+    }
+
     /// 获取缓冲区指定偏移量字节地址
     fn addr_of_offset(&self, offset: usize) -> usize {
         &self.cache[offset] as *const _ as usize
@@ -126,10 +192,15 @@ impl BlockCache {
     }
 
     /// 回收资源时将数据写入到磁盘
+    ///
+    /// 损坏的块不更新校验和 否则会按错误的内容重新计算 掩盖损坏
     pub fn sync(&mut self) {
         if self.modified {
             self.modified = false;
             self.block_device.write_block(self.block_id, &self.cache);
+            if let (Some(checksum), false) = (self.checksum, self.corrupted) {
+                checksum.update(self.block_id, &self.cache, &self.block_device);
+            }
         }
     }
 }
@@ -140,6 +211,16 @@ impl Drop for BlockCache {
     }
 }
 
+/// 只写回指定的块 不在缓存中的块已经写回过
+pub fn block_cache_sync(block_ids: &[usize]) {
+    let manager = BLOCK_CACHE_MANAGER.lock();
+    for (block_id, cache) in manager.queue.iter() {
+        if block_ids.contains(block_id) {
+            cache.lock().sync();
+        }
+    }
+}
+
 pub fn block_cache_sync_all() {
     let manager = BLOCK_CACHE_MANAGER.lock();
     for (_, cache) in manager.queue.iter() {
@@ -5,4 +5,12 @@ pub trait BlockDevice: Send + Sync + Any {
     fn read_block(&self, block_id: usize, buf: &mut [u8]);
     /// 将内存区数据写入到编号为block_id的磁盘块
     fn write_block(&self, block_id: usize, buf: &[u8]);
+    /// 设备的总块数 未知时返回None
+    fn num_blocks(&self) -> Option<usize> {
+        None
+    }
+    /// 把设备自己缓存的写入落到存储介质上 不能保证落盘时返回false
+    fn flush(&self) -> bool {
+        false
+    }
 }
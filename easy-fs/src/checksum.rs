@@ -0,0 +1,98 @@
+use crate::{block_dev::BlockDevice, BLOCK_SZ};
+use alloc::sync::Arc;
+
+/// 每个校验块可容纳的校验和个数
+const CHECKSUMS_PER_BLOCK: usize = BLOCK_SZ / 4;
+
+/// CRC32(IEEE)查找表
+const CRC32_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut j = 0;
+        while j < 8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+            j += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+pub fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0u32, |crc, byte| {
+        CRC32_TABLE[((crc ^ *byte as u32) & 0xff) as usize] ^ (crc >> 8)
+    })
+}
+
+/// 校验区 位于数据块组的数据区之后 每个数据块对应一个u32校验和
+///
+/// 校验和为0表示尚未记录 读取时不做检查
+#[derive(Clone, Copy)]
+pub struct ChecksumArea {
+    /// 校验区开始的块号
+    pub start_block: usize,
+    /// 数据区开始的块号
+    pub data_start_block: usize,
+    /// 数据区块数
+    pub data_blocks: usize,
+}
+
+impl ChecksumArea {
+    /// 校验区所需块数
+    pub fn blocks_for(data_blocks: usize) -> usize {
+        (data_blocks + CHECKSUMS_PER_BLOCK - 1) / CHECKSUMS_PER_BLOCK
+    }
+
+    pub fn contains(&self, block_id: usize) -> bool {
+        block_id >= self.data_start_block && block_id < self.data_start_block + self.data_blocks
+    }
+
+    /// 数据块对应校验和的位置 (校验块号, 块内偏移)
+    fn position(&self, block_id: usize) -> Option<(usize, usize)> {
+        if !self.contains(block_id) {
+            return None;
+        }
+        let index = block_id - self.data_start_block;
+        Some((
+            self.start_block + index / CHECKSUMS_PER_BLOCK,
+            (index % CHECKSUMS_PER_BLOCK) * 4,
+        ))
+    }
+
+    /// 检查块内容是否与记录的校验和一致
+    ///
+    /// 校验块不经过块缓存 直接读写设备
+    pub fn verify(
+        &self,
+        block_id: usize,
+        data: &[u8],
+        block_device: &Arc<dyn BlockDevice>,
+    ) -> bool {
+        match self.position(block_id) {
+            Some((checksum_block, offset)) => {
+                let mut buf = [0u8; BLOCK_SZ];
+                block_device.read_block(checksum_block, &mut buf);
+                let stored = u32::from_le_bytes(buf[offset..offset + 4].try_into().unwrap());
+                stored == 0 || stored == crc32(data)
+            }
+            None => true,
+        }
+    }
+
+    /// 写回数据块时更新校验和
+    pub fn update(&self, block_id: usize, data: &[u8], block_device: &Arc<dyn BlockDevice>) {
+        if let Some((checksum_block, offset)) = self.position(block_id) {
+            let mut buf = [0u8; BLOCK_SZ];
+            block_device.read_block(checksum_block, &mut buf);
+            buf[offset..offset + 4].copy_from_slice(&crc32(data).to_le_bytes());
+            block_device.write_block(checksum_block, &buf);
+        }
+    }
+}
@@ -1,59 +1,88 @@
 use crate::{
     bitmap::Bitmap,
-    block_cache::{block_cache_sync_all, get_block_cache},
+    block_cache::{block_cache_sync_all, get_block_cache, set_checksum_areas},
     block_dev::BlockDevice,
-    layout::{DiskInode, DiskInodeType, SuperBlock},
+    checksum::ChecksumArea,
+    error::FsError,
+    layout::{
+        BlockGroup, DiskInode, DiskInodeType, SnapshotEntry, SnapshotTable, SuperBlock, GROUP_DATA,
+        GROUP_INODE, MAX_GROUPS, SNAPSHOT_NAME_LIMIT, SNAPSHOT_TABLE_OFFSET,
+    },
     vfs::Inode,
     BLOCK_SZ,
 };
-use alloc::sync::Arc;
+use alloc::{string::String, sync::Arc, vec, vec::Vec};
 use spin::Mutex;
 
 type DataBlock = [u8; BLOCK_SZ];
 
+/// 索引节点组 位图之后紧跟索引节点区
+struct InodeGroup {
+    bitmap: Bitmap,
+    bitmap_start_block: u32,
+    bitmap_blocks: u32,
+    area_start_block: u32,
+    area_blocks: u32,
+    /// 组内第一个索引节点的编号
+    first_inode: u32,
+}
+
+/// 数据块组 位图之后紧跟数据区 开启校验时数据区之后还有校验区
+struct DataGroup {
+    bitmap: Bitmap,
+    bitmap_start_block: u32,
+    bitmap_blocks: u32,
+    area_start_block: u32,
+    area_blocks: u32,
+}
+
+impl DataGroup {
+    fn contains(&self, block_id: u32) -> bool {
+        block_id >= self.area_start_block && block_id < self.area_start_block + self.area_blocks
+    }
+
+    fn checksum_area(&self) -> ChecksumArea {
+        ChecksumArea {
+            start_block: (self.area_start_block + self.area_blocks) as usize,
+            data_start_block: self.area_start_block as usize,
+            data_blocks: self.area_blocks as usize,
+        }
+    }
+}
+
 pub struct EasyFileSystem {
     pub block_device: Arc<dyn BlockDevice>,
-    /// 索引节点位图
-    pub inode_bitmap: Bitmap,
-    /// 数据位图
-    pub data_bitmap: Bitmap,
-    inode_area_start_block: u32,
-    data_area_start_block: u32,
+    /// 索引节点组 第一个是创建时划分的 其余由扩容添加
+    inode_groups: Vec<InodeGroup>,
+    /// 数据块组 同上
+    data_groups: Vec<DataGroup>,
+    /// 下一次申请数据块从这个组开始
+    data_group_cursor: usize,
+    total_blocks: u32,
+    checksum: bool,
+    /// 快照表 与块0中的内容保持一致
+    snapshots: SnapshotTable,
+    /// 扩展属性表所在的inode 旧镜像在第一次使用时创建
+    xattr_inode: Option<u32>,
 }
 
 impl EasyFileSystem {
+    /// checksum为true时在数据区之后划出校验区
     pub fn create(
         block_device: Arc<dyn BlockDevice>,
         total_blocks: u32,
         inode_bitmap_blocks: u32,
+        checksum: bool,
     ) -> Arc<Mutex<Self>> {
-        // 索引节点位图
-        let inode_bitmap = Bitmap::new(1, inode_bitmap_blocks as usize);
-        // 位图的比特数
-        let inode_num = inode_bitmap.maximum();
-        // 索引节点内容所需块数 按512字节对齐 每块可容纳4个索引节点
-        let inode_area_blocks =
-            ((inode_num * core::mem::size_of::<DiskInode>() + BLOCK_SZ - 1) / BLOCK_SZ) as u32;
+        // 初始化期间不做校验
+        set_checksum_areas(Vec::new());
+        // 索引节点内容所需块数
+        let inode_area_blocks = Self::inode_area_blocks(inode_bitmap_blocks);
         // 索引节点所需块数
         let inode_total_blocks = inode_bitmap_blocks + inode_area_blocks;
         // 数据所需块数
-        let data_total_blocks = total_blocks - 1 - inode_total_blocks;
-        // 数据位图所需块数 4097 = 4096(内容块) + 1(位图块)
-        let data_bitmap_blocks = (data_total_blocks + 4096) / 4097;
-        // 数据内容所需块数
-        let data_area_blocks = data_total_blocks - data_bitmap_blocks;
-        // 数据位图
-        let data_bitmap = Bitmap::new(
-            (1 + inode_bitmap_blocks + inode_area_blocks) as usize,
-            data_bitmap_blocks as usize,
-        );
-        let mut efs = Self {
-            block_device: Arc::clone(&block_device),
-            inode_bitmap,
-            data_bitmap,
-            inode_area_start_block: 1 + inode_bitmap_blocks,
-            data_area_start_block: 1 + inode_total_blocks + data_bitmap_blocks,
-        };
+        let (data_bitmap_blocks, data_area_blocks, checksum_blocks) =
+            Self::data_layout(total_blocks - 1 - inode_total_blocks, checksum);
         // 初始化所有块
         for i in 0..total_blocks {
             get_block_cache(i as usize, Arc::clone(&block_device))
@@ -75,7 +104,12 @@ impl EasyFileSystem {
                     data_bitmap_blocks,
                     data_area_blocks,
                 );
+                if checksum {
+                    super_block.enable_checksum(checksum_blocks);
+                }
             });
+        block_cache_sync_all();
+        let mut efs = Self::load(Arc::clone(&block_device));
         assert_eq!(efs.alloc_inode(), 0);
         // 第一个inode设置为根目录
         let (root_inode_block_id, root_inode_offset) = efs.get_disk_inode_pos(0);
@@ -90,24 +124,122 @@ impl EasyFileSystem {
 
     /// 从块0读出efs
     pub fn open(block_device: Arc<dyn BlockDevice>) -> Arc<Mutex<Self>> {
-        get_block_cache(0, Arc::clone(&block_device))
+        Arc::new(Mutex::new(Self::load(block_device)))
+    }
+
+    fn load(block_device: Arc<dyn BlockDevice>) -> Self {
+        let snapshots = get_block_cache(0, Arc::clone(&block_device))
             .lock()
-            .read(0, |super_block: &SuperBlock| {
+            .read(SNAPSHOT_TABLE_OFFSET, |snapshots: &SnapshotTable| {
+                *snapshots
+            });
+        let efs = get_block_cache(0, Arc::clone(&block_device)).lock().read(
+            0,
+            |super_block: &SuperBlock| {
                 assert!(super_block.is_valid(), "Error loading EFS!");
                 let inode_total_blocks =
                     super_block.inode_bitmap_blocks + super_block.inode_area_blocks;
-                let efs = Self {
-                    block_device,
-                    inode_bitmap: Bitmap::new(1, super_block.inode_bitmap_blocks as usize),
-                    data_bitmap: Bitmap::new(
+                let mut inode_groups = vec![InodeGroup {
+                    bitmap: Bitmap::with_cursor(
+                        1,
+                        super_block.inode_bitmap_blocks as usize,
+                        0,
+                        super_block.bitmap_cursors[0] as usize,
+                    ),
+                    bitmap_start_block: 1,
+                    bitmap_blocks: super_block.inode_bitmap_blocks,
+                    area_start_block: 1 + super_block.inode_bitmap_blocks,
+                    area_blocks: super_block.inode_area_blocks,
+                    first_inode: 0,
+                }];
+                let mut data_groups = vec![DataGroup {
+                    bitmap: Bitmap::with_cursor(
                         (1 + inode_total_blocks) as usize,
                         super_block.data_bitmap_blocks as usize,
+                        1,
+                        super_block.bitmap_cursors[1] as usize,
                     ),
-                    inode_area_start_block: 1 + super_block.inode_bitmap_blocks,
-                    data_area_start_block: 1 + inode_total_blocks + super_block.data_bitmap_blocks,
-                };
-                Arc::new(Mutex::new(efs))
-            })
+                    bitmap_start_block: 1 + inode_total_blocks,
+                    bitmap_blocks: super_block.data_bitmap_blocks,
+                    area_start_block: 1 + inode_total_blocks + super_block.data_bitmap_blocks,
+                    area_blocks: super_block.data_area_blocks,
+                }];
+                for (i, group) in super_block.groups[..super_block.group_count as usize]
+                    .iter()
+                    .enumerate()
+                {
+                    let bitmap = Bitmap::with_cursor(
+                        group.bitmap_start_block as usize,
+                        group.bitmap_blocks as usize,
+                        2 + i,
+                        super_block.bitmap_cursors[2 + i] as usize,
+                    );
+                    match group.kind {
+                        GROUP_INODE => {
+                            let last = inode_groups.last().unwrap();
+                            let first_inode = last.first_inode + last.bitmap.maximum() as u32;
+                            inode_groups.push(InodeGroup {
+                                bitmap,
+                                bitmap_start_block: group.bitmap_start_block,
+                                bitmap_blocks: group.bitmap_blocks,
+                                area_start_block: group.area_start_block(),
+                                area_blocks: group.area_blocks,
+                                first_inode,
+                            });
+                        }
+                        GROUP_DATA => data_groups.push(DataGroup {
+                            bitmap,
+                            bitmap_start_block: group.bitmap_start_block,
+                            bitmap_blocks: group.bitmap_blocks,
+                            area_start_block: group.area_start_block(),
+                            area_blocks: group.area_blocks,
+                        }),
+                        _ => panic!("Unknown block group!"),
+                    }
+                }
+                Self {
+                    block_device: Arc::clone(&block_device),
+                    inode_groups,
+                    data_groups,
+                    data_group_cursor: 0,
+                    total_blocks: super_block.total_blocks,
+                    checksum: super_block.has_checksum(),
+                    snapshots,
+                    xattr_inode: super_block.xattr_inode(),
+                }
+            },
+        );
+        set_checksum_areas(if efs.checksum {
+            efs.data_groups
+                .iter()
+                .map(|group| group.checksum_area())
+                .collect()
+        } else {
+            Vec::new()
+        });
+        efs
+    }
+
+    /// 索引节点位图对应的索引节点区块数 按512字节对齐 每块可容纳4个索引节点
+    fn inode_area_blocks(inode_bitmap_blocks: u32) -> u32 {
+        let inode_num = inode_bitmap_blocks as usize * BLOCK_SZ * 8;
+        ((inode_num * core::mem::size_of::<DiskInode>() + BLOCK_SZ - 1) / BLOCK_SZ) as u32
+    }
+
+    /// 划分数据所需的块 返回(位图块数, 数据区块数, 校验区块数)
+    fn data_layout(mut data_total_blocks: u32, checksum: bool) -> (u32, u32, u32) {
+        // 校验区块数 按全部数据块计算 略有富余
+        let checksum_blocks = if checksum {
+            ChecksumArea::blocks_for(data_total_blocks as usize) as u32
+        } else {
+            0
+        };
+        data_total_blocks -= checksum_blocks;
+        // 数据位图所需块数 4097 = 4096(内容块) + 1(位图块)
+        let data_bitmap_blocks = (data_total_blocks + 4096) / 4097;
+        // 数据内容所需块数
+        let data_area_blocks = data_total_blocks - data_bitmap_blocks;
+        (data_bitmap_blocks, data_area_blocks, checksum_blocks)
     }
 
     /// 返回第一个inode 也就是根目录的inode
@@ -117,14 +249,91 @@ impl EasyFileSystem {
         Inode::new(block_id, block_offset, Arc::clone(efs), block_device)
     }
 
-    /// 从inode bitmap申请一个inode
+    /// 从inode bitmap申请一个inode 依次尝试各索引节点组
     pub fn alloc_inode(&mut self) -> u32 {
-        self.inode_bitmap.alloc(&self.block_device).unwrap() as u32
+        let block_device = &self.block_device;
+        self.inode_groups
+            .iter_mut()
+            .find_map(|group| {
+                group
+                    .bitmap
+                    .alloc(block_device)
+                    .map(|bit| group.first_inode + bit as u32)
+            })
+            .unwrap()
+    }
+
+    /// 释放inode
+    pub fn dealloc_inode(&mut self, inode_id: u32) {
+        let group = self
+            .inode_groups
+            .iter()
+            .rev()
+            .find(|group| group.first_inode <= inode_id)
+            .unwrap();
+        group
+            .bitmap
+            .dealloc(&self.block_device, (inode_id - group.first_inode) as usize);
+    }
+
+    /// 扩展属性表所在的inode 还没有时新建一个
+    pub fn create_xattr_table(&mut self) -> u32 {
+        if let Some(inode_id) = self.xattr_inode {
+            return inode_id;
+        }
+        let inode_id = self.alloc_inode();
+        let (block_id, block_offset) = self.get_disk_inode_pos(inode_id);
+        get_block_cache(block_id as usize, Arc::clone(&self.block_device))
+            .lock()
+            .modify(block_offset, |disk_inode: &mut DiskInode| {
+                disk_inode.initialize(DiskInodeType::File);
+            });
+        get_block_cache(0, Arc::clone(&self.block_device))
+            .lock()
+            .modify(0, |super_block: &mut SuperBlock| {
+                super_block.enable_xattr(inode_id);
+            });
+        self.xattr_inode = Some(inode_id);
+        inode_id
+    }
+
+    /// 扩展属性表所在的inode
+    pub fn xattr_table(&self) -> Option<u32> {
+        self.xattr_inode
     }
 
     /// 申请一个数据块 返回绝对编号
     pub fn alloc_data(&mut self) -> u32 {
-        self.data_bitmap.alloc(&self.block_device).unwrap() as u32 + self.data_area_start_block
+        self.alloc_data_contiguous(1).unwrap()
+    }
+
+    /// 申请连续的n个数据块 返回第一块的绝对编号
+    ///
+    /// 被快照引用的块即使在当前位图中空闲也不会分配
+    pub fn alloc_data_contiguous(&mut self, n: u32) -> Option<u32> {
+        let frozen = self.frozen_bitmaps();
+        let block_device = &self.block_device;
+        let group_count = self.data_groups.len();
+        for i in 0..group_count {
+            let group_id = (self.data_group_cursor + i) % group_count;
+            let group = &mut self.data_groups[group_id];
+            // 位图最后一块中超出数据区的bit不可用
+            let area_blocks = group.area_blocks as usize;
+            let bit = group
+                .bitmap
+                .alloc_contiguous_except(block_device, n as usize, |bit| {
+                    bit >= area_blocks
+                        || frozen
+                            .iter()
+                            .any(|bitmaps| bitmaps[group_id].test(block_device, bit))
+                });
+            if let Some(bit) = bit {
+                let block_id = group.area_start_block + bit as u32;
+                self.data_group_cursor = group_id;
+                return Some(block_id);
+            }
+        }
+        None
     }
 
     pub fn dealloc_data(&mut self, block_id: u32) {
@@ -135,24 +344,346 @@ impl EasyFileSystem {
                     *p == 0;
                 })
             });
-        self.data_bitmap.dealloc(
-            &self.block_device,
-            (block_id - self.data_area_start_block) as usize,
-        )
+        let (group_id, bit) = self.data_bit(block_id);
+        self.data_groups[group_id]
+            .bitmap
+            .dealloc(&self.block_device, bit)
+    }
+
+    /// 数据块所在的组和组内bit位置
+    fn data_bit(&self, block_id: u32) -> (usize, usize) {
+        let group_id = self
+            .data_groups
+            .iter()
+            .position(|group| group.contains(block_id))
+            .unwrap();
+        let bit = (block_id - self.data_groups[group_id].area_start_block) as usize;
+        (group_id, bit)
+    }
+
+    /// 扩容到new_total_blocks块 设备需已能容纳这么多块
+    ///
+    /// inode_bitmap_blocks大于0时先追加一个索引节点组 剩余的块作为数据块组
+    /// 已有的块号都保持不变
+    pub fn grow(&mut self, new_total_blocks: u32, inode_bitmap_blocks: u32) -> Result<(), FsError> {
+        let groups = self.grow_groups(new_total_blocks, inode_bitmap_blocks)?;
+        // 新增的块不在任何校验区内 可以直接经过块缓存清零
+        for i in self.total_blocks..new_total_blocks {
+            get_block_cache(i as usize, Arc::clone(&self.block_device))
+                .lock()
+                .modify(0, |data_block: &mut DataBlock| {
+                    for byte in data_block.iter_mut() {
+                        *byte = 0;
+                    }
+                });
+        }
+        get_block_cache(0, Arc::clone(&self.block_device))
+            .lock()
+            .modify(0, |super_block: &mut SuperBlock| {
+                for group in groups.iter() {
+                    super_block.add_group(*group);
+                }
+            });
+        block_cache_sync_all();
+        *self = Self::load(Arc::clone(&self.block_device));
+        Ok(())
+    }
+
+    /// 检查能否扩容到new_total_blocks块 不修改设备 用于扩展设备之前
+    pub fn check_grow(
+        &self,
+        new_total_blocks: u32,
+        inode_bitmap_blocks: u32,
+    ) -> Result<(), FsError> {
+        self.grow_groups(new_total_blocks, inode_bitmap_blocks)
+            .map(|_| ())
+    }
+
+    /// 扩容需要追加的块组
+    fn grow_groups(
+        &self,
+        new_total_blocks: u32,
+        inode_bitmap_blocks: u32,
+    ) -> Result<Vec<BlockGroup>, FsError> {
+        // 快照的元数据副本按当前的块组划分 扩容后无法回滚
+        if self.has_snapshots() {
+            return Err(FsError::Busy);
+        }
+        let mut groups: Vec<BlockGroup> = Vec::new();
+        let mut start = self.total_blocks;
+        if inode_bitmap_blocks > 0 {
+            let inode_area_blocks = Self::inode_area_blocks(inode_bitmap_blocks);
+            groups.push(BlockGroup {
+                kind: GROUP_INODE,
+                bitmap_start_block: start,
+                bitmap_blocks: inode_bitmap_blocks,
+                area_blocks: inode_area_blocks,
+                checksum_blocks: 0,
+            });
+            start += inode_bitmap_blocks + inode_area_blocks;
+        }
+        if start > new_total_blocks {
+            return Err(FsError::NoSpace);
+        }
+        let (data_bitmap_blocks, data_area_blocks, checksum_blocks) =
+            Self::data_layout(new_total_blocks - start, self.checksum);
+        if data_area_blocks > 0 {
+            groups.push(BlockGroup {
+                kind: GROUP_DATA,
+                bitmap_start_block: start,
+                bitmap_blocks: data_bitmap_blocks,
+                area_blocks: data_area_blocks,
+                checksum_blocks,
+            });
+        }
+        let group_count = self.inode_groups.len() + self.data_groups.len() - 2;
+        if groups.is_empty() || group_count + groups.len() > MAX_GROUPS {
+            return Err(FsError::NoSpace);
+        }
+        Ok(groups)
+    }
+
+    /// 写时复制 block_id被快照引用时复制到新块并返回新块号
+    pub fn cow_data(&mut self, block_id: u32) -> Option<u32> {
+        let (group_id, bit) = self.data_bit(block_id);
+        if !self
+            .frozen_bitmaps()
+            .iter()
+            .any(|bitmaps| bitmaps[group_id].test(&self.block_device, bit))
+        {
+            return None;
+        }
+        let new_block_id = self.alloc_data();
+        self.copy_block(block_id, new_block_id);
+        // 旧块仍由快照持有 只从当前位图中释放
+        self.data_groups[group_id]
+            .bitmap
+            .dealloc(&self.block_device, bit);
+        Some(new_block_id)
+    }
+
+    pub fn has_snapshots(&self) -> bool {
+        self.snapshots.iter().any(|snapshot| !snapshot.is_empty())
+    }
+
+    /// 创建快照 冻结当前的索引节点和位图 返回快照编号
+    pub fn snapshot(&mut self, name: &str) -> Result<u32, FsError> {
+        if name.len() > SNAPSHOT_NAME_LIMIT {
+            return Err(FsError::NameTooLong);
+        }
+        // 超级块不在快照中 扩展属性表要先于快照创建 回滚后它的inode仍被占用
+        self.create_xattr_table();
+        if self.find_snapshot(name).is_some() {
+            return Err(FsError::AlreadyExists);
+        }
+        let slot = self
+            .snapshots
+            .iter()
+            .position(|snapshot| snapshot.is_empty())
+            .ok_or(FsError::NoSpace)?;
+        let id = self
+            .snapshots
+            .iter()
+            .map(|snapshot| snapshot.id)
+            .max()
+            .unwrap()
+            + 1;
+        let (regions, _) = self.meta_layout();
+        let meta_blocks = regions.iter().map(|(_, blocks)| blocks).sum();
+        // 先在当前位图中占用副本所在的块 使快照自身的数据位图也包含它们
+        let meta_start = self
+            .alloc_data_contiguous(meta_blocks)
+            .ok_or(FsError::NoSpace)?;
+        let mut dst = meta_start;
+        for (start, blocks) in regions {
+            for i in 0..blocks {
+                self.copy_block(start + i, dst);
+                dst += 1;
+            }
+        }
+        self.snapshots[slot] = SnapshotEntry::new(id, meta_start, meta_blocks, name);
+        self.write_snapshot_table();
+        block_cache_sync_all();
+        Ok(id)
+    }
+
+    /// 列出快照 (编号, 名字)
+    pub fn snapshots(&self) -> Vec<(u32, String)> {
+        let mut v: Vec<(u32, String)> = self
+            .snapshots
+            .iter()
+            .filter(|snapshot| !snapshot.is_empty())
+            .map(|snapshot| (snapshot.id, String::from(snapshot.name())))
+            .collect();
+        v.sort_by_key(|(id, _)| *id);
+        v
+    }
+
+    /// 按名字查找快照编号
+    pub fn find_snapshot(&self, name: &str) -> Option<u32> {
+        self.snapshots
+            .iter()
+            .find(|snapshot| !snapshot.is_empty() && snapshot.name() == name)
+            .map(|snapshot| snapshot.id)
+    }
+
+    /// 回滚到快照 快照本身保留
+    ///
+    /// 回滚后之前取得的Inode内容已失效 需要重新获取根目录
+    pub fn rollback(&mut self, id: u32) -> Result<(), FsError> {
+        let snapshot = *self.snapshot_entry(id)?;
+        let (regions, _) = self.meta_layout();
+        let mut src = snapshot.meta_start;
+        for (start, blocks) in regions {
+            for i in 0..blocks {
+                self.copy_block(src, start + i);
+                src += 1;
+            }
+        }
+        // 之后创建的快照的副本不在恢复出的位图中 重新占用
+        for other in self.snapshots.iter().filter(|other| !other.is_empty()) {
+            for block_id in other.meta_start..other.meta_start + other.meta_blocks {
+                let (group_id, bit) = self.data_bit(block_id);
+                self.data_groups[group_id]
+                    .bitmap
+                    .set(&self.block_device, bit);
+            }
+        }
+        block_cache_sync_all();
+        Ok(())
+    }
+
+    /// 删除快照 只被该快照引用的块自然成为空闲块
+    pub fn delete_snapshot(&mut self, id: u32) -> Result<(), FsError> {
+        let slot = self
+            .snapshots
+            .iter()
+            .position(|snapshot| !snapshot.is_empty() && snapshot.id == id)
+            .ok_or(FsError::NotFound)?;
+        let snapshot = self.snapshots[slot];
+        self.snapshots[slot] = SnapshotEntry::empty();
+        // 副本所在的块可能还被当前位图和之后创建的快照的位图标记
+        let frozen = self.frozen_bitmaps();
+        for block_id in snapshot.meta_start..snapshot.meta_start + snapshot.meta_blocks {
+            let (group_id, bit) = self.data_bit(block_id);
+            let bitmap = &self.data_groups[group_id].bitmap;
+            for bitmap in frozen
+                .iter()
+                .map(|bitmaps| &bitmaps[group_id])
+                .chain(core::iter::once(bitmap))
+            {
+                if bitmap.test(&self.block_device, bit) {
+                    bitmap.dealloc(&self.block_device, bit);
+                }
+            }
+        }
+        self.write_snapshot_table();
+        block_cache_sync_all();
+        Ok(())
+    }
+
+    fn snapshot_entry(&self, id: u32) -> Result<&SnapshotEntry, FsError> {
+        self.snapshots
+            .iter()
+            .find(|snapshot| !snapshot.is_empty() && snapshot.id == id)
+            .ok_or(FsError::NotFound)
+    }
+
+    /// 快照需要保存的元数据区域(开始块号, 块数) 以及各数据位图在副本中的偏移
+    ///
+    /// 第一个区域是块1到第一个数据区之前的全部元数据 之后是扩容添加的各组
+    fn meta_layout(&self) -> (Vec<(u32, u32)>, Vec<u32>) {
+        let first_data_group = &self.data_groups[0];
+        let mut regions = vec![(1, first_data_group.area_start_block - 1)];
+        let mut offsets = vec![first_data_group.bitmap_start_block - 1];
+        let mut offset = first_data_group.area_start_block - 1;
+        for group in self.inode_groups.iter().skip(1) {
+            regions.push((
+                group.bitmap_start_block,
+                group.bitmap_blocks + group.area_blocks,
+            ));
+            offset += group.bitmap_blocks + group.area_blocks;
+        }
+        for group in self.data_groups.iter().skip(1) {
+            regions.push((group.bitmap_start_block, group.bitmap_blocks));
+            offsets.push(offset);
+            offset += group.bitmap_blocks;
+        }
+        (regions, offsets)
+    }
+
+    /// 各快照副本中的数据位图 按快照和数据块组索引
+    fn frozen_bitmaps(&self) -> Vec<Vec<Bitmap>> {
+        let (_, offsets) = self.meta_layout();
+        self.snapshots
+            .iter()
+            .filter(|snapshot| !snapshot.is_empty())
+            .map(|snapshot| {
+                self.data_groups
+                    .iter()
+                    .zip(offsets.iter())
+                    .map(|(group, offset)| {
+                        Bitmap::new(
+                            (snapshot.meta_start + offset) as usize,
+                            group.bitmap_blocks as usize,
+                        )
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn write_snapshot_table(&self) {
+        get_block_cache(0, Arc::clone(&self.block_device))
+            .lock()
+            .modify(SNAPSHOT_TABLE_OFFSET, |snapshots: &mut SnapshotTable| {
+                *snapshots = self.snapshots;
+            });
+    }
+
+    fn copy_block(&self, src: u32, dst: u32) {
+        let src = get_block_cache(src as usize, Arc::clone(&self.block_device));
+        get_block_cache(dst as usize, Arc::clone(&self.block_device))
+            .lock()
+            .copy_from(&src.lock());
     }
 
     /// 获取索引节点 返回块号和块内偏移
     pub fn get_disk_inode_pos(&self, inode_id: u32) -> (u32, usize) {
+        let group = self
+            .inode_groups
+            .iter()
+            .rev()
+            .find(|group| group.first_inode <= inode_id)
+            .unwrap();
+        let inode_id = inode_id - group.first_inode;
         let inode_size = core::mem::size_of::<DiskInode>();
         let inodes_per_block = (BLOCK_SZ / inode_size) as u32;
-        let block_id = self.inode_area_start_block + inode_id / inodes_per_block;
+        let block_id = group.area_start_block + inode_id / inodes_per_block;
         (
             block_id,
             (inode_id % inodes_per_block) as usize * inode_size,
         )
     }
 
+    /// 由索引节点的位置得到编号 get_disk_inode_pos的逆运算
+    pub fn get_inode_id(&self, block_id: u32, block_offset: usize) -> u32 {
+        let group = self
+            .inode_groups
+            .iter()
+            .find(|group| {
+                block_id >= group.area_start_block
+                    && block_id < group.area_start_block + group.area_blocks
+            })
+            .unwrap();
+        let inode_size = core::mem::size_of::<DiskInode>();
+        let inodes_per_block = (BLOCK_SZ / inode_size) as u32;
+        group.first_inode
+            + (block_id - group.area_start_block) * inodes_per_block
+            + (block_offset / inode_size) as u32
+    }
+
     pub fn get_data_block_id(&self, data_block_id: u32) -> u32 {
-        self.data_area_start_block + data_block_id
+        self.data_groups[0].area_start_block + data_block_id
     }
 }
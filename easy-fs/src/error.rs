@@ -0,0 +1,16 @@
+/// 文件系统错误
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FsError {
+    /// 数据块校验和不匹配
+    Corrupted,
+    /// 空间或表项不足
+    NoSpace,
+    /// 找不到指定对象
+    NotFound,
+    /// 对象已存在
+    AlreadyExists,
+    /// 名字过长
+    NameTooLong,
+    /// 存在快照等原因暂时无法操作
+    Busy,
+}
@@ -1,8 +1,26 @@
-use crate::{block_cache::get_block_cache, block_dev::BlockDevice, BLOCK_SZ};
-use alloc::{sync::Arc, vec::Vec};
+use crate::{block_cache::get_block_cache, block_dev::BlockDevice, error::FsError, BLOCK_SZ};
+use alloc::{string::String, sync::Arc, vec::Vec};
 
 /// Magic number for sanity check
 const EFS_MAGIC: u32 = 0x3b800001;
+/// 格式特性 数据块校验和
+pub const FEATURE_CHECKSUM: u32 = 1 << 0;
+/// 格式特性 扩展属性表 第一次设置扩展属性时开启
+pub const FEATURE_XATTR: u32 = 1 << 1;
+/// 扩容最多添加的块组数
+pub const MAX_GROUPS: usize = 8;
+/// 超级块中保存的位图游标数 创建时的索引节点位图和数据位图各一个 每个块组一个
+pub const BITMAP_CURSORS: usize = 2 + MAX_GROUPS;
+/// 块组类型 数据块组
+pub const GROUP_DATA: u32 = 1;
+/// 块组类型 索引节点组
+pub const GROUP_INODE: u32 = 2;
+/// 最多同时保留的快照数
+pub const MAX_SNAPSHOTS: usize = 8;
+/// 快照名最大长度
+pub const SNAPSHOT_NAME_LIMIT: usize = 19;
+/// 快照表在块0中的偏移 位于超级块之后
+pub const SNAPSHOT_TABLE_OFFSET: usize = 256;
 /// The max number of direct inodes
 const INODE_DIRECT_COUNT: usize = 28;
 /// The max length of inode name
@@ -27,6 +45,49 @@ pub struct SuperBlock {
     pub inode_area_blocks: u32,
     pub data_bitmap_blocks: u32,
     pub data_area_blocks: u32,
+    /// 格式特性位 旧镜像中为0
+    pub features: u32,
+    /// 校验区块数 位于数据区之后
+    pub checksum_blocks: u32,
+    /// 扩容添加的块组数 旧镜像中为0
+    pub group_count: u32,
+    pub groups: [BlockGroup; MAX_GROUPS],
+    /// 各位图的next-fit游标 旧镜像中为0
+    pub bitmap_cursors: [u32; BITMAP_CURSORS],
+    /// 扩展属性表所在的inode 开启FEATURE_XATTR时有效
+    ///
+    /// 表是一个不在任何目录中的文件 第i个u32是inode i的扩展属性块 0表示没有
+    pub xattr_inode: u32,
+}
+
+/// 扩容时追加到镜像末尾的块组 20字节
+///
+/// 块组由位图和紧随其后的区域组成 数据块组开启校验时区域之后还有校验区
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct BlockGroup {
+    /// GROUP_DATA或GROUP_INODE
+    pub kind: u32,
+    pub bitmap_start_block: u32,
+    pub bitmap_blocks: u32,
+    pub area_blocks: u32,
+    pub checksum_blocks: u32,
+}
+
+impl BlockGroup {
+    pub fn empty() -> Self {
+        Self {
+            kind: 0,
+            bitmap_start_block: 0,
+            bitmap_blocks: 0,
+            area_blocks: 0,
+            checksum_blocks: 0,
+        }
+    }
+
+    pub fn area_start_block(&self) -> u32 {
+        self.bitmap_start_block + self.bitmap_blocks
+    }
 }
 
 impl SuperBlock {
@@ -45,14 +106,95 @@ impl SuperBlock {
             inode_area_blocks,
             data_bitmap_blocks,
             data_area_blocks,
+            features: 0,
+            checksum_blocks: 0,
+            group_count: 0,
+            groups: [BlockGroup::empty(); MAX_GROUPS],
+            bitmap_cursors: [0; BITMAP_CURSORS],
+            xattr_inode: 0,
         }
     }
 
+    /// 记录扩容添加的块组
+    pub fn add_group(&mut self, group: BlockGroup) {
+        assert!((self.group_count as usize) < MAX_GROUPS);
+        self.groups[self.group_count as usize] = group;
+        self.group_count += 1;
+        self.total_blocks = self
+            .total_blocks
+            .max(group.area_start_block() + group.area_blocks + group.checksum_blocks);
+    }
+
+    /// 开启数据块校验和
+    pub fn enable_checksum(&mut self, checksum_blocks: u32) {
+        self.features |= FEATURE_CHECKSUM;
+        self.checksum_blocks = checksum_blocks;
+    }
+
+    pub fn has_checksum(&self) -> bool {
+        self.features & FEATURE_CHECKSUM != 0
+    }
+
+    /// 开启扩展属性表
+    pub fn enable_xattr(&mut self, xattr_inode: u32) {
+        self.features |= FEATURE_XATTR;
+        self.xattr_inode = xattr_inode;
+    }
+
+    /// 扩展属性表所在的inode
+    pub fn xattr_inode(&self) -> Option<u32> {
+        (self.features & FEATURE_XATTR != 0).then(|| self.xattr_inode)
+    }
+
     pub fn is_valid(&self) -> bool {
         self.magic == EFS_MAGIC
     }
 }
 
+/// 快照表项 32字节
+///
+/// 快照保存了块1到数据区之前的全部元数据(索引节点位图 索引节点区 数据位图)的副本
+/// 副本存放在数据区中连续的meta_blocks个块
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct SnapshotEntry {
+    /// 快照编号 0表示空项
+    pub id: u32,
+    /// 元数据副本开始的块号
+    pub meta_start: u32,
+    /// 元数据副本块数
+    pub meta_blocks: u32,
+    name: [u8; SNAPSHOT_NAME_LIMIT + 1],
+}
+
+pub type SnapshotTable = [SnapshotEntry; MAX_SNAPSHOTS];
+
+impl SnapshotEntry {
+    pub fn new(id: u32, meta_start: u32, meta_blocks: u32, name: &str) -> Self {
+        let mut bytes = [0u8; SNAPSHOT_NAME_LIMIT + 1];
+        bytes[..name.len()].copy_from_slice(name.as_bytes());
+        Self {
+            id,
+            meta_start,
+            meta_blocks,
+            name: bytes,
+        }
+    }
+
+    pub fn empty() -> Self {
+        Self::new(0, 0, 0, "")
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.id == 0
+    }
+
+    pub fn name(&self) -> &str {
+        let len = (0usize..).find(|i| self.name[*i] == 0).unwrap();
+        core::str::from_utf8(&self.name[..len]).unwrap()
+    }
+}
+
 /// 索引节点 128字节 每个文件或目录拥有一个
 #[repr(C)]
 pub struct DiskInode {
@@ -76,6 +218,8 @@ pub struct DiskInode {
 pub enum DiskInodeType {
     File,
     Directory,
+    /// 命名管道 数据不保存在磁盘上
+    Fifo,
 }
 /// 索引块 128个u32
 type IndirectBlock = [u32; BLOCK_SZ / 4];
@@ -238,17 +382,17 @@ impl DiskInode {
         v
     }
 
-    /// 从数据块读取内容到buf
+    /// 从数据块读取内容到buf 校验和不匹配时返回错误
     pub fn read_at(
         &self,
         offset: usize,
         buf: &mut [u8],
         block_device: &Arc<dyn BlockDevice>,
-    ) -> usize {
+    ) -> Result<usize, FsError> {
         let mut start = offset;
         let end = (offset + buf.len()).min(self.size as usize);
         if start >= end {
-            return 0;
+            return Ok(0);
         }
 
         let mut start_block = start / BLOCK_SZ;
@@ -259,14 +403,14 @@ impl DiskInode {
             let block_read_size = end_current_block - start;
             let dst = &mut buf[read_size..read_size + block_read_size];
             get_block_cache(
-                self.get_block_id(start_block as u32, block_device) as usize,
+                self.lookup_block_id(start_block as u32, block_device, true)? as usize,
                 Arc::clone(block_device),
             )
             .lock()
-            .read(0, |data_block: &DataBlock| {
+            .checked_read(0, |data_block: &DataBlock| {
                 let src = &data_block[start % BLOCK_SZ..start % BLOCK_SZ + block_read_size];
                 dst.copy_from_slice(src);
-            });
+            })?;
             read_size += block_read_size;
             if end_current_block == end {
                 break;
@@ -274,15 +418,16 @@ impl DiskInode {
             start_block += 1;
             start = end_current_block;
         }
-        read_size
+        Ok(read_size)
     }
 
+    /// 把buf写入数据块 遇到不能修改的损坏块时停止 一字节都没写入则返回错误
     pub fn write_at(
         &mut self,
         offset: usize,
         buf: &[u8],
         block_device: &Arc<dyn BlockDevice>,
-    ) -> usize {
+    ) -> Result<usize, FsError> {
         let mut start = offset;
         let end = (offset + buf.len()).min(self.size as usize);
         assert!(start <= end);
@@ -294,16 +439,29 @@ impl DiskInode {
             end_current_block = end_current_block.min(end);
             // write and update write size
             let block_write_size = end_current_block - start;
-            get_block_cache(
-                self.get_block_id(start_block as u32, block_device) as usize,
-                Arc::clone(block_device),
-            )
-            .lock()
-            .modify(0, |data_block: &mut DataBlock| {
-                let src = &buf[write_size..write_size + block_write_size];
-                let dst = &mut data_block[start % BLOCK_SZ..start % BLOCK_SZ + block_write_size];
-                dst.copy_from_slice(src);
-            });
+            let written = self
+                .lookup_block_id(start_block as u32, block_device, true)
+                .and_then(|block_id| {
+                    let block_cache = get_block_cache(block_id as usize, Arc::clone(block_device));
+                    let mut block_cache = block_cache.lock();
+                    let src = &buf[write_size..write_size + block_write_size];
+                    // 写满整块时不需要旧内容 可以覆盖损坏的块
+                    if block_write_size == BLOCK_SZ {
+                        block_cache.overwrite(src);
+                        return Ok(());
+                    }
+                    block_cache.checked_modify(0, |data_block: &mut DataBlock| {
+                        data_block[start % BLOCK_SZ..start % BLOCK_SZ + block_write_size]
+                            .copy_from_slice(src);
+                    })
+                });
+            if let Err(err) = written {
+                return if write_size == 0 {
+                    Err(err)
+                } else {
+                    Ok(write_size)
+                };
+            }
             write_size += block_write_size;
             // move to next block
             if end_current_block == end {
@@ -312,7 +470,7 @@ impl DiskInode {
             start_block += 1;
             start = end_current_block;
         }
-        write_size
+        Ok(write_size)
     }
 
     /// 计算自身所需数据块
@@ -347,31 +505,126 @@ impl DiskInode {
         Self::total_blocks(new_size) - Self::total_blocks(self.size)
     }
 
-    pub fn get_block_id(&self, inner_id: u32, block_device: &Arc<dyn BlockDevice>) -> u32 {
+    /// 写时复制 保证第inner_id个数据块及途经的索引块都可以直接修改
+    ///
+    /// cow返回Some(新块号)表示该块被快照引用 已复制到新块
+    pub fn cow_block(
+        &mut self,
+        inner_id: u32,
+        block_device: &Arc<dyn BlockDevice>,
+        cow: &mut impl FnMut(u32) -> Option<u32>,
+    ) {
         let inner_id = inner_id as usize;
         if inner_id < INODE_DIRECT_COUNT {
-            /// 直接索引
-            self.direct[inner_id]
+            if let Some(block_id) = cow(self.direct[inner_id]) {
+                self.direct[inner_id] = block_id;
+            }
         } else if inner_id < INDIRECT1_BOUND {
-            /// 一级索引
-            get_block_cache(self.indirect1 as usize, Arc::clone(block_device))
-                .lock()
-                .read(0, |indirect_block: &IndirectBlock| {
-                    indirect_block[inner_id - INODE_DIRECT_COUNT]
-                })
+            if let Some(block_id) = cow(self.indirect1) {
+                self.indirect1 = block_id;
+            }
+            Self::cow_entry(
+                self.indirect1,
+                inner_id - INODE_DIRECT_COUNT,
+                block_device,
+                cow,
+            );
         } else {
-            /// 二级索引
+            if let Some(block_id) = cow(self.indirect2) {
+                self.indirect2 = block_id;
+            }
             let last = inner_id - INDIRECT1_BOUND;
-            let indirect1 = get_block_cache(self.indirect2 as usize, Arc::clone(block_device))
+            let indirect1 = Self::cow_entry(
+                self.indirect2,
+                last / INODE_INDIRECT1_COUNT,
+                block_device,
+                cow,
+            );
+            Self::cow_entry(indirect1, last % INODE_INDIRECT1_COUNT, block_device, cow);
+        }
+    }
+
+    /// 写时复制索引块中的一项 返回该项现在指向的块号
+    fn cow_entry(
+        block_id: u32,
+        index: usize,
+        block_device: &Arc<dyn BlockDevice>,
+        cow: &mut impl FnMut(u32) -> Option<u32>,
+    ) -> u32 {
+        let entry = get_block_cache(block_id as usize, Arc::clone(block_device))
+            .lock()
+            .read(0, |indirect_block: &IndirectBlock| indirect_block[index]);
+        match cow(entry) {
+            Some(new_entry) => {
+                get_block_cache(block_id as usize, Arc::clone(block_device))
+                    .lock()
+                    .modify(0, |indirect_block: &mut IndirectBlock| {
+                        indirect_block[index] = new_entry;
+                    });
+                new_entry
+            }
+            None => entry,
+        }
+    }
+
+    /// 文件占用的所有块 包括数据块和索引块 扩展属性块记录在扩展属性表中 不在这里
+    pub fn block_ids(&self, block_device: &Arc<dyn BlockDevice>) -> Vec<u32> {
+        let data_blocks = self.data_blocks() as usize;
+        let mut v = Vec::new();
+        if data_blocks > INODE_DIRECT_COUNT {
+            v.push(self.indirect1);
+        }
+        if data_blocks > INDIRECT1_BOUND {
+            v.push(self.indirect2);
+            // 二级索引下用到的一级索引块
+            let indirect1_blocks = (INDIRECT1_BOUND..data_blocks)
+                .step_by(INODE_INDIRECT1_COUNT)
+                .count();
+            get_block_cache(self.indirect2 as usize, Arc::clone(block_device))
                 .lock()
                 .read(0, |indirect2: &IndirectBlock| {
-                    indirect2[last / INODE_INDIRECT1_COUNT]
+                    v.extend(indirect2.iter().take(indirect1_blocks))
                 });
-            get_block_cache(indirect1 as usize, Arc::clone(block_device))
-                .lock()
-                .read(0, |indirect1: &IndirectBlock| {
-                    indirect1[last % INODE_INDIRECT1_COUNT]
-                })
+        }
+        for inner_id in 0..data_blocks {
+            v.push(self.get_block_id(inner_id as u32, block_device));
+        }
+        v
+    }
+
+    pub fn get_block_id(&self, inner_id: u32, block_device: &Arc<dyn BlockDevice>) -> u32 {
+        self.lookup_block_id(inner_id, block_device, false).unwrap()
+    }
+
+    /// 查找数据块号 checked为true时索引块校验失败返回错误
+    fn lookup_block_id(
+        &self,
+        inner_id: u32,
+        block_device: &Arc<dyn BlockDevice>,
+        checked: bool,
+    ) -> Result<u32, FsError> {
+        let read_index = |block_id: u32, index: usize| {
+            let block_cache = get_block_cache(block_id as usize, Arc::clone(block_device));
+            let block_cache = block_cache.lock();
+            let f = |indirect_block: &IndirectBlock| indirect_block[index];
+            if checked {
+                block_cache.checked_read(0, f)
+            } else {
+                Ok(block_cache.read(0, f))
+            }
+        };
+        let inner_id = inner_id as usize;
+        if inner_id < INODE_DIRECT_COUNT {
+            // 直接索引
+            Ok(self.direct[inner_id])
+        } else if inner_id < INDIRECT1_BOUND {
+            // 一级索引
+            read_index(self.indirect1, inner_id - INODE_DIRECT_COUNT)
+        } else {
+            // 二级索引
+            let last = inner_id - INDIRECT1_BOUND;
+            let indirect1 = read_index(self.indirect2, last / INODE_INDIRECT1_COUNT)?;
+            read_index(indirect1, last % INODE_INDIRECT1_COUNT)
         }
     }
 
@@ -382,6 +635,10 @@ impl DiskInode {
     pub fn is_file(&self) -> bool {
         self.type_ == DiskInodeType::File
     }
+
+    pub fn is_fifo(&self) -> bool {
+        self.type_ == DiskInodeType::Fifo
+    }
 }
 
 /// 目录项 32字节
@@ -428,3 +685,53 @@ impl DirEntry {
         self.inode_number
     }
 }
+
+/// 扩展属性名最大长度
+pub const XATTR_NAME_LIMIT: usize = u8::MAX as usize;
+/// 扩展属性块 依次存放每一项的名字长度(u8) 值长度(u16) 名字 值
+/// 名字长度为0表示结束
+pub type XattrBlock = [u8; BLOCK_SZ];
+/// 每一项的头部字节数
+const XATTR_HEADER_SZ: usize = 3;
+
+/// 解析扩展属性块
+pub fn parse_xattrs(block: &XattrBlock) -> Vec<(String, Vec<u8>)> {
+    let mut v = Vec::new();
+    let mut pos = 0;
+    while pos + XATTR_HEADER_SZ <= BLOCK_SZ && block[pos] != 0 {
+        let name_len = block[pos] as usize;
+        let value_len = u16::from_le_bytes([block[pos + 1], block[pos + 2]]) as usize;
+        let name_start = pos + XATTR_HEADER_SZ;
+        let value_start = name_start + name_len;
+        if value_start + value_len > BLOCK_SZ {
+            break;
+        }
+        let name = String::from_utf8_lossy(&block[name_start..value_start]).into();
+        v.push((name, block[value_start..value_start + value_len].to_vec()));
+        pos = value_start + value_len;
+    }
+    v
+}
+
+/// 写入扩展属性块 放不下时返回false
+pub fn encode_xattrs(xattrs: &[(String, Vec<u8>)], block: &mut XattrBlock) -> bool {
+    let total: usize = xattrs
+        .iter()
+        .map(|(name, value)| XATTR_HEADER_SZ + name.len() + value.len())
+        .sum();
+    if total > BLOCK_SZ {
+        return false;
+    }
+    block.fill(0);
+    let mut pos = 0;
+    for (name, value) in xattrs {
+        block[pos] = name.len() as u8;
+        block[pos + 1..pos + XATTR_HEADER_SZ].copy_from_slice(&(value.len() as u16).to_le_bytes());
+        pos += XATTR_HEADER_SZ;
+        block[pos..pos + name.len()].copy_from_slice(name.as_bytes());
+        pos += name.len();
+        block[pos..pos + value.len()].copy_from_slice(value);
+        pos += value.len();
+    }
+    true
+}
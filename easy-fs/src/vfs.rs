@@ -1,10 +1,12 @@
 use crate::{
-    block_cache::{block_cache_sync_all, get_block_cache},
+    block_cache::{block_cache_sync, block_cache_sync_all, get_block_cache},
     block_dev::BlockDevice,
     efs::EasyFileSystem,
+    error::FsError,
     layout::*,
+    BLOCK_SZ,
 };
-use alloc::{string::String, sync::Arc, vec::Vec};
+use alloc::{string::String, sync::Arc, vec, vec::Vec};
 use spin::{Mutex, MutexGuard};
 
 /// 索引节点
@@ -43,44 +45,117 @@ impl Inode {
         if new_size < disk_inode.size {
             return;
         }
+        // 扩容会修改最后一块所在的索引块 先让它们脱离快照
+        let data_blocks = disk_inode.data_blocks();
+        if data_blocks > 0 && fs.has_snapshots() {
+            disk_inode.cow_block(data_blocks - 1, &self.block_device, &mut |block_id| {
+                fs.cow_data(block_id)
+            });
+        }
         // 需要新增块
         let blocks_needed = disk_inode.blocks_num_needed(new_size);
         let mut v: Vec<u32> = Vec::new();
-        for _ in 0..blocks_needed {
-            v.push(fs.alloc_data());
+        // 优先申请连续的块 让文件内容在磁盘上相邻
+        if let Some(start) = fs.alloc_data_contiguous(blocks_needed) {
+            v.extend(start..start + blocks_needed);
+        } else {
+            for _ in 0..blocks_needed {
+                v.push(fs.alloc_data());
+            }
         }
         disk_inode.increase_size(new_size, v, &self.block_device);
     }
 
-    pub fn find(&self, name: &str) -> Option<Arc<Inode>> {
+    /// 写时复制 [offset, offset + len)涉及的数据块被快照引用时先复制
+    fn cow_range(
+        &self,
+        offset: usize,
+        len: usize,
+        disk_inode: &mut DiskInode,
+        fs: &mut MutexGuard<EasyFileSystem>,
+    ) {
+        if len == 0 || !fs.has_snapshots() {
+            return;
+        }
+        let end_block =
+            ((offset + len + BLOCK_SZ - 1) / BLOCK_SZ).min(disk_inode.data_blocks() as usize);
+        for inner_id in offset / BLOCK_SZ..end_block {
+            disk_inode.cow_block(inner_id as u32, &self.block_device, &mut |block_id| {
+                fs.cow_data(block_id)
+            });
+        }
+    }
+
+    pub fn is_dir(&self) -> bool {
+        let _fs = self.fs.lock();
+        self.read_disk_inode(|disk_inode| disk_inode.is_dir())
+    }
+
+    pub fn is_fifo(&self) -> bool {
+        let _fs = self.fs.lock();
+        self.read_disk_inode(|disk_inode| disk_inode.is_fifo())
+    }
+
+    /// 索引节点编号
+    pub fn inode_id(&self) -> u32 {
+        self.fs
+            .lock()
+            .get_inode_id(self.block_id as u32, self.block_offset)
+    }
+
+    /// 把这个文件修改过的块写回磁盘 设备不能保证落盘时返回false
+    pub fn fsync(&self) -> bool {
         let fs = self.fs.lock();
-        self.read_disk_inode(|disk_inode| {
-            self.find_inode_id(name, disk_inode).map(|inode_id| {
-                let (block_id, block_offset) = fs.get_disk_inode_pos(inode_id);
-                Arc::new(Self::new(
-                    block_id,
-                    block_offset,
-                    self.fs.clone(),
-                    self.block_device.clone(),
-                ))
-            })
-        })
+        let mut block_ids: Vec<usize> = self
+            .read_disk_inode(|disk_inode| disk_inode.block_ids(&self.block_device))
+            .into_iter()
+            .map(|block_id| block_id as usize)
+            .collect();
+        block_ids.push(self.block_id);
+        block_ids.extend(self.xattr_block_ids(&fs));
+        block_cache_sync(&block_ids);
+        self.block_device.flush()
     }
 
-    fn find_inode_id(&self, name: &str, disk_inode: &DiskInode) -> Option<u32> {
+    /// 把整个文件系统修改过的块写回磁盘
+    pub fn sync_fs(&self) -> bool {
+        let _fs = self.fs.lock();
+        block_cache_sync_all();
+        self.block_device.flush()
+    }
+
+    /// 文件大小 单位字节
+    pub fn size(&self) -> usize {
+        let _fs = self.fs.lock();
+        self.read_disk_inode(|disk_inode| disk_inode.size as usize)
+    }
+
+    /// 在目录中查找 目录项损坏时返回FsError::Corrupted
+    pub fn find(&self, name: &str) -> Result<Option<Arc<Inode>>, FsError> {
+        let fs = self.fs.lock();
+        let inode_id = self.read_disk_inode(|disk_inode| self.find_inode_id(name, disk_inode))?;
+        Ok(inode_id.map(|inode_id| {
+            let (block_id, block_offset) = fs.get_disk_inode_pos(inode_id);
+            Arc::new(Self::new(
+                block_id,
+                block_offset,
+                self.fs.clone(),
+                self.block_device.clone(),
+            ))
+        }))
+    }
+
+    fn find_inode_id(&self, name: &str, disk_inode: &DiskInode) -> Result<Option<u32>, FsError> {
         assert!(disk_inode.is_dir());
         let file_count = (disk_inode.size as usize) / DIRENT_SZ;
         let mut dirent = DirEntry::empty();
         for i in 0..file_count {
-            assert_eq!(
-                disk_inode.read_at(DIRENT_SZ * i, dirent.as_bytes_mut(), &self.block_device),
-                DIRENT_SZ
-            );
+            disk_inode.read_at(DIRENT_SZ * i, dirent.as_bytes_mut(), &self.block_device)?;
             if dirent.name() == name {
-                return Some(dirent.inode_number() as u32);
+                return Ok(Some(dirent.inode_number() as u32));
             }
         }
-        None
+        Ok(None)
     }
 
     fn read_disk_inode<V>(&self, f: impl FnOnce(&DiskInode) -> V) -> V {
@@ -95,33 +170,45 @@ impl Inode {
             .modify(self.block_offset, f)
     }
 
-    pub fn ls(&self) -> Vec<String> {
+    /// 列出目录项 目录项损坏时返回FsError::Corrupted
+    pub fn ls(&self) -> Result<Vec<String>, FsError> {
         let _fs = self.fs.lock();
         self.read_disk_inode(|disk_inode| {
             let file_count = (disk_inode.size as usize) / DIRENT_SZ;
             let mut v: Vec<String> = Vec::new();
             for i in 0..file_count {
                 let mut dirent = DirEntry::empty();
-                assert_eq!(
-                    disk_inode.read_at(i * DIRENT_SZ, dirent.as_bytes_mut(), &self.block_device),
-                    DIRENT_SZ
-                );
+                disk_inode.read_at(i * DIRENT_SZ, dirent.as_bytes_mut(), &self.block_device)?;
                 v.push(String::from(dirent.name()));
             }
-            v
+            Ok(v)
         })
     }
 
     /// 创建文件
     pub fn create(&self, name: &str) -> Option<Arc<Inode>> {
+        self.create_inode(name, DiskInodeType::File)
+    }
+
+    /// 创建子目录
+    pub fn mkdir(&self, name: &str) -> Option<Arc<Inode>> {
+        self.create_inode(name, DiskInodeType::Directory)
+    }
+
+    /// 创建命名管道
+    pub fn mkfifo(&self, name: &str) -> Option<Arc<Inode>> {
+        self.create_inode(name, DiskInodeType::Fifo)
+    }
+
+    fn create_inode(&self, name: &str, type_: DiskInodeType) -> Option<Arc<Inode>> {
         let mut fs = self.fs.lock();
 
-        // 判断当前inode是否为目录
+        // 判断当前inode是否为目录 目录项损坏时也不创建
         let op = |root_inode: &DiskInode| {
             assert!(root_inode.is_dir());
             self.find_inode_id(name, root_inode)
         };
-        if self.read_disk_inode(op).is_some() {
+        if self.read_disk_inode(op) != Ok(None) {
             return None;
         }
 
@@ -131,24 +218,29 @@ impl Inode {
         get_block_cache(new_inode_block_id as usize, Arc::clone(&self.block_device))
             .lock()
             .modify(new_inode_block_offset, |new_inode: &mut DiskInode| {
-                // 初始化为文件
-                new_inode.initialize(DiskInodeType::File);
+                new_inode.initialize(type_);
             });
 
         // 添加文件到当前目录
-        self.modify_disk_inode(|root_inode| {
+        let written = self.modify_disk_inode(|root_inode| {
             let file_count = (root_inode.size as usize) / DIRENT_SZ;
             let new_size = (file_count + 1) * DIRENT_SZ;
             // 新增一个目录项32字节大小
             self.increase_size(new_size as u32, root_inode, &mut fs);
             let dirent = DirEntry::new(name, new_inode_id);
+            self.cow_range(file_count * DIRENT_SZ, DIRENT_SZ, root_inode, &mut fs);
             // 写入磁盘缓冲
             root_inode.write_at(
                 file_count * DIRENT_SZ,
                 dirent.as_bytes(),
                 &self.block_device,
-            );
+            )
         });
+        // 目录所在的块已损坏 释放新申请的inode
+        if written.is_err() {
+            fs.dealloc_inode(new_inode_id);
+            return None;
+        }
 
         // 返回新创建文件的inode
         let (block_id, block_offset) = fs.get_disk_inode_pos(new_inode_id);
@@ -174,18 +266,200 @@ impl Inode {
         block_cache_sync_all();
     }
 
-    pub fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
+    /// 读取文件内容 数据块校验失败时返回FsError::Corrupted
+    pub fn read_at(&self, offset: usize, buf: &mut [u8]) -> Result<usize, FsError> {
         let _fs = self.fs.lock();
         self.read_disk_inode(|disk_inode| disk_inode.read_at(offset, buf, &self.block_device))
     }
 
-    pub fn write_at(&self, offset: usize, buf: &[u8]) -> usize {
+    /// 写入文件内容 数据块校验失败时返回FsError::Corrupted
+    pub fn write_at(&self, offset: usize, buf: &[u8]) -> Result<usize, FsError> {
         let mut fs = self.fs.lock();
         let size = self.modify_disk_inode(|disk_inode| {
             self.increase_size((offset + buf.len()) as u32, disk_inode, &mut fs);
+            self.cow_range(offset, buf.len(), disk_inode, &mut fs);
             disk_inode.write_at(offset, buf, &self.block_device)
         });
         block_cache_sync_all();
         size
     }
+
+    /// 扩展属性表中本inode对应项的偏移 每项是一个u32块号
+    fn xattr_entry_offset(&self, fs: &MutexGuard<EasyFileSystem>) -> usize {
+        fs.get_inode_id(self.block_id as u32, self.block_offset) as usize * 4
+    }
+
+    /// 扩展属性块 0表示没有
+    fn xattr_block(&self, fs: &MutexGuard<EasyFileSystem>) -> Result<u32, FsError> {
+        let table_id = match fs.xattr_table() {
+            Some(table_id) => table_id,
+            None => return Ok(0),
+        };
+        let (block_id, block_offset) = fs.get_disk_inode_pos(table_id);
+        let mut entry = [0u8; 4];
+        // 超出表的大小时读不到内容 仍为0
+        get_block_cache(block_id as usize, Arc::clone(&self.block_device))
+            .lock()
+            .read(block_offset, |table: &DiskInode| {
+                table.read_at(self.xattr_entry_offset(fs), &mut entry, &self.block_device)
+            })?;
+        Ok(u32::from_le_bytes(entry))
+    }
+
+    /// 扩展属性用到的块 包括扩展属性表的所有块和本inode的扩展属性块
+    fn xattr_block_ids(&self, fs: &MutexGuard<EasyFileSystem>) -> Vec<usize> {
+        let table_id = match fs.xattr_table() {
+            Some(table_id) => table_id,
+            None => return Vec::new(),
+        };
+        let (block_id, block_offset) = fs.get_disk_inode_pos(table_id);
+        let mut block_ids: Vec<usize> =
+            get_block_cache(block_id as usize, Arc::clone(&self.block_device))
+                .lock()
+                .read(block_offset, |table: &DiskInode| {
+                    table.block_ids(&self.block_device)
+                })
+                .into_iter()
+                .map(|block_id| block_id as usize)
+                .collect();
+        block_ids.push(block_id as usize);
+        // 扩展属性表损坏时只能写回表本身
+        if let Ok(xattr_block) = self.xattr_block(fs) {
+            if xattr_block != 0 {
+                block_ids.push(xattr_block as usize);
+            }
+        }
+        block_ids
+    }
+
+    /// 在扩展属性表中记录扩展属性块
+    fn set_xattr_block(
+        &self,
+        xattr_block: u32,
+        fs: &mut MutexGuard<EasyFileSystem>,
+    ) -> Result<(), FsError> {
+        let table_id = fs.create_xattr_table();
+        let (block_id, block_offset) = fs.get_disk_inode_pos(table_id);
+        let offset = self.xattr_entry_offset(fs);
+        get_block_cache(block_id as usize, Arc::clone(&self.block_device))
+            .lock()
+            .modify(block_offset, |table: &mut DiskInode| {
+                // 新分配的块内容不确定 扩容的部分要清零
+                let start = (table.size as usize).min(offset);
+                let mut buf = vec![0u8; offset + 4 - start];
+                buf[offset - start..].copy_from_slice(&xattr_block.to_le_bytes());
+                self.increase_size((offset + 4) as u32, table, fs);
+                self.cow_range(start, buf.len(), table, fs);
+                table.write_at(start, &buf, &self.block_device)
+            })?;
+        Ok(())
+    }
+
+    /// 读出全部扩展属性
+    fn read_xattrs(
+        &self,
+        fs: &MutexGuard<EasyFileSystem>,
+    ) -> Result<Vec<(String, Vec<u8>)>, FsError> {
+        let xattr_block = self.xattr_block(fs)?;
+        if xattr_block == 0 {
+            return Ok(Vec::new());
+        }
+        get_block_cache(xattr_block as usize, Arc::clone(&self.block_device))
+            .lock()
+            .checked_read(0, parse_xattrs)
+    }
+
+    /// 写回全部扩展属性 没有属性时释放扩展属性块
+    fn write_xattrs(
+        &self,
+        xattrs: &[(String, Vec<u8>)],
+        fs: &mut MutexGuard<EasyFileSystem>,
+    ) -> Result<(), FsError> {
+        let mut block: XattrBlock = [0; BLOCK_SZ];
+        if !encode_xattrs(xattrs, &mut block) {
+            return Err(FsError::NoSpace);
+        }
+        let old_block = self.xattr_block(fs)?;
+        if xattrs.is_empty() {
+            if old_block != 0 {
+                self.set_xattr_block(0, fs)?;
+                fs.dealloc_data(old_block);
+            }
+            return Ok(());
+        }
+        let xattr_block = if old_block == 0 {
+            fs.alloc_data()
+        } else {
+            fs.cow_data(old_block).unwrap_or(old_block)
+        };
+        if xattr_block != old_block {
+            self.set_xattr_block(xattr_block, fs)?;
+        }
+        get_block_cache(xattr_block as usize, Arc::clone(&self.block_device))
+            .lock()
+            .overwrite(&block);
+        Ok(())
+    }
+
+    /// 读取扩展属性
+    pub fn get_xattr(&self, name: &str) -> Result<Vec<u8>, FsError> {
+        let fs = self.fs.lock();
+        self.read_xattrs(&fs)?
+            .into_iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value)
+            .ok_or(FsError::NotFound)
+    }
+
+    /// 列出扩展属性名
+    pub fn list_xattr(&self) -> Result<Vec<String>, FsError> {
+        let fs = self.fs.lock();
+        let xattrs = self.read_xattrs(&fs)?;
+        Ok(xattrs.into_iter().map(|(key, _)| key).collect())
+    }
+
+    /// 设置扩展属性 已存在时覆盖
+    pub fn set_xattr(&self, name: &str, value: &[u8]) -> Result<(), FsError> {
+        self.update_xattr(name, value, false, false)
+    }
+
+    /// 设置扩展属性 create为true时要求属性不存在 replace为true时要求属性已存在
+    ///
+    /// 检查和修改都在文件系统锁内完成
+    pub fn update_xattr(
+        &self,
+        name: &str,
+        value: &[u8],
+        create: bool,
+        replace: bool,
+    ) -> Result<(), FsError> {
+        if name.is_empty() || name.len() > XATTR_NAME_LIMIT {
+            return Err(FsError::NameTooLong);
+        }
+        let mut fs = self.fs.lock();
+        let mut xattrs = self.read_xattrs(&fs)?;
+        match xattrs.iter_mut().find(|(key, _)| key == name) {
+            Some(_) if create => return Err(FsError::AlreadyExists),
+            Some((_, old)) => *old = value.to_vec(),
+            None if replace => return Err(FsError::NotFound),
+            None => xattrs.push((String::from(name), value.to_vec())),
+        }
+        self.write_xattrs(&xattrs, &mut fs)?;
+        block_cache_sync_all();
+        Ok(())
+    }
+
+    /// 删除扩展属性
+    pub fn remove_xattr(&self, name: &str) -> Result<(), FsError> {
+        let mut fs = self.fs.lock();
+        let mut xattrs = self.read_xattrs(&fs)?;
+        let len = xattrs.len();
+        xattrs.retain(|(key, _)| key != name);
+        if xattrs.len() == len {
+            return Err(FsError::NotFound);
+        }
+        self.write_xattrs(&xattrs, &mut fs)?;
+        block_cache_sync_all();
+        Ok(())
+    }
 }
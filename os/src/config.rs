@@ -7,5 +7,25 @@ pub const PAGE_SIZE_BITS: usize = 0xc;
 
 pub const TRAMPOLINE: usize = usize::MAX - PAGE_SIZE + 1;
 pub const TRAP_CONTEXT_BASE: usize = TRAMPOLINE - PAGE_SIZE;
+/// 用户栈区域的起始地址 各线程的用户栈依次向上排列 ELF之后的空间留给堆
+pub const USER_STACK_BASE: usize = 0x30_0000_0000;
+/// mmap没有指定地址时从这里开始找空闲的地址
+pub const MMAP_BASE: usize = 0x20_0000_0000;
 
 pub use crate::board::CLOCK_FREQ;
+
+/// 每个进程文件描述符的上限 文件描述符都小于这个数
+pub const FD_LIMIT: usize = 1024;
+
+/// 管道缓冲区大小
+pub const PIPE_BUFFER_SIZE: usize = 4096;
+
+/// 空闲页帧少于这个数时开始换出用户页
+pub const SWAP_LOW_FRAMES: usize = 32;
+/// 每次换出到空闲页帧达到这个数为止
+pub const SWAP_HIGH_FRAMES: usize = 64;
+
+/// tmpfs单个文件的最大字节数
+pub const TMPFS_FILE_MAX: usize = 0x4_0000;
+/// 一个tmpfs所有文件内容的最大字节数 内容保存在内核堆中
+pub const TMPFS_SIZE_MAX: usize = 0x10_0000;
@@ -4,9 +4,12 @@ use crate::board::BlockDeviceImpl;
 use alloc::sync::Arc;
 use easy_fs::BlockDevice;
 use lazy_static::*;
-pub use virtio_blk::VirtIOBlock;
+pub use virtio_blk::{VirtIOBlock, VIRTIO0, VIRTIO1};
 
 lazy_static! {
     /// 块设备
-    pub static ref BLOCK_DEVICE: Arc<dyn BlockDevice> = Arc::new(BlockDeviceImpl::new());
+    pub static ref BLOCK_DEVICE: Arc<dyn BlockDevice> = Arc::new(BlockDeviceImpl::new(VIRTIO0));
+    /// 交换区 没有接入第二块磁盘时不换出页面
+    pub static ref SWAP_DEVICE: Option<Arc<dyn BlockDevice>> = BlockDeviceImpl::probe(VIRTIO1)
+        .then(|| Arc::new(BlockDeviceImpl::new(VIRTIO1)) as Arc<dyn BlockDevice>);
 }
@@ -3,40 +3,77 @@ use crate::sync::UPSafeCell;
 use alloc::vec::Vec;
 use easy_fs::BlockDevice;
 use lazy_static::*;
+use riscv::register::satp;
 use virtio_drivers::{Hal, VirtIOBlk, VirtIOHeader};
 
-const VIRTIO0: usize = 0x10001000;
+/// 文件系统所在的磁盘
+pub const VIRTIO0: usize = 0x10001000;
+/// 交换区所在的磁盘
+pub const VIRTIO1: usize = 0x10002000;
+/// MMIO头部的魔数"virt"
+const VIRTIO_MAGIC: u32 = 0x7472_6976;
+/// 块设备的设备号 没有接入设备的槽位为0
+const VIRTIO_DEVICE_BLOCK: u32 = 2;
+/// MMIO配置空间的偏移 virtio-blk配置的第一项是以512字节扇区计的容量
+const VIRTIO_CONFIG: usize = 0x100;
 
 lazy_static! {
     static ref QUEUE_FRAMES: UPSafeCell<Vec<FrameTracker>> = unsafe { UPSafeCell::new(Vec::new()) };
 }
 
-pub struct VirtIOBlock(UPSafeCell<VirtIOBlk<'static, VirtioHal>>);
+pub struct VirtIOBlock {
+    blk: UPSafeCell<VirtIOBlk<'static, VirtioHal>>,
+    /// MMIO基地址
+    base: usize,
+}
 
 impl VirtIOBlock {
-    pub fn new() -> Self {
+    pub fn new(base: usize) -> Self {
+        unsafe {
+            Self {
+                blk: UPSafeCell::new(
+                    VirtIOBlk::<VirtioHal>::new(&mut *(base as *mut VirtIOHeader)).unwrap(),
+                ),
+                base,
+            }
+        }
+    }
+
+    /// base处是否接入了virtio块设备
+    pub fn probe(base: usize) -> bool {
         unsafe {
-            Self(UPSafeCell::new(
-                VirtIOBlk::<VirtioHal>::new(&mut *(VIRTIO0 as *mut VirtIOHeader)).unwrap(),
-            ))
+            (base as *const u32).read_volatile() == VIRTIO_MAGIC
+                && ((base + 8) as *const u32).read_volatile() == VIRTIO_DEVICE_BLOCK
         }
     }
 }
 
 impl BlockDevice for VirtIOBlock {
     fn read_block(&self, block_id: usize, buf: &mut [u8]) {
-        self.0
+        self.blk
             .exclusive_access()
             .read_block(block_id, buf)
             .expect("Error when reading VirtIOBlk");
     }
 
     fn write_block(&self, block_id: usize, buf: &[u8]) {
-        self.0
+        self.blk
             .exclusive_access()
             .write_block(block_id, buf)
             .expect("Error when writing VirtIOBlk");
     }
+
+    /// 写请求等设备完成后才返回 没有协商VIRTIO_BLK_F_FLUSH时设备按直写处理
+    /// 写完的数据已经落盘 不需要再发VIRTIO_BLK_T_FLUSH
+    fn flush(&self) -> bool {
+        true
+    }
+
+    fn num_blocks(&self) -> Option<usize> {
+        let capacity =
+            unsafe { ((self.base + VIRTIO_CONFIG) as *const u64).read_volatile() } as usize;
+        Some(capacity)
+    }
 }
 
 pub struct VirtioHal;
@@ -70,8 +107,9 @@ impl Hal for VirtioHal {
         addr
     }
 
+    /// 换出页时可能正在修改内核地址空间 不借用KERNEL_SPACE 内核运行时satp就是内核地址空间
     fn virt_to_phys(vaddr: usize) -> usize {
-        PageTable::from_token(kernel_token())
+        PageTable::from_token(satp::read().bits())
             .translate_va(VirtAddr::from(vaddr))
             .unwrap()
             .0
@@ -0,0 +1,287 @@
+use super::{alloc_fs_id, is_mounted, File, FileSystem, Inode, InodeId, InodeType, Tty};
+use crate::drivers::BLOCK_DEVICE;
+use crate::mm::UserBuffer;
+use crate::sync::UPSafeCell;
+use crate::timer::get_time;
+use alloc::{
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
+use easy_fs::{block_cache_read, BlockDevice, BLOCK_SZ};
+use lazy_static::*;
+
+/// 设备文件系统 挂载在/dev
+pub struct DevFs;
+
+impl DevFs {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self)
+    }
+}
+
+impl FileSystem for DevFs {
+    fn name(&self) -> &'static str {
+        "devfs"
+    }
+
+    fn root(&self) -> Arc<dyn Inode> {
+        Arc::new(DevDir)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum DevKind {
+    Null,
+    Zero,
+    Random,
+    Tty,
+    Vda,
+}
+
+/// 设备名和设备
+const DEVICES: &[(&str, DevKind)] = &[
+    ("null", DevKind::Null),
+    ("zero", DevKind::Zero),
+    ("random", DevKind::Random),
+    ("urandom", DevKind::Random),
+    ("tty", DevKind::Tty),
+    ("vda", DevKind::Vda),
+];
+
+/// /dev目录 设备固定 不能创建文件
+struct DevDir;
+
+impl Inode for DevDir {
+    fn itype(&self) -> InodeType {
+        InodeType::Dir
+    }
+
+    fn id(&self) -> InodeId {
+        InodeId {
+            fs: *DEVFS_ID,
+            ino: 0,
+        }
+    }
+
+    fn find(&self, name: &str) -> Option<Arc<dyn Inode>> {
+        DEVICES
+            .iter()
+            .find(|(dev_name, _)| *dev_name == name)
+            .map(|(_, kind)| Arc::new(DevInode(*kind)) as Arc<dyn Inode>)
+    }
+
+    fn ls(&self) -> Vec<String> {
+        DEVICES.iter().map(|(name, _)| name.to_string()).collect()
+    }
+
+    fn read_at(&self, _offset: usize, _buf: &mut [u8]) -> Option<usize> {
+        Some(0)
+    }
+
+    fn write_at(&self, _offset: usize, _buf: &[u8]) -> Option<usize> {
+        Some(0)
+    }
+}
+
+/// 设备节点 读写需要先打开得到设备文件
+struct DevInode(DevKind);
+
+impl Inode for DevInode {
+    fn itype(&self) -> InodeType {
+        match self.0 {
+            DevKind::Vda => InodeType::BlockDevice,
+            _ => InodeType::CharDevice,
+        }
+    }
+
+    /// 目录是0号 设备按在DEVICES中的位置编号
+    fn id(&self) -> InodeId {
+        InodeId {
+            fs: *DEVFS_ID,
+            ino: DEVICES
+                .iter()
+                .position(|(_, kind)| *kind == self.0)
+                .unwrap()
+                + 1,
+        }
+    }
+
+    fn read_at(&self, _offset: usize, _buf: &mut [u8]) -> Option<usize> {
+        Some(0)
+    }
+
+    fn write_at(&self, _offset: usize, _buf: &[u8]) -> Option<usize> {
+        Some(0)
+    }
+
+    /// 已挂载的块设备只能只读打开 直接写会被块缓存覆盖或破坏文件系统
+    fn open_device(&self, readable: bool, writable: bool) -> Option<Arc<dyn File + Send + Sync>> {
+        if self.0 == DevKind::Vda && writable && is_mounted("/dev/vda") {
+            return None;
+        }
+        Some(match self.0 {
+            DevKind::Null => Arc::new(CharDev::new(DevKind::Null, readable, writable)),
+            DevKind::Zero => Arc::new(CharDev::new(DevKind::Zero, readable, writable)),
+            DevKind::Random => Arc::new(CharDev::new(DevKind::Random, readable, writable)),
+            DevKind::Tty => Arc::new(Tty::new(readable, writable)),
+            DevKind::Vda => Arc::new(BlockDev::new(BLOCK_DEVICE.clone(), readable, writable)),
+        })
+    }
+}
+
+lazy_static! {
+    /// 设备固定 各次挂载的devfs共用一个文件系统编号
+    static ref DEVFS_ID: usize = alloc_fs_id();
+    /// 随机数状态 第一次使用时用时钟初始化
+    static ref RANDOM_STATE: UPSafeCell<u64> = unsafe { UPSafeCell::new(0) };
+}
+
+/// xorshift64 伪随机数
+fn next_random() -> u64 {
+    let mut state = RANDOM_STATE.exclusive_access();
+    if *state == 0 {
+        *state = get_time() as u64 | 1;
+    }
+    *state ^= *state << 13;
+    *state ^= *state >> 7;
+    *state ^= *state << 17;
+    *state
+}
+
+/// null zero random 三种字符设备
+struct CharDev {
+    kind: DevKind,
+    readable: bool,
+    writable: bool,
+}
+
+impl CharDev {
+    fn new(kind: DevKind, readable: bool, writable: bool) -> Self {
+        Self {
+            kind,
+            readable,
+            writable,
+        }
+    }
+}
+
+impl File for CharDev {
+    fn readable(&self) -> bool {
+        self.readable
+    }
+
+    fn writable(&self) -> bool {
+        self.writable
+    }
+
+    fn read(&self, mut buf: UserBuffer) -> Option<usize> {
+        match self.kind {
+            DevKind::Null => Some(0),
+            DevKind::Zero => {
+                for slice in buf.buffers.iter_mut() {
+                    slice.fill(0);
+                }
+                Some(buf.len())
+            }
+            _ => {
+                for slice in buf.buffers.iter_mut() {
+                    for chunk in slice.chunks_mut(8) {
+                        let random = next_random().to_le_bytes();
+                        chunk.copy_from_slice(&random[..chunk.len()]);
+                    }
+                }
+                Some(buf.len())
+            }
+        }
+    }
+
+    /// 写入的数据直接丢弃
+    fn write(&self, buf: UserBuffer) -> Option<usize> {
+        Some(buf.len())
+    }
+}
+
+/// 块设备文件 读经过块缓存 只有没挂载时才能写
+struct BlockDev {
+    device: Arc<dyn BlockDevice>,
+    readable: bool,
+    writable: bool,
+    offset: UPSafeCell<usize>,
+}
+
+impl BlockDev {
+    fn new(device: Arc<dyn BlockDevice>, readable: bool, writable: bool) -> Self {
+        Self {
+            device,
+            readable,
+            writable,
+            offset: unsafe { UPSafeCell::new(0) },
+        }
+    }
+
+    /// 从offset开始最多还能访问的字节数
+    fn remain(&self, offset: usize) -> usize {
+        match self.device.num_blocks() {
+            Some(blocks) => (blocks * BLOCK_SZ).saturating_sub(offset),
+            None => usize::MAX,
+        }
+    }
+}
+
+impl File for BlockDev {
+    fn readable(&self) -> bool {
+        self.readable
+    }
+
+    fn writable(&self) -> bool {
+        self.writable
+    }
+
+    fn read(&self, mut buf: UserBuffer) -> Option<usize> {
+        let mut offset = self.offset.exclusive_access();
+        let mut block = [0u8; BLOCK_SZ];
+        let mut total = 0;
+        for slice in buf.buffers.iter_mut() {
+            let mut start = 0;
+            while start < slice.len() && self.remain(*offset) > 0 {
+                let block_offset = *offset % BLOCK_SZ;
+                let len = (slice.len() - start).min(BLOCK_SZ - block_offset);
+                block_cache_read(*offset / BLOCK_SZ, self.device.clone(), &mut block);
+                slice[start..start + len].copy_from_slice(&block[block_offset..block_offset + len]);
+                start += len;
+                *offset += len;
+                total += len;
+            }
+        }
+        Some(total)
+    }
+
+    fn write(&self, buf: UserBuffer) -> Option<usize> {
+        let mut offset = self.offset.exclusive_access();
+        let mut block = [0u8; BLOCK_SZ];
+        let mut total = 0;
+        for slice in buf.buffers.iter() {
+            let mut start = 0;
+            while start < slice.len() && self.remain(*offset) > 0 {
+                let block_id = *offset / BLOCK_SZ;
+                let block_offset = *offset % BLOCK_SZ;
+                let len = (slice.len() - start).min(BLOCK_SZ - block_offset);
+                // 不是整块写入时先读出原来的内容
+                if len < BLOCK_SZ {
+                    self.device.read_block(block_id, &mut block);
+                }
+                block[block_offset..block_offset + len].copy_from_slice(&slice[start..start + len]);
+                self.device.write_block(block_id, &block);
+                start += len;
+                *offset += len;
+                total += len;
+            }
+        }
+        Some(total)
+    }
+
+    fn sync(&self) -> bool {
+        self.device.flush()
+    }
+}
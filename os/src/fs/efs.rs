@@ -0,0 +1,142 @@
+use super::{alloc_fs_id, FileSystem, Inode, InodeId, InodeType, XattrFlags};
+use alloc::{string::String, sync::Arc, vec::Vec};
+use easy_fs::{BlockDevice, EasyFileSystem};
+
+/// 挂载的easy-fs
+pub struct EasyFs {
+    id: usize,
+    root: Arc<easy_fs::Inode>,
+}
+
+impl EasyFs {
+    pub fn open(block_device: Arc<dyn BlockDevice>) -> Arc<Self> {
+        let efs = EasyFileSystem::open(block_device);
+        Arc::new(Self {
+            id: alloc_fs_id(),
+            root: Arc::new(EasyFileSystem::root_inode(&efs)),
+        })
+    }
+}
+
+impl FileSystem for EasyFs {
+    fn name(&self) -> &'static str {
+        "easy-fs"
+    }
+
+    fn sync(&self) -> bool {
+        self.root.sync_fs()
+    }
+
+    fn root(&self) -> Arc<dyn Inode> {
+        Arc::new(EfsInode(self.root.clone(), self.id))
+    }
+}
+
+/// easy-fs的索引节点和所在文件系统的编号
+pub struct EfsInode(Arc<easy_fs::Inode>, usize);
+
+impl Inode for EfsInode {
+    fn id(&self) -> InodeId {
+        InodeId {
+            fs: self.1,
+            ino: self.0.inode_id() as usize,
+        }
+    }
+
+    fn itype(&self) -> InodeType {
+        if self.0.is_dir() {
+            InodeType::Dir
+        } else if self.0.is_fifo() {
+            InodeType::Fifo
+        } else {
+            InodeType::File
+        }
+    }
+
+    fn find(&self, name: &str) -> Option<Arc<dyn Inode>> {
+        // 目录项损坏时查找失败
+        match self.0.find(name) {
+            Ok(inode) => inode.map(|inode| Arc::new(EfsInode(inode, self.1)) as Arc<dyn Inode>),
+            Err(err) => {
+                warn!("easy-fs: {:?} when looking up {}", err, name);
+                None
+            }
+        }
+    }
+
+    fn create(&self, name: &str, itype: InodeType) -> Option<Arc<dyn Inode>> {
+        let inode = match itype {
+            InodeType::File => self.0.create(name),
+            InodeType::Dir => self.0.mkdir(name),
+            InodeType::Fifo => self.0.mkfifo(name),
+            _ => None,
+        };
+        inode.map(|inode| Arc::new(EfsInode(inode, self.1)) as Arc<dyn Inode>)
+    }
+
+    fn ls(&self) -> Vec<String> {
+        match self.0.ls() {
+            Ok(names) => names,
+            Err(err) => {
+                warn!("easy-fs: {:?} when listing directory", err);
+                Vec::new()
+            }
+        }
+    }
+
+    /// 数据块损坏时返回None 不能当成读到了文件末尾
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> Option<usize> {
+        match self.0.read_at(offset, buf) {
+            Ok(len) => Some(len),
+            Err(err) => {
+                warn!("easy-fs: {:?} at offset {}", err, offset);
+                None
+            }
+        }
+    }
+
+    fn write_at(&self, offset: usize, buf: &[u8]) -> Option<usize> {
+        match self.0.write_at(offset, buf) {
+            Ok(len) => Some(len),
+            Err(err) => {
+                warn!("easy-fs: {:?} at offset {}", err, offset);
+                None
+            }
+        }
+    }
+
+    fn size(&self) -> usize {
+        self.0.size()
+    }
+
+    fn clear(&self) {
+        self.0.clear();
+    }
+
+    fn sync(&self) -> bool {
+        self.0.fsync()
+    }
+
+    fn get_xattr(&self, name: &str) -> Option<Vec<u8>> {
+        self.0.get_xattr(name).ok()
+    }
+
+    fn list_xattr(&self) -> Option<Vec<String>> {
+        self.0.list_xattr().ok()
+    }
+
+    fn set_xattr(&self, name: &str, value: &[u8], flags: XattrFlags) -> bool {
+        self.0
+            .update_xattr(
+                name,
+                value,
+                flags.contains(XattrFlags::CREATE),
+                flags.contains(XattrFlags::REPLACE),
+            )
+            .is_ok()
+    }
+
+    fn remove_xattr(&self, name: &str) -> bool {
+        self.0.remove_xattr(name).is_ok()
+    }
+}
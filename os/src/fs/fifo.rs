@@ -0,0 +1,50 @@
+use super::{File, InodeId, OpenFlags, Pipe, PipeRingBuffer};
+use crate::config::PIPE_BUFFER_SIZE;
+use crate::sync::UPSafeCell;
+use alloc::{
+    collections::BTreeMap,
+    string::{String, ToString},
+    sync::{Arc, Weak},
+};
+use lazy_static::*;
+
+lazy_static! {
+    /// 打开的命名管道 按绝对路径共享缓冲区 所有端都关闭后缓冲区释放
+    static ref FIFOS: UPSafeCell<BTreeMap<String, Weak<UPSafeCell<PipeRingBuffer>>>> =
+        unsafe { UPSafeCell::new(BTreeMap::new()) };
+}
+
+/// 打开命名管道
+///
+/// 只读或只写打开时阻塞到另一端也被打开 设置了NONBLOCK时只读直接返回 只写失败
+pub fn open_fifo(path: &str, id: InodeId, flags: OpenFlags) -> Option<Arc<dyn File + Send + Sync>> {
+    let (readable, writable) = flags.read_write();
+    let buffer = {
+        let mut fifos = FIFOS.exclusive_access();
+        fifos.retain(|_, buffer| buffer.strong_count() > 0);
+        match fifos.get(path).and_then(|buffer| buffer.upgrade()) {
+            Some(buffer) => buffer,
+            None => {
+                let buffer =
+                    Arc::new(unsafe { UPSafeCell::new(PipeRingBuffer::new(PIPE_BUFFER_SIZE)) });
+                fifos.insert(path.to_string(), Arc::downgrade(&buffer));
+                buffer
+            }
+        }
+    };
+    if writable && !readable && flags.contains(OpenFlags::NONBLOCK) {
+        if buffer.exclusive_access().all_read_ends_closed() {
+            return None;
+        }
+    }
+    let pipe = Arc::new(Pipe::new(
+        readable,
+        writable,
+        Some((path.to_string(), id)),
+        buffer.clone(),
+    ));
+    if readable != writable && !flags.contains(OpenFlags::NONBLOCK) {
+        PipeRingBuffer::wait_other_end(&buffer, readable);
+    }
+    Some(pipe)
+}
@@ -1,45 +1,42 @@
-use super::File;
-use crate::{drivers::BLOCK_DEVICE, sync::UPSafeCell};
-use alloc::{sync::Arc, vec::Vec};
+use super::{
+    absolute_path, lookup, lookup_parent, open_fifo, release_locks, File, Inode, InodeType,
+    LockOwner,
+};
+use crate::mm::page_cache;
+use crate::sync::UPSafeCell;
+use alloc::{string::String, sync::Arc, vec::Vec};
 use bitflags::*;
-use easy_fs::{EasyFileSystem, Inode};
-use lazy_static::lazy_static;
 
 pub struct OSInodeInner {
     offset: usize,
-    inode: Arc<Inode>,
+    /// 可以用fcntl修改的状态标志
+    status: OpenFlags,
+    inode: Arc<dyn Inode>,
 }
 
 pub struct OSInode {
     readable: bool,
     writable: bool,
+    path: String,
     inner: UPSafeCell<OSInodeInner>,
 }
 
 impl OSInode {
-    pub fn new(readable: bool, writable: bool, inode: Arc<Inode>) -> Self {
+    pub fn new(flags: OpenFlags, path: &str, inode: Arc<dyn Inode>) -> Self {
+        let (readable, writable) = flags.read_write();
         Self {
             readable,
             writable,
-            inner: unsafe { UPSafeCell::new(OSInodeInner { offset: 0, inode }) },
+            path: absolute_path("/", path),
+            inner: unsafe {
+                UPSafeCell::new(OSInodeInner {
+                    offset: 0,
+                    status: flags & OpenFlags::STATUS,
+                    inode,
+                })
+            },
         }
     }
-
-    /// Read all data inside a inode into vector
-    pub fn read_all(&self) -> Vec<u8> {
-        let mut inner = self.inner.exclusive_access();
-        let mut buffer = [0u8; 512];
-        let mut v: Vec<u8> = Vec::new();
-        loop {
-            let len = inner.inode.read_at(inner.offset, &mut buffer);
-            if len == 0 {
-                break;
-            }
-            inner.offset += len;
-            v.extend_from_slice(&buffer[..len]);
-        }
-        v
-    }
 }
 
 impl File for OSInode {
@@ -51,43 +48,129 @@ impl File for OSInode {
         self.writable
     }
 
-    fn read(&self, mut buf: crate::mm::UserBuffer) -> usize {
+    fn read(&self, mut buf: crate::mm::UserBuffer) -> Option<usize> {
         let mut inner = self.inner.exclusive_access();
         let mut total_read_size = 0usize;
         for slice in buf.buffers.iter_mut() {
-            let read_size = inner.inode.read_at(inner.offset, *slice);
+            let read_size = match page_cache::read_at(&inner.inode, inner.offset, slice) {
+                Some(read_size) => read_size,
+                // 已经读到的部分照常返回 什么都没读到时报告错误
+                None if total_read_size == 0 => return None,
+                None => break,
+            };
             if read_size == 0 {
                 break;
             }
             inner.offset += read_size;
             total_read_size += read_size;
         }
-        total_read_size
+        Some(total_read_size)
     }
 
-    fn write(&self, buf: crate::mm::UserBuffer) -> usize {
+    fn write(&self, buf: crate::mm::UserBuffer) -> Option<usize> {
         let mut inner = self.inner.exclusive_access();
+        // 持有锁期间移到文件末尾 追加写不会和其他写交错
+        if inner.status.contains(OpenFlags::APPEND) {
+            inner.offset = inner.inode.size();
+        }
         let mut total_write_size = 0usize;
         for slice in buf.buffers.iter() {
-            let write_size = inner.inode.write_at(inner.offset, *slice);
-            assert_eq!(write_size, slice.len());
+            let write_size = match inner.inode.write_at(inner.offset, *slice) {
+                Some(write_size) => write_size,
+                None if total_write_size == 0 => return None,
+                None => break,
+            };
+            page_cache::update(inner.inode.id(), inner.offset, &slice[..write_size]);
             inner.offset += write_size;
             total_write_size += write_size;
+            // 超出大小限制时只写入了一部分
+            if write_size < slice.len() {
+                break;
+            }
+        }
+        Some(total_write_size)
+    }
+
+    fn path(&self) -> Option<String> {
+        Some(self.path.clone())
+    }
+
+    fn ls(&self) -> Option<Vec<String>> {
+        let inner = self.inner.exclusive_access();
+        if inner.inode.itype() == InodeType::Dir {
+            Some(inner.inode.ls())
+        } else {
+            None
+        }
+    }
+
+    fn pread(&self, mut offset: usize, mut buf: crate::mm::UserBuffer) -> Option<usize> {
+        let inner = self.inner.exclusive_access();
+        let mut total_read_size = 0usize;
+        for slice in buf.buffers.iter_mut() {
+            let read_size = match page_cache::read_at(&inner.inode, offset, slice) {
+                Some(read_size) => read_size,
+                None if total_read_size == 0 => return None,
+                None => break,
+            };
+            offset += read_size;
+            total_read_size += read_size;
+            if read_size < slice.len() {
+                break;
+            }
+        }
+        Some(total_read_size)
+    }
+
+    fn pwrite(&self, mut offset: usize, buf: crate::mm::UserBuffer) -> Option<usize> {
+        let inner = self.inner.exclusive_access();
+        let mut total_write_size = 0usize;
+        for slice in buf.buffers.iter() {
+            let write_size = match inner.inode.write_at(offset, *slice) {
+                Some(write_size) => write_size,
+                None if total_write_size == 0 => return None,
+                None => break,
+            };
+            page_cache::update(inner.inode.id(), offset, &slice[..write_size]);
+            offset += write_size;
+            total_write_size += write_size;
+            if write_size < slice.len() {
+                break;
+            }
         }
-        total_write_size
+        Some(total_write_size)
+    }
+
+    fn sync(&self) -> bool {
+        let inner = self.inner.exclusive_access();
+        page_cache::flush(inner.inode.id());
+        inner.inode.sync()
+    }
+
+    fn inode(&self) -> Option<Arc<dyn Inode>> {
+        Some(self.inner.exclusive_access().inode.clone())
+    }
+
+    fn status(&self) -> OpenFlags {
+        self.inner.exclusive_access().status
+    }
+
+    fn set_status(&self, flags: OpenFlags) -> bool {
+        self.inner.exclusive_access().status = flags & OpenFlags::STATUS;
+        true
     }
 }
 
-lazy_static! {
-    pub static ref ROOT_INODE: Arc<Inode> = {
-        let efs = EasyFileSystem::open(BLOCK_DEVICE.clone());
-        Arc::new(EasyFileSystem::root_inode(&efs))
-    };
+/// 关闭最后一个引用这个文件的描述符时释放flock锁
+impl Drop for OSInode {
+    fn drop(&mut self) {
+        release_locks(LockOwner::File(self as *const _ as usize));
+    }
 }
 
 pub fn list_apps() {
     kernel!("============== LOAD APPS ================");
-    for app in ROOT_INODE.ls() {
+    for app in lookup("/").unwrap().ls() {
         kernel!("{}", app);
     }
     kernel!("=========================================\n");
@@ -104,43 +187,76 @@ bitflags! {
         const RDWR = 1 << 1;
         ///Allow create
         const CREATE = 1 << 9;
+        ///Fail if the file already exists, used with CREATE
+        const EXCL = 1 << 7;
         ///Clear file and return an empty one
         const TRUNC = 1 << 10;
+        ///Do not block on read or write
+        const NONBLOCK = 1 << 11;
+        ///Every write goes to the end of the file
+        const APPEND = 1 << 12;
+        ///Fail if the path is not a directory
+        const DIRECTORY = 1 << 16;
+        ///Close the fd on exec
+        const CLOEXEC = 1 << 19;
+        ///Status flags that can be changed by fcntl
+        const STATUS = Self::NONBLOCK.bits | Self::APPEND.bits;
     }
 }
 
 impl OpenFlags {
-    /// Do not check validity for simplicity
+    /// Only the access mode bits are checked
     /// Return (readable, writable)
     pub fn read_write(&self) -> (bool, bool) {
-        if self.is_empty() {
-            (true, false)
-        } else if self.contains(Self::WRONLY) {
+        if self.contains(Self::WRONLY) {
             (false, true)
-        } else {
+        } else if self.contains(Self::RDWR) {
             (true, true)
+        } else {
+            (true, false)
         }
     }
 }
 
-/// 打开根目录下文件
-pub fn open_file(name: &str, flags: OpenFlags) -> Option<Arc<OSInode>> {
+/// 按路径打开文件
+pub fn open_file(path: &str, flags: OpenFlags) -> Option<Arc<OSInode>> {
+    let inode = match lookup(path) {
+        Some(_) if flags.contains(OpenFlags::CREATE | OpenFlags::EXCL) => return None,
+        Some(inode) => inode,
+        None if flags.contains(OpenFlags::CREATE) => {
+            let (parent, name) = lookup_parent(path)?;
+            parent.create(&name, InodeType::File)?
+        }
+        None => return None,
+    };
+    if flags.contains(OpenFlags::DIRECTORY) && inode.itype() != InodeType::Dir {
+        return None;
+    }
+    if flags.contains(OpenFlags::TRUNC) {
+        inode.clear();
+        page_cache::clear(inode.id());
+    }
+    Some(Arc::new(OSInode::new(flags, path, inode)))
+}
+
+/// 打开文件或设备 设备节点得到对应的设备文件 命名管道得到管道的一端
+pub fn open_path(path: &str, flags: OpenFlags) -> Option<Arc<dyn File + Send + Sync>> {
     let (readable, writable) = flags.read_write();
-    if flags.contains(OpenFlags::CREATE) {
-        if let Some(inode) = ROOT_INODE.find(name) {
-            inode.clear();
-            Some(Arc::new(OSInode::new(readable, writable, inode)))
-        } else {
-            ROOT_INODE
-                .create(name)
-                .map(|inode| Arc::new(OSInode::new(readable, writable, inode)))
+    if !flags.contains(OpenFlags::DIRECTORY) {
+        if let Some(inode) = lookup(path) {
+            if flags.contains(OpenFlags::CREATE | OpenFlags::EXCL) {
+                return None;
+            }
+            if inode.itype() == InodeType::Fifo {
+                return open_fifo(&absolute_path("/", path), inode.id(), flags);
+            }
+            if matches!(
+                inode.itype(),
+                InodeType::CharDevice | InodeType::BlockDevice
+            ) {
+                return inode.open_device(readable, writable);
+            }
         }
-    } else {
-        ROOT_INODE.find(name).map(|inode| {
-            if flags.contains(OpenFlags::TRUNC) {
-                inode.clear();
-            };
-            Arc::new(OSInode::new(readable, writable, inode))
-        })
     }
+    open_file(path, flags).map(|inode| inode as Arc<dyn File + Send + Sync>)
 }
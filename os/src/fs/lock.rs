@@ -0,0 +1,136 @@
+use super::pipe::wake_all;
+use super::InodeId;
+use crate::sync::UPSafeCell;
+use crate::task::{block_current_and_run_next, current_task, TaskControlBlock};
+use alloc::{
+    collections::{BTreeMap, VecDeque},
+    sync::Arc,
+    vec::Vec,
+};
+use lazy_static::*;
+
+/// 锁的持有者
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockOwner {
+    /// flock锁属于打开的文件 dup和fork得到的文件描述符共享
+    File(usize),
+    /// fcntl记录锁属于进程
+    Process(usize),
+}
+
+impl LockOwner {
+    fn is_flock(&self) -> bool {
+        matches!(self, LockOwner::File(_))
+    }
+}
+
+/// 加锁的字节范围[start, end)
+#[derive(Debug, Clone, Copy)]
+pub struct FileLock {
+    pub owner: LockOwner,
+    pub start: usize,
+    pub end: usize,
+    /// 写锁 否则是读锁
+    pub exclusive: bool,
+}
+
+impl FileLock {
+    /// 两把锁不能同时持有 flock和记录锁互不影响
+    fn conflicts(&self, other: &FileLock) -> bool {
+        self.owner != other.owner
+            && self.owner.is_flock() == other.owner.is_flock()
+            && (self.exclusive || other.exclusive)
+            && self.start < other.end
+            && other.start < self.end
+    }
+}
+
+#[derive(Default)]
+struct InodeLocks {
+    locks: Vec<FileLock>,
+    /// 等待锁释放的任务
+    waiters: VecDeque<Arc<TaskControlBlock>>,
+}
+
+impl InodeLocks {
+    /// 去掉owner在[start, end)中的锁 部分重叠的锁被切开
+    fn remove_range(&mut self, owner: LockOwner, start: usize, end: usize) {
+        let mut locks = Vec::new();
+        for lock in self.locks.drain(..) {
+            if lock.owner != owner || lock.end <= start || end <= lock.start {
+                locks.push(lock);
+                continue;
+            }
+            if lock.start < start {
+                locks.push(FileLock { end: start, ..lock });
+            }
+            if end < lock.end {
+                locks.push(FileLock { start: end, ..lock });
+            }
+        }
+        self.locks = locks;
+    }
+}
+
+lazy_static! {
+    /// 文件锁 以索引节点的标识区分文件
+    static ref FILE_LOCKS: UPSafeCell<BTreeMap<InodeId, InodeLocks>> =
+        unsafe { UPSafeCell::new(BTreeMap::new()) };
+}
+
+/// 和lock冲突的第一把锁
+pub fn test_lock(id: InodeId, lock: &FileLock) -> Option<FileLock> {
+    let table = FILE_LOCKS.exclusive_access();
+    table
+        .get(&id)?
+        .locks
+        .iter()
+        .find(|other| other.conflicts(lock))
+        .copied()
+}
+
+/// 加锁 冲突时wait为false直接返回false 否则阻塞到可以加锁
+///
+/// 调用者在等待期间要保持锁的持有者存活 否则得到的锁不会再被释放
+pub fn set_lock(id: InodeId, lock: FileLock, wait: bool) -> bool {
+    loop {
+        let mut table = FILE_LOCKS.exclusive_access();
+        let inode_locks = table.entry(id).or_default();
+        if !inode_locks.locks.iter().any(|other| other.conflicts(&lock)) {
+            inode_locks.remove_range(lock.owner, lock.start, lock.end);
+            inode_locks.locks.push(lock);
+            return true;
+        }
+        if !wait {
+            return false;
+        }
+        inode_locks.waiters.push_back(current_task().unwrap());
+        drop(table);
+        block_current_and_run_next();
+    }
+}
+
+/// 解锁[start, end) 唤醒等待这个文件的任务
+pub fn unlock(id: InodeId, owner: LockOwner, start: usize, end: usize) {
+    let mut table = FILE_LOCKS.exclusive_access();
+    if let Some(inode_locks) = table.get_mut(&id) {
+        inode_locks.remove_range(owner, start, end);
+        wake_all(&mut inode_locks.waiters);
+        if inode_locks.locks.is_empty() {
+            table.remove(&id);
+        }
+    }
+}
+
+/// 释放owner持有的所有锁 关闭文件或者进程退出时调用
+pub fn release_locks(owner: LockOwner) {
+    let mut table = FILE_LOCKS.exclusive_access();
+    for inode_locks in table.values_mut() {
+        let len = inode_locks.locks.len();
+        inode_locks.locks.retain(|lock| lock.owner != owner);
+        if inode_locks.locks.len() != len {
+            wake_all(&mut inode_locks.waiters);
+        }
+    }
+    table.retain(|_, inode_locks| !inode_locks.locks.is_empty());
+}
@@ -1,15 +1,110 @@
+mod devfs;
+mod efs;
+mod fifo;
 mod inode;
+mod lock;
+mod mount;
 mod pipe;
+mod procfs;
 mod stdio;
+mod tmpfs;
+mod vfs;
 
 use crate::mm::UserBuffer;
+use crate::task::TaskControlBlock;
+use alloc::{string::String, sync::Arc, vec::Vec};
+use bitflags::*;
+pub use devfs::DevFs;
+pub use efs::EasyFs;
+pub use fifo::open_fifo;
 pub use inode::*;
+pub use lock::{release_locks, set_lock, test_lock, unlock, FileLock, LockOwner};
+pub use mount::{
+    absolute_path, init, is_mounted, lookup, lookup_parent, mkdir, mknod, mount, sync_all, sync_fs,
+    umount,
+};
 pub use pipe::*;
-pub use stdio::{Stdin, Stdout};
+pub use procfs::ProcFs;
+pub use stdio::{Stdin, Stdout, Tty};
+pub use tmpfs::TmpFs;
+pub use vfs::{alloc_fs_id, FileSystem, Inode, InodeId, InodeType, XattrFlags};
+
+bitflags! {
+    /// poll关心的事件和返回的就绪状态
+    pub struct PollEvents: u16 {
+        /// 有数据可读
+        const IN = 1 << 0;
+        const PRI = 1 << 1;
+        /// 可以写入
+        const OUT = 1 << 2;
+        const ERR = 1 << 3;
+        /// 对端已经关闭
+        const HUP = 1 << 4;
+        /// 文件描述符无效
+        const NVAL = 1 << 5;
+    }
+}
 
 pub trait File: Send + Sync {
     fn readable(&self) -> bool;
     fn writable(&self) -> bool;
-    fn read(&self, buf: UserBuffer) -> usize;
-    fn write(&self, buf: UserBuffer) -> usize;
+    /// 读到的字节数 出错时返回None
+    fn read(&self, buf: UserBuffer) -> Option<usize>;
+    /// 写入的字节数 出错时返回None
+    fn write(&self, buf: UserBuffer) -> Option<usize>;
+    /// 目录中的文件名 不是目录时返回None
+    fn ls(&self) -> Option<Vec<String>> {
+        None
+    }
+    /// 打开时的绝对路径 管道等没有路径
+    fn path(&self) -> Option<String> {
+        None
+    }
+    /// 文件状态标志 dup和fork得到的文件描述符共享
+    fn status(&self) -> OpenFlags {
+        OpenFlags::empty()
+    }
+    /// 修改文件状态标志 不支持时返回false
+    fn set_status(&self, _flags: OpenFlags) -> bool {
+        false
+    }
+    /// 从指定偏移读 不改变文件偏移 不能定位的文件返回None
+    fn pread(&self, _offset: usize, _buf: UserBuffer) -> Option<usize> {
+        None
+    }
+    /// 在指定偏移写 不改变文件偏移
+    fn pwrite(&self, _offset: usize, _buf: UserBuffer) -> Option<usize> {
+        None
+    }
+    /// 把写入的数据落到设备上 管道等不支持时返回false
+    fn sync(&self) -> bool {
+        false
+    }
+    /// 可以映射到内存的文件返回索引节点
+    fn inode(&self) -> Option<Arc<dyn Inode>> {
+        None
+    }
+    /// 文件所在索引节点的标识 文件锁以它区分文件 管道等没有索引节点
+    fn inode_id(&self) -> Option<InodeId> {
+        self.inode().map(|inode| inode.id())
+    }
+    /// 当前就绪的事件 普通文件读写都不会阻塞
+    fn poll(&self, events: PollEvents) -> PollEvents {
+        let mut ready = PollEvents::empty();
+        if self.readable() {
+            ready |= PollEvents::IN;
+        }
+        if self.writable() {
+            ready |= PollEvents::OUT;
+        }
+        ready & events
+    }
+    /// 等待就绪 把任务加入文件的等待队列 状态变化时被唤醒
+    ///
+    /// 就绪状态不会变化的文件不需要等待 不能主动唤醒的文件返回false
+    fn poll_register(&self, _task: &Arc<TaskControlBlock>) -> bool {
+        true
+    }
+    /// 从文件的等待队列中移除任务
+    fn poll_unregister(&self, _task: &Arc<TaskControlBlock>) {}
 }
@@ -0,0 +1,250 @@
+use super::{DevFs, EasyFs, FileSystem, Inode, InodeType, ProcFs, TmpFs};
+use crate::drivers::BLOCK_DEVICE;
+use crate::mm::page_cache;
+use crate::sync::UPSafeCell;
+use crate::task::manager::{pid2process, pids};
+use alloc::vec;
+use alloc::{
+    format,
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
+use lazy_static::*;
+
+/// 挂载点
+struct Mount {
+    /// 挂载路径 已规范化的各级目录名
+    path: Vec<String>,
+    /// 挂载来源 如/dev/vda
+    source: String,
+    fs: Arc<dyn FileSystem>,
+}
+
+pub struct MountTable {
+    mounts: Vec<Mount>,
+}
+
+impl MountTable {
+    /// 路径所在的文件系统 以及剩余的路径
+    fn resolve<'a>(&self, components: &'a [&'a str]) -> (Arc<dyn FileSystem>, &'a [&'a str]) {
+        let mount = self
+            .mounts
+            .iter()
+            .filter(|mount| {
+                mount.path.len() <= components.len()
+                    && mount.path.iter().zip(components).all(|(a, b)| a == b)
+            })
+            .max_by_key(|mount| mount.path.len())
+            .unwrap();
+        (mount.fs.clone(), &components[mount.path.len()..])
+    }
+}
+
+lazy_static! {
+    /// 挂载表 easy-fs挂载在根目录
+    pub static ref MOUNT_TABLE: UPSafeCell<MountTable> = unsafe {
+        UPSafeCell::new(MountTable {
+            mounts: vec![Mount {
+                path: Vec::new(),
+                source: "/dev/vda".to_string(),
+                fs: EasyFs::open(BLOCK_DEVICE.clone()),
+            }],
+        })
+    };
+}
+
+/// 把路径拆分成各级目录名 处理.和..
+///
+/// 内核中的相对路径都从根目录开始 进程的相对路径先用absolute_path补全
+pub fn split_path(path: &str) -> Vec<&str> {
+    let mut components = Vec::new();
+    for name in path.split('/') {
+        match name {
+            "" | "." => {}
+            ".." => {
+                components.pop();
+            }
+            _ => components.push(name),
+        }
+    }
+    components
+}
+
+/// 相对于cwd的路径转换成规范的绝对路径
+pub fn absolute_path(cwd: &str, path: &str) -> String {
+    let components = if path.starts_with('/') {
+        split_path(path)
+    } else {
+        let mut components = split_path(cwd);
+        for name in path.split('/') {
+            match name {
+                "" | "." => {}
+                ".." => {
+                    components.pop();
+                }
+                _ => components.push(name),
+            }
+        }
+        components
+    };
+    format!("/{}", components.join("/"))
+}
+
+/// 按路径查找索引节点
+pub fn lookup(path: &str) -> Option<Arc<dyn Inode>> {
+    let components = split_path(path);
+    let (fs, rest) = MOUNT_TABLE.exclusive_access().resolve(&components);
+    let mut inode = fs.root();
+    for name in rest {
+        if inode.itype() != InodeType::Dir {
+            return None;
+        }
+        inode = inode.find(name)?;
+    }
+    Some(inode)
+}
+
+/// 查找路径的父目录 返回父目录和最后一级名字
+pub fn lookup_parent(path: &str) -> Option<(Arc<dyn Inode>, String)> {
+    let mut components = split_path(path);
+    let name = components.pop()?.to_string();
+    let parent = lookup(&components.join("/"))?;
+    if parent.itype() != InodeType::Dir {
+        return None;
+    }
+    Some((parent, name))
+}
+
+/// 根据文件系统类型和来源创建文件系统
+fn new_fs(fstype: &str, source: &str) -> Option<Arc<dyn FileSystem>> {
+    match fstype {
+        // 块缓存按块号全局共享 只能有一个easy-fs卷
+        "easy-fs" if source == "/dev/vda" => Some(EasyFs::open(BLOCK_DEVICE.clone())),
+        "tmpfs" => Some(TmpFs::new()),
+        "devfs" => Some(DevFs::new()),
+        "procfs" => Some(ProcFs::new()),
+        _ => None,
+    }
+}
+
+/// 来源是否已经挂载
+pub fn is_mounted(source: &str) -> bool {
+    MOUNT_TABLE
+        .exclusive_access()
+        .mounts
+        .iter()
+        .any(|mount| mount.source == source)
+}
+
+/// 挂载文件系统 同一块设备不能重复挂载
+pub fn mount(source: &str, target: &str, fstype: &str) -> bool {
+    let path: Vec<String> = split_path(target).iter().map(|s| s.to_string()).collect();
+    {
+        let table = MOUNT_TABLE.exclusive_access();
+        if table.mounts.iter().any(|mount| {
+            mount.path == path || (mount.source == source && source.starts_with("/dev/"))
+        }) {
+            return false;
+        }
+    }
+    match lookup(target) {
+        Some(dir) if dir.itype() == InodeType::Dir => {}
+        _ => return false,
+    }
+    let fs = match new_fs(fstype, source) {
+        Some(fs) => fs,
+        None => return false,
+    };
+    info!("mount {} ({}) on /{}", source, fs.name(), path.join("/"));
+    MOUNT_TABLE.exclusive_access().mounts.push(Mount {
+        path,
+        source: source.to_string(),
+        fs,
+    });
+    true
+}
+
+/// 挂载点下是否有进程打开或映射的文件 或者进程的当前目录在其中
+fn mount_busy(path: &[String]) -> bool {
+    let under = |abs_path: &str| {
+        let components = split_path(abs_path);
+        components.len() >= path.len() && path.iter().zip(&components).all(|(a, b)| a == b)
+    };
+    pids().into_iter().filter_map(pid2process).any(|process| {
+        process
+            .inner_exclusive_access()
+            .uses_path(|abs_path| under(abs_path))
+    })
+}
+
+/// 卸载文件系统 根目录 还有子挂载点的和正在使用的不能卸载
+pub fn umount(target: &str) -> bool {
+    let path: Vec<String> = split_path(target).iter().map(|s| s.to_string()).collect();
+    if path.is_empty() || mount_busy(&path) {
+        return false;
+    }
+    let mut table = MOUNT_TABLE.exclusive_access();
+    if table
+        .mounts
+        .iter()
+        .any(|mount| mount.path.len() > path.len() && mount.path.starts_with(&path))
+    {
+        return false;
+    }
+    let len = table.mounts.len();
+    table.mounts.retain(|mount| mount.path != path);
+    table.mounts.len() != len
+}
+
+/// 写回所有文件系统 先把共享映射的修改写进文件 有设备不能保证落盘时返回false
+pub fn sync_all() -> bool {
+    page_cache::flush_all();
+    let filesystems: Vec<Arc<dyn FileSystem>> = MOUNT_TABLE
+        .exclusive_access()
+        .mounts
+        .iter()
+        .map(|mount| mount.fs.clone())
+        .collect();
+    filesystems
+        .iter()
+        .fold(true, |durable, fs| fs.sync() && durable)
+}
+
+/// 写回路径所在的文件系统
+pub fn sync_fs(path: &str) -> bool {
+    page_cache::flush_all();
+    let components = split_path(path);
+    let (fs, _) = MOUNT_TABLE.exclusive_access().resolve(&components);
+    fs.sync()
+}
+
+/// 在目录中创建子目录
+pub fn mkdir(path: &str) -> bool {
+    mknod(path, InodeType::Dir)
+}
+
+/// 创建指定类型的索引节点
+pub fn mknod(path: &str, itype: InodeType) -> bool {
+    match lookup_parent(path) {
+        Some((parent, name)) => parent.create(&name, itype).is_some(),
+        None => false,
+    }
+}
+
+/// 启动时挂载的文件系统 (来源, 挂载点, 类型)
+const BOOT_MOUNTS: &[(&str, &str, &str)] = &[
+    ("tmpfs", "/tmp", "tmpfs"),
+    ("devfs", "/dev", "devfs"),
+    ("procfs", "/proc", "procfs"),
+];
+
+/// 挂载启动时需要的文件系统 挂载点不存在时先创建
+pub fn init() {
+    for (source, target, fstype) in BOOT_MOUNTS {
+        if lookup(target).is_none() {
+            assert!(mkdir(target), "cannot create {}", target);
+        }
+        assert!(mount(source, target, fstype), "cannot mount {}", target);
+    }
+}
@@ -1,29 +1,53 @@
-use crate::{mm::UserBuffer, sync::UPSafeCell, task::suspend_current_and_run_next};
-use alloc::sync::{Arc, Weak};
+use crate::config::PIPE_BUFFER_SIZE;
+use crate::task::{block_current_and_run_next, current_task, wakeup_task, TaskControlBlock};
+use crate::{mm::UserBuffer, sync::UPSafeCell};
+use alloc::collections::VecDeque;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
 
-use super::File;
+use super::{release_locks, File, InodeId, LockOwner, PollEvents};
 
 pub struct Pipe {
     readable: bool,
     writable: bool,
+    /// 命名管道的路径和索引节点
+    fifo: Option<(String, InodeId)>,
     buffer: Arc<UPSafeCell<PipeRingBuffer>>,
 }
 
 impl Pipe {
-    pub fn read_end_with_buffer(buffer: Arc<UPSafeCell<PipeRingBuffer>>) -> Self {
+    /// 打开管道的一端 命名管道可以同时读写
+    pub fn new(
+        readable: bool,
+        writable: bool,
+        fifo: Option<(String, InodeId)>,
+        buffer: Arc<UPSafeCell<PipeRingBuffer>>,
+    ) -> Self {
+        let mut ring_buffer = buffer.exclusive_access();
+        if readable {
+            ring_buffer.read_ends += 1;
+        }
+        if writable {
+            ring_buffer.write_ends += 1;
+        }
+        ring_buffer.wake_openers();
+        drop(ring_buffer);
         Self {
-            readable: true,
-            writable: false,
+            readable,
+            writable,
+            fifo,
             buffer,
         }
     }
 
+    pub fn read_end_with_buffer(buffer: Arc<UPSafeCell<PipeRingBuffer>>) -> Self {
+        Self::new(true, false, None, buffer)
+    }
+
     pub fn write_end_with_buffer(buffer: Arc<UPSafeCell<PipeRingBuffer>>) -> Self {
-        Self {
-            readable: false,
-            writable: true,
-            buffer,
-        }
+        Self::new(false, true, None, buffer)
     }
 }
 
@@ -36,7 +60,7 @@ impl File for Pipe {
         self.writable
     }
 
-    fn read(&self, buf: UserBuffer) -> usize {
+    fn read(&self, buf: UserBuffer) -> Option<usize> {
         assert!(self.readable());
         let want_to_read = buf.len();
         let mut buf_iter = buf.into_iter();
@@ -44,15 +68,18 @@ impl File for Pipe {
         loop {
             let mut ring_buffer = self.buffer.exclusive_access();
             let loop_read = ring_buffer.available_read();
-            // 管道中没有可读的数据
+            // 管道中没有可读的数据 等待写者写入或者关闭写端
             if loop_read == 0 {
                 if ring_buffer.all_write_ends_closed() {
-                    return already_read;
+                    return Some(already_read);
                 }
+                ring_buffer.readers.push_back(current_task().unwrap());
                 drop(ring_buffer);
-                suspend_current_and_run_next();
+                block_current_and_run_next();
                 continue;
             }
+            // 读出数据后缓冲区有了空间
+            ring_buffer.wake_writers();
             for _ in 0..loop_read {
                 if let Some(byte_ref) = buf_iter.next() {
                     unsafe {
@@ -60,44 +87,119 @@ impl File for Pipe {
                     }
                     already_read += 1;
                     if already_read == want_to_read {
-                        return want_to_read;
+                        return Some(want_to_read);
                     }
                 } else {
-                    return already_read;
+                    return Some(already_read);
                 }
             }
         }
     }
 
-    fn write(&self, buf: UserBuffer) -> usize {
+    fn write(&self, buf: UserBuffer) -> Option<usize> {
         assert!(self.writable());
         let want_to_write = buf.len();
         let mut buf_iter = buf.into_iter();
         let mut already_write = 0usize;
         loop {
             let mut ring_buffer = self.buffer.exclusive_access();
+            // 没有读者时写入的数据不会再被读出
+            if ring_buffer.all_read_ends_closed() {
+                return Some(already_write);
+            }
             let loop_write = ring_buffer.available_write();
             if loop_write == 0 {
+                ring_buffer.writers.push_back(current_task().unwrap());
                 drop(ring_buffer);
-                suspend_current_and_run_next();
+                block_current_and_run_next();
                 continue;
             }
+            ring_buffer.wake_readers();
             for _ in 0..loop_write {
                 if let Some(byte_ref) = buf_iter.next() {
                     ring_buffer.write_byte(unsafe { *byte_ref });
                     already_write += 1;
                     if already_write == want_to_write {
-                        return want_to_write;
+                        return Some(want_to_write);
                     }
                 } else {
-                    return already_write;
+                    return Some(already_write);
                 }
             }
         }
     }
+
+    fn poll(&self, events: PollEvents) -> PollEvents {
+        let ring_buffer = self.buffer.exclusive_access();
+        let mut ready = PollEvents::empty();
+        if self.readable {
+            if ring_buffer.available_read() > 0 {
+                ready |= PollEvents::IN;
+            }
+            // 写端全部关闭后读不会再阻塞
+            if ring_buffer.all_write_ends_closed() {
+                ready |= PollEvents::HUP;
+            }
+        }
+        if self.writable {
+            if ring_buffer.available_write() > 0 {
+                ready |= PollEvents::OUT;
+            }
+            if ring_buffer.all_read_ends_closed() {
+                ready |= PollEvents::ERR;
+            }
+        }
+        ready & (events | PollEvents::HUP | PollEvents::ERR)
+    }
+
+    fn path(&self) -> Option<String> {
+        self.fifo.as_ref().map(|(path, _)| path.clone())
+    }
+
+    fn inode_id(&self) -> Option<InodeId> {
+        self.fifo.as_ref().map(|(_, id)| *id)
+    }
+
+    /// 读端等数据 写端等空间 另一端关闭时也会被唤醒
+    fn poll_register(&self, task: &Arc<TaskControlBlock>) -> bool {
+        let mut ring_buffer = self.buffer.exclusive_access();
+        if self.readable {
+            ring_buffer.readers.push_back(task.clone());
+        }
+        if self.writable {
+            ring_buffer.writers.push_back(task.clone());
+        }
+        true
+    }
+
+    fn poll_unregister(&self, task: &Arc<TaskControlBlock>) {
+        let mut ring_buffer = self.buffer.exclusive_access();
+        ring_buffer
+            .readers
+            .retain(|waiter| !Arc::ptr_eq(waiter, task));
+        ring_buffer
+            .writers
+            .retain(|waiter| !Arc::ptr_eq(waiter, task));
+    }
 }
 
-const RING_BUFFER_SIZE: usize = 32;
+/// 管道一端关闭时唤醒等待另一端的任务 命名管道还要释放flock锁
+impl Drop for Pipe {
+    fn drop(&mut self) {
+        if self.fifo.is_some() {
+            release_locks(LockOwner::File(self as *const _ as usize));
+        }
+        let mut ring_buffer = self.buffer.exclusive_access();
+        if self.writable {
+            ring_buffer.write_ends -= 1;
+            ring_buffer.wake_readers();
+        }
+        if self.readable {
+            ring_buffer.read_ends -= 1;
+            ring_buffer.wake_writers();
+        }
+    }
+}
 
 #[derive(Copy, Clone, PartialEq)]
 enum RingBufferStatus {
@@ -108,32 +210,70 @@ enum RingBufferStatus {
 
 /// 循环队列
 pub struct PipeRingBuffer {
-    arr: [u8; RING_BUFFER_SIZE],
+    arr: Vec<u8>,
     head: usize,
     tail: usize,
     status: RingBufferStatus,
-    write_end: Option<Weak<Pipe>>,
+    /// 打开的读端和写端个数
+    read_ends: usize,
+    write_ends: usize,
+    /// 等待数据的读者
+    readers: VecDeque<Arc<TaskControlBlock>>,
+    /// 等待空间的写者
+    writers: VecDeque<Arc<TaskControlBlock>>,
+    /// 等待另一端打开的任务
+    openers: VecDeque<Arc<TaskControlBlock>>,
 }
 
 impl PipeRingBuffer {
-    pub fn new() -> Self {
+    pub fn new(capacity: usize) -> Self {
         Self {
-            arr: [0; RING_BUFFER_SIZE],
+            arr: vec![0; capacity],
             head: 0,
             tail: 0,
             status: RingBufferStatus::Empty,
-            write_end: None,
+            read_ends: 0,
+            write_ends: 0,
+            readers: VecDeque::new(),
+            writers: VecDeque::new(),
+            openers: VecDeque::new(),
         }
     }
 
-    pub fn set_write_end(&mut self, write_end: &Arc<Pipe>) {
-        self.write_end = Some(Arc::downgrade(write_end));
+    fn wake_readers(&mut self) {
+        wake_all(&mut self.readers);
+    }
+
+    fn wake_writers(&mut self) {
+        wake_all(&mut self.writers);
+    }
+
+    fn wake_openers(&mut self) {
+        wake_all(&mut self.openers);
+    }
+
+    /// 阻塞到管道的另一端被打开
+    pub fn wait_other_end(buffer: &UPSafeCell<Self>, readable: bool) {
+        loop {
+            let mut ring_buffer = buffer.exclusive_access();
+            let opened = if readable {
+                ring_buffer.write_ends > 0
+            } else {
+                ring_buffer.read_ends > 0
+            };
+            if opened {
+                return;
+            }
+            ring_buffer.openers.push_back(current_task().unwrap());
+            drop(ring_buffer);
+            block_current_and_run_next();
+        }
     }
 
     pub fn read_byte(&mut self) -> u8 {
         self.status = RingBufferStatus::Normal;
         let c = self.arr[self.head];
-        self.head = (self.head + 1) % RING_BUFFER_SIZE;
+        self.head = (self.head + 1) % self.arr.len();
         if self.head == self.tail {
             self.status = RingBufferStatus::Empty;
         }
@@ -143,7 +283,7 @@ impl PipeRingBuffer {
     pub fn write_byte(&mut self, byte: u8) {
         self.status = RingBufferStatus::Normal;
         self.arr[self.tail] = byte;
-        self.tail = (self.tail + 1) % RING_BUFFER_SIZE;
+        self.tail = (self.tail + 1) % self.arr.len();
         if self.tail == self.head {
             self.status = RingBufferStatus::Full;
         }
@@ -155,7 +295,7 @@ impl PipeRingBuffer {
         } else if self.tail > self.head {
             self.tail - self.head
         } else {
-            self.tail + RING_BUFFER_SIZE - self.head
+            self.tail + self.arr.len() - self.head
         }
     }
 
@@ -163,20 +303,32 @@ impl PipeRingBuffer {
         if self.status == RingBufferStatus::Full {
             0
         } else {
-            RING_BUFFER_SIZE - self.available_read()
+            self.arr.len() - self.available_read()
         }
     }
 
     pub fn all_write_ends_closed(&self) -> bool {
-        self.write_end.as_ref().unwrap().upgrade().is_none()
+        self.write_ends == 0
+    }
+
+    pub fn all_read_ends_closed(&self) -> bool {
+        self.read_ends == 0
+    }
+}
+
+/// 唤醒等待队列中的任务 进程退出时已经回收资源的线程不再调度
+pub(super) fn wake_all(queue: &mut VecDeque<Arc<TaskControlBlock>>) {
+    while let Some(task) = queue.pop_front() {
+        if task.inner_exclusive_access().res.is_some() {
+            wakeup_task(task);
+        }
     }
 }
 
 /// 创建管道
 pub fn make_pipe() -> (Arc<Pipe>, Arc<Pipe>) {
-    let buffer = Arc::new(unsafe { UPSafeCell::new(PipeRingBuffer::new()) });
+    let buffer = Arc::new(unsafe { UPSafeCell::new(PipeRingBuffer::new(PIPE_BUFFER_SIZE)) });
     let read_end = Arc::new(Pipe::read_end_with_buffer(buffer.clone()));
-    let write_end = Arc::new(Pipe::write_end_with_buffer(buffer.clone()));
-    buffer.exclusive_access().set_write_end(&write_end);
+    let write_end = Arc::new(Pipe::write_end_with_buffer(buffer));
     (read_end, write_end)
 }
@@ -0,0 +1,260 @@
+use super::{alloc_fs_id, FileSystem, Inode, InodeId, InodeType};
+use crate::config::{FD_LIMIT, PAGE_SIZE};
+use crate::mm::{frame_usage, swap::swap_usage, MapPermission};
+use crate::task::current_process;
+use crate::task::manager::{pid2process, pids, ready_task_count};
+use crate::timer::get_time_ms;
+use alloc::{
+    format,
+    string::{String, ToString},
+    sync::Arc,
+    vec,
+    vec::Vec,
+};
+use core::fmt::Write;
+use lazy_static::*;
+
+lazy_static! {
+    /// 内容由内核状态生成 各次挂载的procfs共用一个文件系统编号
+    static ref PROCFS_ID: usize = alloc_fs_id();
+}
+
+/// 进程信息文件系统 读取时按内核当前状态生成内容
+pub struct ProcFs;
+
+impl ProcFs {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self)
+    }
+}
+
+impl FileSystem for ProcFs {
+    fn name(&self) -> &'static str {
+        "procfs"
+    }
+
+    fn root(&self) -> Arc<dyn Inode> {
+        Arc::new(ProcInode(ProcEntry::Root))
+    }
+}
+
+/// 全局文件
+const GLOBAL_FILES: &[&str] = &["meminfo", "uptime", "stat"];
+/// 每个进程目录下的文件
+const PID_FILES: &[&str] = &["status", "maps", "cmdline", "fd"];
+
+#[derive(Clone, Copy)]
+enum ProcEntry {
+    Root,
+    Meminfo,
+    Uptime,
+    Stat,
+    PidDir(usize),
+    Status(usize),
+    Maps(usize),
+    Cmdline(usize),
+    FdDir(usize),
+    Fd(usize, usize),
+}
+
+struct ProcInode(ProcEntry);
+
+impl ProcInode {
+    fn entry(entry: ProcEntry) -> Option<Arc<dyn Inode>> {
+        Some(Arc::new(ProcInode(entry)))
+    }
+
+    /// 生成文件内容 进程已经退出时为空
+    fn content(&self) -> String {
+        let mut s = String::new();
+        match self.0 {
+            ProcEntry::Meminfo => {
+                let (total, free) = frame_usage();
+                writeln!(s, "MemTotal: {} kB", total * PAGE_SIZE / 1024).unwrap();
+                writeln!(s, "MemFree: {} kB", free * PAGE_SIZE / 1024).unwrap();
+                writeln!(s, "MemUsed: {} kB", (total - free) * PAGE_SIZE / 1024).unwrap();
+                let (total, free) = swap_usage();
+                writeln!(s, "SwapTotal: {} kB", total * PAGE_SIZE / 1024).unwrap();
+                writeln!(s, "SwapFree: {} kB", free * PAGE_SIZE / 1024).unwrap();
+            }
+            ProcEntry::Uptime => {
+                let ms = get_time_ms();
+                writeln!(s, "{}.{:02}", ms / 1000, ms % 1000 / 10).unwrap();
+            }
+            ProcEntry::Stat => {
+                let pids = pids();
+                let threads: usize = pids
+                    .iter()
+                    .filter_map(|pid| pid2process(*pid))
+                    .map(|process| process.inner_exclusive_access().thread_count())
+                    .sum();
+                writeln!(s, "processes {}", pids.len()).unwrap();
+                writeln!(s, "threads {}", threads).unwrap();
+                writeln!(s, "procs_ready {}", ready_task_count()).unwrap();
+            }
+            ProcEntry::Status(pid) => {
+                if let Some(process) = pid2process(pid) {
+                    let inner = process.inner_exclusive_access();
+                    let ppid = inner
+                        .parent
+                        .as_ref()
+                        .and_then(|parent| parent.upgrade())
+                        .map_or(0, |parent| parent.getpid());
+                    let frames: usize = inner
+                        .memory_set
+                        .areas()
+                        .iter()
+                        .map(|(_, _, _, frames)| frames)
+                        .sum();
+                    let name = inner.cmdline.first().map_or("", |name| name.as_str());
+                    writeln!(s, "Name: {}", name).unwrap();
+                    writeln!(s, "Pid: {}", pid).unwrap();
+                    writeln!(s, "PPid: {}", ppid).unwrap();
+                    let state = if inner.is_zombie { "zombie" } else { "alive" };
+                    writeln!(s, "State: {}", state).unwrap();
+                    writeln!(s, "Cwd: {}", inner.cwd).unwrap();
+                    writeln!(s, "Threads: {}", inner.thread_count()).unwrap();
+                    writeln!(s, "VmRSS: {} kB", frames * PAGE_SIZE / 1024).unwrap();
+                }
+            }
+            ProcEntry::Maps(pid) => {
+                if let Some(process) = pid2process(pid) {
+                    for (start, end, perm, _) in process.inner_exclusive_access().memory_set.areas()
+                    {
+                        let flag = |bit, c| if perm.contains(bit) { c } else { '-' };
+                        writeln!(
+                            s,
+                            "{:x}-{:x} {}{}{}",
+                            usize::from(start),
+                            usize::from(end),
+                            flag(MapPermission::R, 'r'),
+                            flag(MapPermission::W, 'w'),
+                            flag(MapPermission::X, 'x'),
+                        )
+                        .unwrap();
+                    }
+                }
+            }
+            ProcEntry::Cmdline(pid) => {
+                if let Some(process) = pid2process(pid) {
+                    for arg in process.inner_exclusive_access().cmdline.iter() {
+                        s.push_str(arg);
+                        s.push('\0');
+                    }
+                }
+            }
+            ProcEntry::Fd(pid, fd) => {
+                if let Some(process) = pid2process(pid) {
+                    if let Some(Some(file)) = process.inner_exclusive_access().fd_table.get(fd) {
+                        let readable = if file.readable() { 'r' } else { '-' };
+                        let writable = if file.writable() { 'w' } else { '-' };
+                        let path = file.path().unwrap_or_default();
+                        writeln!(s, "{}{} {}", readable, writable, path).unwrap();
+                    }
+                }
+            }
+            _ => {}
+        }
+        s
+    }
+
+    /// 进程打开的文件描述符
+    fn fds(pid: usize) -> Vec<usize> {
+        pid2process(pid).map_or(Vec::new(), |process| {
+            let inner = process.inner_exclusive_access();
+            (0..inner.fd_table.len())
+                .filter(|fd| inner.fd_table[*fd].is_some())
+                .collect()
+        })
+    }
+}
+
+impl Inode for ProcInode {
+    fn itype(&self) -> InodeType {
+        match self.0 {
+            ProcEntry::Root | ProcEntry::PidDir(_) | ProcEntry::FdDir(_) => InodeType::Dir,
+            _ => InodeType::File,
+        }
+    }
+
+    /// 进程目录下的文件按pid分段编号 文件描述符小于FD_LIMIT
+    fn id(&self) -> InodeId {
+        let pid_base = |pid: usize| (pid + 1) * (FD_LIMIT + 5);
+        let ino = match self.0 {
+            ProcEntry::Root => 0,
+            ProcEntry::Meminfo => 1,
+            ProcEntry::Uptime => 2,
+            ProcEntry::Stat => 3,
+            ProcEntry::PidDir(pid) => pid_base(pid),
+            ProcEntry::Status(pid) => pid_base(pid) + 1,
+            ProcEntry::Maps(pid) => pid_base(pid) + 2,
+            ProcEntry::Cmdline(pid) => pid_base(pid) + 3,
+            ProcEntry::FdDir(pid) => pid_base(pid) + 4,
+            ProcEntry::Fd(pid, fd) => pid_base(pid) + 5 + fd,
+        };
+        InodeId {
+            fs: *PROCFS_ID,
+            ino,
+        }
+    }
+
+    fn find(&self, name: &str) -> Option<Arc<dyn Inode>> {
+        match self.0 {
+            ProcEntry::Root => match name {
+                "meminfo" => Self::entry(ProcEntry::Meminfo),
+                "uptime" => Self::entry(ProcEntry::Uptime),
+                "stat" => Self::entry(ProcEntry::Stat),
+                "self" => Self::entry(ProcEntry::PidDir(current_process().getpid())),
+                _ => {
+                    let pid = name.parse().ok()?;
+                    pid2process(pid)?;
+                    Self::entry(ProcEntry::PidDir(pid))
+                }
+            },
+            ProcEntry::PidDir(pid) => match name {
+                "status" => Self::entry(ProcEntry::Status(pid)),
+                "maps" => Self::entry(ProcEntry::Maps(pid)),
+                "cmdline" => Self::entry(ProcEntry::Cmdline(pid)),
+                "fd" => Self::entry(ProcEntry::FdDir(pid)),
+                _ => None,
+            },
+            ProcEntry::FdDir(pid) => {
+                let fd = name.parse().ok()?;
+                if !Self::fds(pid).contains(&fd) {
+                    return None;
+                }
+                Self::entry(ProcEntry::Fd(pid, fd))
+            }
+            _ => None,
+        }
+    }
+
+    fn ls(&self) -> Vec<String> {
+        match self.0 {
+            ProcEntry::Root => {
+                let mut v: Vec<String> = GLOBAL_FILES.iter().map(|name| name.to_string()).collect();
+                v.push("self".to_string());
+                v.extend(pids().iter().map(|pid| format!("{}", pid)));
+                v
+            }
+            ProcEntry::PidDir(_) => PID_FILES.iter().map(|name| name.to_string()).collect(),
+            ProcEntry::FdDir(pid) => Self::fds(pid).iter().map(|fd| format!("{}", fd)).collect(),
+            _ => vec![],
+        }
+    }
+
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> Option<usize> {
+        let content = self.content();
+        let content = content.as_bytes();
+        if offset >= content.len() {
+            return Some(0);
+        }
+        let len = buf.len().min(content.len() - offset);
+        buf[..len].copy_from_slice(&content[offset..offset + len]);
+        Some(len)
+    }
+
+    fn write_at(&self, _offset: usize, _buf: &[u8]) -> Option<usize> {
+        Some(0)
+    }
+}
@@ -1,10 +1,41 @@
-use super::File;
+use super::{File, PollEvents};
 use crate::mm::UserBuffer;
 use crate::sbi::console_getchar;
-use crate::task::suspend_current_and_run_next;
+use crate::sync::UPSafeCell;
+use crate::task::{check_signals_error_of_current, suspend_current_and_run_next, TaskControlBlock};
+use alloc::sync::Arc;
+use lazy_static::*;
+
+lazy_static! {
+    /// poll时从控制台读到的字符 下次read先返回它
+    static ref PENDING_CHAR: UPSafeCell<Option<u8>> = unsafe { UPSafeCell::new(None) };
+}
+
+/// 控制台是否有输入 读到的字符先保存起来
+fn stdin_ready() -> bool {
+    let mut pending = PENDING_CHAR.exclusive_access();
+    if pending.is_none() {
+        match console_getchar() {
+            0 => {}
+            c => *pending = Some(c as u8),
+        }
+    }
+    pending.is_some()
+}
 
 pub struct Stdin;
 pub struct Stdout;
+/// 打开的控制台 按打开方式读写
+pub struct Tty {
+    readable: bool,
+    writable: bool,
+}
+
+impl Tty {
+    pub fn new(readable: bool, writable: bool) -> Self {
+        Self { readable, writable }
+    }
+}
 
 impl File for Stdin {
     fn readable(&self) -> bool {
@@ -15,29 +46,50 @@ impl File for Stdin {
         false
     }
 
-    fn read(&self, mut user_buf: UserBuffer) -> usize {
-        assert_eq!(user_buf.len(), 1);
+    /// 等到有输入 再读出已经到达的字符 最多填满缓冲区
+    ///
+    /// 等待时进程收到终止信号就返回0
+    fn read(&self, user_buf: UserBuffer) -> Option<usize> {
+        if user_buf.len() == 0 {
+            return Some(0);
+        }
         // busy loop
-        let mut c: usize;
-        loop {
-            c = console_getchar();
-            if c == 0 {
-                suspend_current_and_run_next();
-                continue;
-            } else {
-                break;
+        while !stdin_ready() {
+            if check_signals_error_of_current().is_some() {
+                return Some(0);
             }
+            suspend_current_and_run_next();
         }
-        let ch = c as u8;
-        unsafe {
-            user_buf.buffers[0].as_mut_ptr().write_volatile(ch);
+        let mut count = 0;
+        for byte in user_buf {
+            if !stdin_ready() {
+                break;
+            }
+            let ch = PENDING_CHAR.exclusive_access().take().unwrap();
+            unsafe {
+                byte.write_volatile(ch);
+            }
+            count += 1;
         }
-        1
+        Some(count)
     }
 
-    fn write(&self, _user_buf: UserBuffer) -> usize {
+    fn write(&self, _user_buf: UserBuffer) -> Option<usize> {
         panic!("Cannot write to stdin!");
     }
+
+    fn poll(&self, events: PollEvents) -> PollEvents {
+        if stdin_ready() {
+            events & PollEvents::IN
+        } else {
+            PollEvents::empty()
+        }
+    }
+
+    /// 控制台没有输入中断 不能唤醒等待的任务
+    fn poll_register(&self, _task: &Arc<TaskControlBlock>) -> bool {
+        false
+    }
 }
 
 impl File for Stdout {
@@ -49,14 +101,47 @@ impl File for Stdout {
         true
     }
 
-    fn read(&self, _user_buf: UserBuffer) -> usize {
+    fn read(&self, _user_buf: UserBuffer) -> Option<usize> {
         panic!("Cannot read from stdout!");
     }
 
-    fn write(&self, user_buf: UserBuffer) -> usize {
+    fn write(&self, user_buf: UserBuffer) -> Option<usize> {
         for buffer in user_buf.buffers.iter() {
             print!("{}", core::str::from_utf8(*buffer).unwrap());
         }
-        user_buf.len()
+        Some(user_buf.len())
+    }
+}
+
+impl File for Tty {
+    fn readable(&self) -> bool {
+        self.readable
+    }
+
+    fn writable(&self) -> bool {
+        self.writable
+    }
+
+    fn read(&self, user_buf: UserBuffer) -> Option<usize> {
+        Stdin.read(user_buf)
+    }
+
+    fn write(&self, user_buf: UserBuffer) -> Option<usize> {
+        Stdout.write(user_buf)
+    }
+
+    fn poll(&self, events: PollEvents) -> PollEvents {
+        let mut ready = PollEvents::empty();
+        if self.readable {
+            ready |= Stdin.poll(events);
+        }
+        if self.writable {
+            ready |= events & PollEvents::OUT;
+        }
+        ready
+    }
+
+    fn poll_register(&self, task: &Arc<TaskControlBlock>) -> bool {
+        Stdin.poll_register(task)
     }
 }
@@ -0,0 +1,204 @@
+use super::{alloc_fs_id, FileSystem, Inode, InodeId, InodeType, XattrFlags};
+use crate::config::{TMPFS_FILE_MAX, TMPFS_SIZE_MAX};
+use crate::sync::UPSafeCell;
+use alloc::{
+    collections::BTreeMap,
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
+
+/// 内存文件系统 内容保存在内核堆中 卸载或关机后丢失
+pub struct TmpFs {
+    root: Arc<TmpInode>,
+}
+
+impl TmpFs {
+    pub fn new() -> Arc<Self> {
+        let fs = Arc::new(TmpFsInner {
+            id: alloc_fs_id(),
+            used: unsafe { UPSafeCell::new(0) },
+            next_ino: unsafe { UPSafeCell::new(0) },
+        });
+        Arc::new(Self {
+            root: TmpInode::new(InodeType::Dir, fs),
+        })
+    }
+}
+
+/// 各索引节点共享的文件系统状态
+struct TmpFsInner {
+    id: usize,
+    /// 所有文件内容已经使用的字节数
+    used: UPSafeCell<usize>,
+    /// 下一个索引节点编号
+    next_ino: UPSafeCell<usize>,
+}
+
+impl FileSystem for TmpFs {
+    fn name(&self) -> &'static str {
+        "tmpfs"
+    }
+
+    fn root(&self) -> Arc<dyn Inode> {
+        self.root.clone()
+    }
+}
+
+pub struct TmpInode {
+    itype: InodeType,
+    ino: usize,
+    inner: UPSafeCell<TmpInodeInner>,
+    fs: Arc<TmpFsInner>,
+}
+
+struct TmpInodeInner {
+    /// 文件内容
+    data: Vec<u8>,
+    /// 目录项
+    children: BTreeMap<String, Arc<TmpInode>>,
+    xattrs: BTreeMap<String, Vec<u8>>,
+}
+
+impl TmpInode {
+    fn new(itype: InodeType, fs: Arc<TmpFsInner>) -> Arc<Self> {
+        let ino = {
+            let mut next_ino = fs.next_ino.exclusive_access();
+            *next_ino += 1;
+            *next_ino
+        };
+        Arc::new(Self {
+            itype,
+            ino,
+            inner: unsafe {
+                UPSafeCell::new(TmpInodeInner {
+                    data: Vec::new(),
+                    children: BTreeMap::new(),
+                    xattrs: BTreeMap::new(),
+                })
+            },
+            fs,
+        })
+    }
+}
+
+impl Drop for TmpInode {
+    fn drop(&mut self) {
+        *self.fs.used.exclusive_access() -= self.inner.exclusive_access().data.len();
+    }
+}
+
+impl Inode for TmpInode {
+    fn itype(&self) -> InodeType {
+        self.itype
+    }
+
+    fn id(&self) -> InodeId {
+        InodeId {
+            fs: self.fs.id,
+            ino: self.ino,
+        }
+    }
+
+    fn find(&self, name: &str) -> Option<Arc<dyn Inode>> {
+        self.inner
+            .exclusive_access()
+            .children
+            .get(name)
+            .map(|inode| inode.clone() as Arc<dyn Inode>)
+    }
+
+    fn create(&self, name: &str, itype: InodeType) -> Option<Arc<dyn Inode>> {
+        let supported = matches!(itype, InodeType::File | InodeType::Dir | InodeType::Fifo);
+        if self.itype != InodeType::Dir || !supported {
+            return None;
+        }
+        let mut inner = self.inner.exclusive_access();
+        if inner.children.contains_key(name) {
+            return None;
+        }
+        let inode = TmpInode::new(itype, self.fs.clone());
+        inner.children.insert(name.to_string(), inode.clone());
+        Some(inode)
+    }
+
+    fn ls(&self) -> Vec<String> {
+        self.inner
+            .exclusive_access()
+            .children
+            .keys()
+            .cloned()
+            .collect()
+    }
+
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> Option<usize> {
+        let inner = self.inner.exclusive_access();
+        if offset >= inner.data.len() {
+            return Some(0);
+        }
+        let len = buf.len().min(inner.data.len() - offset);
+        buf[..len].copy_from_slice(&inner.data[offset..offset + len]);
+        Some(len)
+    }
+
+    /// 超出单个文件或整个文件系统的大小限制时只写入能容纳的部分
+    fn write_at(&self, offset: usize, buf: &[u8]) -> Option<usize> {
+        let mut inner = self.inner.exclusive_access();
+        let mut used = self.fs.used.exclusive_access();
+        let size = inner.data.len();
+        let limit = TMPFS_FILE_MAX.min(size + TMPFS_SIZE_MAX - *used);
+        if offset >= limit {
+            return Some(0);
+        }
+        let end = limit.min(offset + buf.len());
+        if end > size {
+            inner.data.reserve_exact(end - size);
+            inner.data.resize(end, 0);
+            *used += end - size;
+        }
+        inner.data[offset..end].copy_from_slice(&buf[..end - offset]);
+        Some(end - offset)
+    }
+
+    fn size(&self) -> usize {
+        self.inner.exclusive_access().data.len()
+    }
+
+    fn clear(&self) {
+        let mut inner = self.inner.exclusive_access();
+        *self.fs.used.exclusive_access() -= inner.data.len();
+        inner.data.clear();
+        inner.data.shrink_to_fit();
+    }
+
+    fn get_xattr(&self, name: &str) -> Option<Vec<u8>> {
+        self.inner.exclusive_access().xattrs.get(name).cloned()
+    }
+
+    fn list_xattr(&self) -> Option<Vec<String>> {
+        Some(
+            self.inner
+                .exclusive_access()
+                .xattrs
+                .keys()
+                .cloned()
+                .collect(),
+        )
+    }
+
+    fn set_xattr(&self, name: &str, value: &[u8], flags: XattrFlags) -> bool {
+        let mut inner = self.inner.exclusive_access();
+        let exists = inner.xattrs.contains_key(name);
+        if (exists && flags.contains(XattrFlags::CREATE))
+            || (!exists && flags.contains(XattrFlags::REPLACE))
+        {
+            return false;
+        }
+        inner.xattrs.insert(name.to_string(), value.to_vec());
+        true
+    }
+
+    fn remove_xattr(&self, name: &str) -> bool {
+        self.inner.exclusive_access().xattrs.remove(name).is_some()
+    }
+}
@@ -0,0 +1,116 @@
+use super::File;
+use alloc::{string::String, sync::Arc, vec::Vec};
+use bitflags::*;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+bitflags! {
+    /// setxattr的标志
+    pub struct XattrFlags: u32 {
+        /// 属性已存在时失败
+        const CREATE = 1;
+        /// 属性不存在时失败
+        const REPLACE = 2;
+    }
+}
+
+/// 索引节点类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InodeType {
+    File,
+    Dir,
+    CharDevice,
+    BlockDevice,
+    /// 命名管道
+    Fifo,
+}
+
+/// 索引节点的标识 文件系统编号和文件系统中的索引节点编号
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct InodeId {
+    pub fs: usize,
+    pub ino: usize,
+}
+
+/// 分配文件系统编号 每个文件系统实例不同
+pub fn alloc_fs_id() -> usize {
+    static NEXT_FS_ID: AtomicUsize = AtomicUsize::new(0);
+    NEXT_FS_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+/// 文件系统 挂载到挂载表中
+pub trait FileSystem: Send + Sync {
+    /// 文件系统类型名
+    fn name(&self) -> &'static str;
+    /// 根目录
+    fn root(&self) -> Arc<dyn Inode>;
+    /// 把缓存的修改写回设备 不能保证落盘时返回false 内存中的文件系统不需要
+    fn sync(&self) -> bool {
+        true
+    }
+}
+
+/// 文件系统中的索引节点
+///
+/// 不支持的操作使用默认实现 返回失败
+pub trait Inode: Send + Sync {
+    fn itype(&self) -> InodeType;
+
+    /// 索引节点的标识 文件锁和页缓存以它区分文件
+    fn id(&self) -> InodeId;
+
+    /// 在目录中查找
+    fn find(&self, _name: &str) -> Option<Arc<dyn Inode>> {
+        None
+    }
+
+    /// 在目录中创建
+    fn create(&self, _name: &str, _itype: InodeType) -> Option<Arc<dyn Inode>> {
+        None
+    }
+
+    /// 列出目录项
+    fn ls(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// 读出的字节数 读到文件末尾时为0 出错时返回None
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> Option<usize>;
+
+    /// 写入的字节数 出错时返回None
+    fn write_at(&self, offset: usize, buf: &[u8]) -> Option<usize>;
+
+    /// 文件大小 O_APPEND写入时从这里开始
+    fn size(&self) -> usize {
+        0
+    }
+
+    /// 清空文件内容
+    fn clear(&self) {}
+
+    /// 把这个文件缓存的修改写回设备 不能保证落盘时返回false
+    fn sync(&self) -> bool {
+        true
+    }
+
+    /// 打开设备 每次打开得到新的文件 普通文件返回None
+    fn open_device(&self, _readable: bool, _writable: bool) -> Option<Arc<dyn File + Send + Sync>> {
+        None
+    }
+
+    fn get_xattr(&self, _name: &str) -> Option<Vec<u8>> {
+        None
+    }
+
+    fn list_xattr(&self) -> Option<Vec<String>> {
+        None
+    }
+
+    /// 设置扩展属性 按flags检查属性是否已存在 检查和设置不可分割
+    fn set_xattr(&self, _name: &str, _value: &[u8], _flags: XattrFlags) -> bool {
+        false
+    }
+
+    fn remove_xattr(&self, _name: &str) -> bool {
+        false
+    }
+}
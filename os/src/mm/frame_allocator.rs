@@ -1,5 +1,7 @@
 use super::address::PhysPageNum;
-use crate::{config::MEMORY_END, mm::address::PhysAddr, sync::UPSafeCell};
+use super::swap;
+use crate::config::{MEMORY_END, SWAP_LOW_FRAMES};
+use crate::{mm::address::PhysAddr, sync::UPSafeCell};
 use alloc::vec::Vec;
 use lazy_static::*;
 
@@ -10,6 +12,7 @@ trait FrameAllocator {
 }
 
 pub struct StackFrameAllocator {
+    start: usize,
     current: usize,
     end: usize,
     recycled: Vec<usize>,
@@ -35,17 +38,27 @@ pub fn init_frame_allocator() {
     );
 }
 
+/// 分配页帧 空闲页帧低于SWAP_LOW_FRAMES或者分配失败时先换出一些用户页
 pub fn frame_alloc() -> Option<FrameTracker> {
-    FRAME_ALLOCATOR
-        .exclusive_access()
-        .alloc()
-        .map(|ppn| FrameTracker::new(ppn))
+    let mut ppn = FRAME_ALLOCATOR.exclusive_access().alloc();
+    if ppn.is_none() || frame_usage().1 < SWAP_LOW_FRAMES {
+        swap::balance();
+        if ppn.is_none() {
+            ppn = FRAME_ALLOCATOR.exclusive_access().alloc();
+        }
+    }
+    ppn.map(FrameTracker::new)
 }
 
 pub fn frame_dealloc(ppn: PhysPageNum) {
     FRAME_ALLOCATOR.exclusive_access().dealloc(ppn);
 }
 
+/// 返回(总页帧数, 空闲页帧数)
+pub fn frame_usage() -> (usize, usize) {
+    FRAME_ALLOCATOR.exclusive_access().usage()
+}
+
 #[derive(Debug)]
 pub struct FrameTracker {
     pub ppn: PhysPageNum,
@@ -70,6 +83,7 @@ impl Drop for FrameTracker {
 impl FrameAllocator for StackFrameAllocator {
     fn new() -> Self {
         Self {
+            start: 0,
             current: 0,
             end: 0,
             recycled: Vec::new(),
@@ -102,10 +116,19 @@ impl FrameAllocator for StackFrameAllocator {
 
 impl StackFrameAllocator {
     pub fn init(&mut self, l: PhysPageNum, r: PhysPageNum) {
+        self.start = l.0;
         self.current = l.0;
         self.end = r.0;
         info!("StackFrameAllocator init: current = {:?}, end = {:?}", l, r);
     }
+
+    /// 返回(总页帧数, 空闲页帧数)
+    pub fn usage(&self) -> (usize, usize) {
+        (
+            self.end - self.start,
+            self.end - self.current + self.recycled.len(),
+        )
+    }
 }
 
 #[allow(unused)]
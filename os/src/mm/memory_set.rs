@@ -1,10 +1,13 @@
 use super::page_table::*;
-use super::{address::*, frame_allocator::*};
+use super::shm::{SharedMemory, ShmMapping};
+use super::swap::SwapSlot;
+use super::{address::*, frame_allocator::*, page_cache};
 use crate::board::MMIO;
 use crate::config::*;
+use crate::fs::{Inode, InodeId};
 use crate::sync::UPSafeCell;
 use alloc::sync::Arc;
-use alloc::{collections::BTreeMap, vec::Vec};
+use alloc::{collections::BTreeMap, string::String, vec, vec::Vec};
 use bitflags::*;
 use core::arch::asm;
 use lazy_static::lazy_static;
@@ -79,6 +82,10 @@ pub struct MemorySet {
     /// 多级页表
     page_table: PageTable,
     areas: Vec<MapArea>,
+    /// 堆底 紧跟在ELF之后
+    heap_bottom: usize,
+    /// 程序断点 堆的结束地址
+    brk: usize,
 }
 
 impl MemorySet {
@@ -87,6 +94,8 @@ impl MemorySet {
         Self {
             page_table: PageTable::new(),
             areas: Vec::new(),
+            heap_bottom: 0,
+            brk: 0,
         }
     }
 
@@ -98,9 +107,236 @@ impl MemorySet {
         self.page_table.token()
     }
 
-    pub fn remove_framed_area(&mut self, start_va: VirtAddr, end_va: VirtAddr) {
-        let mut ma = MapArea::new(start_va, end_va, MapType::Framed, MapPermission::empty());
-        ma.unmap(&mut self.page_table);
+    pub fn brk(&self) -> usize {
+        self.brk
+    }
+
+    /// 调整程序断点 返回调整后的断点 失败时断点不变
+    ///
+    /// 断点不能低于堆底 堆扩大的部分不能和其他逻辑段重叠 缩小时释放断点之后的页
+    pub fn set_brk(&mut self, brk: usize) -> usize {
+        if brk < self.heap_bottom {
+            return self.brk;
+        }
+        let heap_start = VirtAddr::from(self.heap_bottom).floor();
+        let old_end = VirtAddr::from(self.brk).ceil();
+        let new_end = VirtAddr::from(brk).ceil();
+        if new_end > old_end && self.overlaps(old_end, new_end) {
+            return self.brk;
+        }
+        let page_table = &mut self.page_table;
+        match self
+            .areas
+            .iter_mut()
+            .find(|area| area.vpn_range.get_start() == heap_start)
+        {
+            Some(area) => area.set_end(page_table, new_end),
+            // 堆被munmap拆掉了 重新建立
+            None => self.push(
+                MapArea::new(
+                    self.heap_bottom.into(),
+                    brk.into(),
+                    MapType::Framed,
+                    MapPermission::R | MapPermission::W | MapPermission::U,
+                ),
+                None,
+            ),
+        }
+        self.brk = brk;
+        self.brk
+    }
+
+    /// [start_vpn, end_vpn)和已有的逻辑段重叠
+    pub fn overlaps(&self, start_vpn: VirtPageNum, end_vpn: VirtPageNum) -> bool {
+        self.areas
+            .iter()
+            .any(|area| area.overlaps(start_vpn, end_vpn))
+    }
+
+    /// 映射[start, start + len) 返回映射的起始地址
+    ///
+    /// fixed时必须映射在start 先拆除原有的用户映射 否则start只是建议
+    /// 和已有的逻辑段重叠时由内核从MMAP_BASE开始找一段空闲的地址
+    pub fn mmap(
+        &mut self,
+        start: usize,
+        len: usize,
+        fixed: bool,
+        permission: MapPermission,
+        file: Option<FileMapping>,
+    ) -> Option<usize> {
+        let pages = (len + PAGE_SIZE - 1) / PAGE_SIZE;
+        let start_vpn = self.place(start, pages, fixed)?;
+        let start_va: VirtAddr = start_vpn.into();
+        let end_va: VirtAddr = VirtPageNum(start_vpn.0 + pages).into();
+        let map_area = match file {
+            Some(file) => MapArea::new_file(start_va, end_va, permission, file),
+            None => MapArea::new(start_va, end_va, MapType::Framed, permission),
+        };
+        self.push(map_area, None);
+        Some(start_va.into())
+    }
+
+    /// 把共享内存段整个映射到start附近 地址的选择和mmap相同
+    pub fn mmap_shm(
+        &mut self,
+        start: usize,
+        fixed: bool,
+        permission: MapPermission,
+        segment: Arc<SharedMemory>,
+    ) -> Option<usize> {
+        let start_vpn = self.place(start, segment.pages(), fixed)?;
+        let start_va: VirtAddr = start_vpn.into();
+        let end_va: VirtAddr = VirtPageNum(start_vpn.0 + segment.pages()).into();
+        let shm = ShmMapping { segment, page: 0 };
+        self.push(MapArea::new_shm(start_va, end_va, permission, shm), None);
+        Some(start_va.into())
+    }
+
+    /// 拆除映射在start_vpn处的共享内存段 那里没有映射共享内存段时返回false
+    pub fn munmap_shm(&mut self, start_vpn: VirtPageNum) -> bool {
+        let pages = match self
+            .areas
+            .iter()
+            .filter(|area| area.vpn_range.get_start() == start_vpn)
+            .find_map(|area| area.shm.as_ref().filter(|shm| shm.page == 0))
+        {
+            Some(shm) => shm.segment.pages(),
+            None => return false,
+        };
+        self.munmap(start_vpn, VirtPageNum(start_vpn.0 + pages))
+    }
+
+    /// 为pages页的新映射选择起始页号
+    fn place(&mut self, start: usize, pages: usize, fixed: bool) -> Option<VirtPageNum> {
+        let start_vpn = VirtAddr::from(start).floor();
+        let end_vpn = VirtPageNum(start_vpn.0 + pages);
+        let user_end = VirtAddr::from(USER_STACK_BASE).floor();
+        if fixed {
+            // 内核使用的逻辑段不能被替换
+            if end_vpn > user_end
+                || self
+                    .areas
+                    .iter()
+                    .any(|area| !area.is_user() && area.overlaps(start_vpn, end_vpn))
+            {
+                return None;
+            }
+            self.munmap(start_vpn, end_vpn);
+            Some(start_vpn)
+        } else if start != 0 && end_vpn <= user_end && !self.overlaps(start_vpn, end_vpn) {
+            Some(start_vpn)
+        } else {
+            self.find_free_area(pages)
+        }
+    }
+
+    /// 从MMAP_BASE开始找一段连续pages页没有映射的地址
+    fn find_free_area(&self, pages: usize) -> Option<VirtPageNum> {
+        let mut start = VirtAddr::from(MMAP_BASE).floor();
+        let mut ranges: Vec<(VirtPageNum, VirtPageNum)> = self
+            .areas
+            .iter()
+            .map(|area| (area.vpn_range.get_start(), area.vpn_range.get_end()))
+            .filter(|(_, end)| *end > start)
+            .collect();
+        ranges.sort();
+        for (area_start, area_end) in ranges {
+            if area_start.0 >= start.0 + pages {
+                break;
+            }
+            start = start.max(area_end);
+        }
+        if start.0 + pages <= VirtAddr::from(USER_STACK_BASE).floor().0 {
+            Some(start)
+        } else {
+            None
+        }
+    }
+
+    /// 在start_vpn和end_vpn处切开跨过边界的用户逻辑段
+    ///
+    /// 之后每个用户逻辑段要么完全在范围内 要么完全在范围外
+    fn split_areas(&mut self, start_vpn: VirtPageNum, end_vpn: VirtPageNum) {
+        let mut pieces = Vec::new();
+        for area in self.areas.iter_mut().filter(|area| area.is_user()) {
+            if area.contains(start_vpn) && area.vpn_range.get_start() != start_vpn {
+                let mut tail = area.split_off(start_vpn);
+                if tail.contains(end_vpn) && start_vpn != end_vpn {
+                    pieces.push(tail.split_off(end_vpn));
+                }
+                pieces.push(tail);
+            } else if area.contains(end_vpn) && area.vpn_range.get_start() != end_vpn {
+                pieces.push(area.split_off(end_vpn));
+            }
+        }
+        self.areas.extend(pieces);
+    }
+
+    /// 拆除[start_vpn, end_vpn)中的用户映射 逻辑段只有一部分在范围内时切开后缩小
+    ///
+    /// 范围内没有用户映射时返回false
+    pub fn munmap(&mut self, start_vpn: VirtPageNum, end_vpn: VirtPageNum) -> bool {
+        if !self
+            .areas
+            .iter()
+            .any(|area| area.is_user() && area.overlaps(start_vpn, end_vpn))
+        {
+            return false;
+        }
+        self.split_areas(start_vpn, end_vpn);
+        let page_table = &mut self.page_table;
+        self.areas.retain_mut(|area| {
+            let inside = area.is_user() && area.within(start_vpn, end_vpn);
+            if inside {
+                area.unmap(page_table);
+            }
+            !inside
+        });
+        true
+    }
+
+    /// 修改[start_vpn, end_vpn)的访问权限 范围内有没映射的页时返回false
+    pub fn mprotect(
+        &mut self,
+        start_vpn: VirtPageNum,
+        end_vpn: VirtPageNum,
+        permission: MapPermission,
+    ) -> bool {
+        let mut covered: Vec<&MapArea> = self
+            .areas
+            .iter()
+            .filter(|area| area.is_user() && area.overlaps(start_vpn, end_vpn))
+            .collect();
+        covered.sort_by_key(|area| area.vpn_range.get_start());
+        let mut next = start_vpn;
+        for area in covered.iter() {
+            if area.vpn_range.get_start() > next {
+                return false;
+            }
+            next = next.max(area.vpn_range.get_end());
+        }
+        if next < end_vpn {
+            return false;
+        }
+        // 只读打开的文件不能通过共享映射写入
+        if permission.contains(MapPermission::W)
+            && covered.iter().any(|area| {
+                area.file
+                    .as_ref()
+                    .map_or(false, |file| file.shared && !file.writable)
+            })
+        {
+            return false;
+        }
+        self.split_areas(start_vpn, end_vpn);
+        let page_table = &mut self.page_table;
+        for area in self.areas.iter_mut() {
+            if area.is_user() && area.within(start_vpn, end_vpn) {
+                area.set_perm(page_table, permission | MapPermission::U);
+            }
+        }
+        true
     }
 
     pub fn remove_area_with_start_vpn(&mut self, start_vpn: VirtPageNum) {
@@ -216,10 +452,12 @@ impl MemorySet {
         memory_set
     }
 
-    pub fn from_elf(elf_data: &[u8]) -> (Self, usize, usize) {
+    /// 按ELF文件建立地址空间 各段映射到文件 运行时按页调入
+    pub fn from_elf(path: &str, inode: Arc<dyn Inode>) -> (Self, usize, usize) {
         let mut memory_set = Self::new_bare();
         memory_set.map_trampoline();
-        let elf = xmas_elf::ElfFile::new(elf_data).unwrap();
+        let header_data = read_elf_header(&inode);
+        let elf = xmas_elf::ElfFile::new(&header_data).unwrap();
         let elf_header = elf.header;
         let magic = elf_header.pt1.magic;
         assert_eq!(magic, [0x7f, 0x45, 0x4c, 0x46], "invalid elf!");
@@ -241,20 +479,47 @@ impl MemorySet {
                 if ph_flags.is_execute() {
                     map_perm |= MapPermission::X;
                 }
-                let map_area = MapArea::new(start_va, end_va, MapType::Framed, map_perm);
-                max_end_vpn = map_area.vpn_range.get_end();
-                memory_set.push(
-                    map_area,
-                    Some(&elf.input[ph.offset() as usize..(ph.offset() + ph.file_size()) as usize]),
-                );
+                let offset = ph.offset() as usize;
+                let file_size = ph.file_size() as usize;
+                let page_offset = start_va.page_offset();
+                if offset % PAGE_SIZE == page_offset {
+                    let file = FileMapping::new(
+                        path,
+                        inode.clone(),
+                        offset - page_offset,
+                        page_offset + file_size,
+                        false,
+                        false,
+                    );
+                    let map_area = MapArea::new_file(start_va, end_va, map_perm, file);
+                    max_end_vpn = map_area.vpn_range.get_end();
+                    memory_set.push(map_area, None);
+                } else {
+                    // 文件偏移和虚拟地址不是同一页内位置时不能按页映射 直接读入
+                    let mut data = vec![0u8; file_size];
+                    page_cache::read_at(&inode, offset, &mut data).unwrap();
+                    let map_area = MapArea::new(start_va, end_va, MapType::Framed, map_perm);
+                    max_end_vpn = map_area.vpn_range.get_end();
+                    memory_set.push(map_area, Some(&data));
+                }
             }
         }
+        // 堆从ELF之后开始 初始为空 通过brk调整大小
         let max_end_va: VirtAddr = max_end_vpn.into();
-        let mut user_stack_base: usize = max_end_va.into();
-        user_stack_base += PAGE_SIZE;
+        memory_set.heap_bottom = max_end_va.into();
+        memory_set.brk = memory_set.heap_bottom;
+        memory_set.push(
+            MapArea::new(
+                max_end_va,
+                max_end_va,
+                MapType::Framed,
+                MapPermission::R | MapPermission::W | MapPermission::U,
+            ),
+            None,
+        );
         (
             memory_set,
-            user_stack_base,
+            USER_STACK_BASE,
             elf.header.pt2.entry_point() as usize,
         )
     }
@@ -270,25 +535,237 @@ impl MemorySet {
         info!("Sv39 active");
     }
 
+    /// 各逻辑段的(起始地址, 结束地址, 权限, 已分配页帧数)
+    pub fn areas(&self) -> Vec<(VirtAddr, VirtAddr, MapPermission, usize)> {
+        self.areas
+            .iter()
+            .map(|area| {
+                (
+                    area.vpn_range.get_start().into(),
+                    area.vpn_range.get_end().into(),
+                    area.map_perm,
+                    area.data_frames.len(),
+                )
+            })
+            .collect()
+    }
+
+    /// 映射的文件的路径
+    pub fn mapped_paths(&self) -> Vec<String> {
+        self.areas
+            .iter()
+            .filter_map(|area| area.file.as_ref())
+            .map(|file| file.path.clone())
+            .collect()
+    }
+
     pub fn translate(&self, vpn: VirtPageNum) -> Option<PageTableEntry> {
         self.page_table.translate(vpn)
     }
 
-    pub fn from_existed_user(user_space: &MemorySet) -> MemorySet {
+    /// 处理用户态的缺页 access是触发缺页的访问
+    ///
+    /// 地址不在任何逻辑段中 逻辑段不允许这种访问或者分配不到页帧时返回false
+    pub fn handle_page_fault(&mut self, va: VirtAddr, access: MapPermission) -> bool {
+        let vpn = va.floor();
+        let page_table = &mut self.page_table;
+        match self.areas.iter_mut().find(|area| area.contains(vpn)) {
+            Some(area) if area.map_perm.contains(access | MapPermission::U) => {
+                area.handle_page_fault(page_table, vpn, access)
+            }
+            _ => false,
+        }
+    }
+
+    /// 内核直接读写用户页之前调用 还没调入的页先调入 写入时先完成写时复制
+    ///
+    /// 和用户访问一样检查权限 内核不会写入只读的页或者还在共享的页帧
+    /// 返回页帧 调用者持有期间这一页不会被换出 分配不到页帧时返回None
+    pub fn touch(&mut self, vpn: VirtPageNum, access: MapPermission) -> Option<Arc<FrameTracker>> {
+        let page_table = &mut self.page_table;
+        let area = self
+            .areas
+            .iter_mut()
+            .find(|area| area.contains(vpn))
+            .filter(|area| area.map_perm.contains(access | MapPermission::U))?;
+        if !area.handle_page_fault(page_table, vpn, access) {
+            return None;
+        }
+        area.data_frames.get(&vpn).cloned()
+    }
+
+    /// 时钟算法 从from开始最多换出count页 访问位为1的页清除访问位 下一圈再考虑
+    ///
+    /// 只换出进程独占的页 和页缓存或其他进程共享的页 以及内核正在访问的页都不换出
+    /// 返回换出的页数 提前停下时还返回停下的位置
+    pub fn swap_out(&mut self, from: VirtPageNum, count: usize) -> (usize, Option<VirtPageNum>) {
+        let page_table = &mut self.page_table;
+        let mut areas: Vec<&mut MapArea> = self
+            .areas
+            .iter_mut()
+            .filter(|area| area.vpn_range.get_end() > from && area.is_swappable())
+            .collect();
+        areas.sort_by_key(|area| area.vpn_range.get_start());
+        let mut evicted = 0;
+        for area in areas {
+            let vpns: Vec<VirtPageNum> = area
+                .data_frames
+                .range(from..)
+                .filter(|(_, frame)| Arc::strong_count(frame) == 1)
+                .map(|(vpn, _)| *vpn)
+                .collect();
+            for vpn in vpns {
+                if evicted == count {
+                    return (evicted, Some(vpn));
+                }
+                let flags = page_table.translate(vpn).unwrap().flags();
+                if flags.contains(PTEFlags::A) {
+                    page_table.set_flags(vpn, flags - PTEFlags::A);
+                    continue;
+                }
+                if !area.swap_out_one(page_table, vpn) {
+                    return (evicted, Some(vpn));
+                }
+                evicted += 1;
+            }
+        }
+        (evicted, None)
+    }
+
+    /// fork时复制地址空间 用户页和父进程共享 可写的私有页双方都改成只读 写入时再复制
+    ///
+    /// 换出的页复制一份交换区 交换区满时给子进程调入 分配不到页帧时返回None
+    pub fn from_existed_user(user_space: &mut MemorySet) -> Option<MemorySet> {
         let mut memory_set = Self::new_bare();
         memory_set.map_trampoline();
+        memory_set.heap_bottom = user_space.heap_bottom;
+        memory_set.brk = user_space.brk;
         for area in user_space.areas.iter() {
-            let new_area = MapArea::from_another(area);
-            memory_set.push(new_area, None);
-            for vpn in area.vpn_range {
-                let src_ppn = user_space.translate(vpn).unwrap().ppn();
-                let dst_ppn = memory_set.translate(vpn).unwrap().ppn();
-                dst_ppn
-                    .get_bytes_array()
-                    .copy_from_slice(src_ppn.get_bytes_array());
+            let mut new_area = MapArea::from_another(area);
+            for (vpn, frame) in area.data_frames.iter() {
+                let mut flags = user_space.translate(*vpn).unwrap().flags();
+                // TrapContext等页内核按物理地址直接写 不能共享
+                if !area.map_perm.contains(MapPermission::U) {
+                    let new_frame = frame_alloc()?;
+                    new_frame
+                        .ppn
+                        .get_bytes_array()
+                        .copy_from_slice(frame.ppn.get_bytes_array());
+                    memory_set.page_table.map(*vpn, new_frame.ppn, flags);
+                    new_area.data_frames.insert(*vpn, Arc::new(new_frame));
+                    continue;
+                }
+                if !area.is_shared() && flags.contains(PTEFlags::W) {
+                    flags.remove(PTEFlags::W);
+                    user_space.page_table.set_flags(*vpn, flags);
+                }
+                memory_set.page_table.map(*vpn, frame.ppn, flags);
+                new_area.data_frames.insert(*vpn, frame.clone());
+            }
+            for (vpn, slot) in area.swapped.iter() {
+                match slot.duplicate() {
+                    Some(new_slot) => {
+                        new_area.swapped.insert(*vpn, new_slot);
+                    }
+                    None => {
+                        if !new_area.swap_in(&mut memory_set.page_table, *vpn, slot) {
+                            return None;
+                        }
+                    }
+                }
             }
+            memory_set.areas.push(new_area);
         }
-        memory_set
+        Some(memory_set)
+    }
+}
+
+/// 读ELF头和程序头表 包括共享映射中还没有写回的修改
+fn read_elf_header(inode: &Arc<dyn Inode>) -> Vec<u8> {
+    let mut data = vec![0u8; PAGE_SIZE];
+    let len = page_cache::read_at(inode, 0, &mut data).unwrap();
+    data.truncate(len);
+    // 程序头表一般紧跟在ELF头后面 超出第一页时再多读一些
+    let ph_end = match xmas_elf::ElfFile::new(&data) {
+        Ok(elf) => {
+            let pt2 = &elf.header.pt2;
+            pt2.ph_offset() as usize + pt2.ph_count() as usize * pt2.ph_entry_size() as usize
+        }
+        Err(_) => 0,
+    };
+    if ph_end > data.len() {
+        data.resize(ph_end, 0);
+        page_cache::read_at(inode, 0, &mut data).unwrap();
+    }
+    data
+}
+
+/// 逻辑段映射的文件
+#[derive(Clone)]
+pub struct FileMapping {
+    /// 文件的绝对路径 卸载文件系统时检查是否还有映射
+    path: String,
+    inode: Arc<dyn Inode>,
+    /// 索引节点的标识 用来索引页缓存
+    id: InodeId,
+    /// 逻辑段第一页对应的文件偏移 按页对齐
+    offset: usize,
+    /// 逻辑段开头的len字节来自文件 之后的部分为0 如ELF的.bss
+    len: usize,
+    /// 共享映射的修改写回文件 私有映射写时复制
+    shared: bool,
+    /// 文件以可写方式打开 共享映射才能写入
+    writable: bool,
+}
+
+impl FileMapping {
+    pub fn new(
+        path: &str,
+        inode: Arc<dyn Inode>,
+        offset: usize,
+        len: usize,
+        shared: bool,
+        writable: bool,
+    ) -> Self {
+        Self {
+            path: String::from(path),
+            id: inode.id(),
+            inode,
+            offset,
+            len,
+            shared,
+            writable,
+        }
+    }
+
+    /// 逻辑段从第pages页切开后 后一半对应的文件映射
+    fn skip(&self, pages: usize) -> Self {
+        let mut file = self.clone();
+        file.offset += pages * PAGE_SIZE;
+        file.len = file.len.saturating_sub(pages * PAGE_SIZE);
+        file
+    }
+
+    /// 逻辑段中第index页的页帧 第二个返回值表示是否是页缓存中的页 分配不到页帧时返回None
+    ///
+    /// 跨过文件范围的页不能共享 复制出文件范围内的部分
+    fn frame(&self, index: usize) -> Option<(Arc<FrameTracker>, bool)> {
+        let start = index * PAGE_SIZE;
+        if !self.shared && start >= self.len {
+            return Some((Arc::new(frame_alloc()?), false));
+        }
+        let page = self.offset / PAGE_SIZE + index;
+        let cached = page_cache::get_page(&self.inode, page)?;
+        if self.shared || start + PAGE_SIZE <= self.len {
+            return Some((cached, true));
+        }
+        let frame = frame_alloc()?;
+        if start < self.len {
+            let len = self.len - start;
+            frame.ppn.get_bytes_array()[..len]
+                .copy_from_slice(&cached.ppn.get_bytes_array()[..len]);
+        }
+        Some((Arc::new(frame), false))
     }
 }
 
@@ -296,11 +773,17 @@ impl MemorySet {
 pub struct MapArea {
     /// 虚拟页号的连续区间
     pub vpn_range: VPNRange,
-    /// 虚拟页号与物理页号的映射
-    data_frames: BTreeMap<VirtPageNum, FrameTracker>,
+    /// 虚拟页号与物理页号的映射 页缓存中的页和其他逻辑段共享
+    data_frames: BTreeMap<VirtPageNum, Arc<FrameTracker>>,
+    /// 换出到交换区的页
+    swapped: BTreeMap<VirtPageNum, SwapSlot>,
     /// 映射类型
     map_type: MapType,
     map_perm: MapPermission,
+    /// 映射的文件 匿名映射为None
+    file: Option<FileMapping>,
+    /// 映射的共享内存段
+    shm: Option<ShmMapping>,
 }
 
 impl MapArea {
@@ -320,39 +803,266 @@ impl MapArea {
         Self {
             vpn_range: VPNRange::new(start_vpn, end_vpn),
             data_frames: BTreeMap::new(),
+            swapped: BTreeMap::new(),
             map_type,
             map_perm,
+            file: None,
+            shm: None,
         }
     }
 
-    /// 插入页表项
-    pub fn map_one(&mut self, page_table: &mut PageTable, vpn: VirtPageNum) {
+    /// 新建映射文件的逻辑段
+    pub fn new_file(
+        start_va: VirtAddr,
+        end_va: VirtAddr,
+        map_perm: MapPermission,
+        file: FileMapping,
+    ) -> Self {
+        let mut map_area = Self::new(start_va, end_va, MapType::Framed, map_perm);
+        map_area.file = Some(file);
+        map_area
+    }
+
+    /// 新建映射共享内存段的逻辑段 访问时才分配页帧
+    pub fn new_shm(
+        start_va: VirtAddr,
+        end_va: VirtAddr,
+        map_perm: MapPermission,
+        shm: ShmMapping,
+    ) -> Self {
+        let mut map_area = Self::new(start_va, end_va, MapType::Framed, map_perm);
+        map_area.shm = Some(shm);
+        map_area
+    }
+
+    pub fn contains(&self, vpn: VirtPageNum) -> bool {
+        self.vpn_range.get_start() <= vpn && vpn < self.vpn_range.get_end()
+    }
+
+    fn overlaps(&self, start_vpn: VirtPageNum, end_vpn: VirtPageNum) -> bool {
+        self.vpn_range.get_start() < end_vpn && start_vpn < self.vpn_range.get_end()
+    }
+
+    /// 整个逻辑段都在[start_vpn, end_vpn)中
+    fn within(&self, start_vpn: VirtPageNum, end_vpn: VirtPageNum) -> bool {
+        start_vpn <= self.vpn_range.get_start() && self.vpn_range.get_end() <= end_vpn
+    }
+
+    /// 用户程序自己的逻辑段 可以被munmap和mprotect修改
+    fn is_user(&self) -> bool {
+        self.map_perm.contains(MapPermission::U)
+    }
+
+    /// 从at处切开 自己保留前一半 返回后一半 已经映射的页跟着移动
+    fn split_off(&mut self, at: VirtPageNum) -> Self {
+        let start = self.vpn_range.get_start();
+        let mut tail = Self::from_another(self);
+        tail.vpn_range = VPNRange::new(at, self.vpn_range.get_end());
+        tail.data_frames = self.data_frames.split_off(&at);
+        tail.swapped = self.swapped.split_off(&at);
+        tail.file = self.file.as_ref().map(|file| file.skip(at.0 - start.0));
+        tail.shm = self.shm.as_ref().map(|shm| ShmMapping {
+            segment: shm.segment.clone(),
+            page: shm.page + at.0 - start.0,
+        });
+        self.vpn_range = VPNRange::new(start, at);
+        tail
+    }
+
+    /// 修改权限并更新已经映射的页
+    ///
+    /// 私有映射中还在共享的只读页保持只读 写入时再复制 共享映射直接按新权限映射
+    fn set_perm(&mut self, page_table: &mut PageTable, map_perm: MapPermission) {
+        self.map_perm = map_perm;
+        let pte_flags = PTEFlags::from_bits(map_perm.bits).unwrap();
+        for vpn in self.data_frames.keys() {
+            let mut flags = pte_flags;
+            if !self.is_shared() && !page_table.translate(*vpn).unwrap().writable() {
+                flags.remove(PTEFlags::W);
+            }
+            page_table.set_flags(*vpn, flags);
+            if let Some(file) = self.file.as_ref().filter(|file| file.shared) {
+                if map_perm.contains(MapPermission::W) {
+                    let index = vpn.0 - self.vpn_range.get_start().0;
+                    page_cache::mark_dirty(file.id, file.offset / PAGE_SIZE + index);
+                }
+            }
+        }
+    }
+
+    /// 用户的页在第一次访问时才分配或者调入 内核按物理地址访问的页立即映射
+    fn is_lazy(&self) -> bool {
+        self.map_type == MapType::Framed && self.map_perm.contains(MapPermission::U)
+    }
+
+    /// 共享映射 修改对其他进程可见 不做写时复制
+    fn is_shared(&self) -> bool {
+        self.shm.is_some() || self.file.as_ref().map_or(false, |file| file.shared)
+    }
+
+    /// 进程独占的用户页可以换出 共享映射的页帧由多个进程使用 不换出
+    fn is_swappable(&self) -> bool {
+        self.is_lazy() && !self.is_shared()
+    }
+
+    /// 插入页表项 分配不到页帧时返回false
+    pub fn map_one(&mut self, page_table: &mut PageTable, vpn: VirtPageNum) -> bool {
         let ppn: PhysPageNum;
+        let mut pte_flags = PTEFlags::from_bits(self.map_perm.bits).unwrap();
         match self.map_type {
             MapType::Identical => {
                 ppn = PhysPageNum(vpn.0);
             }
             MapType::Framed => {
-                let frame = frame_alloc().expect("physical frame none!");
+                let frame = match &self.file {
+                    Some(file) => {
+                        let index = vpn.0 - self.vpn_range.get_start().0;
+                        let (frame, cached) = match file.frame(index) {
+                            Some(frame) => frame,
+                            None => return false,
+                        };
+                        if cached && !file.shared {
+                            // 私有映射和页缓存共享页帧时只读 写入时再复制
+                            pte_flags.remove(PTEFlags::W);
+                        } else if file.shared && self.map_perm.contains(MapPermission::W) {
+                            page_cache::mark_dirty(file.id, file.offset / PAGE_SIZE + index);
+                        }
+                        frame
+                    }
+                    None => match &self.shm {
+                        Some(shm) => {
+                            let index = vpn.0 - self.vpn_range.get_start().0;
+                            match shm.segment.frame(shm.page + index) {
+                                Some(frame) => frame,
+                                None => return false,
+                            }
+                        }
+                        None => match frame_alloc() {
+                            Some(frame) => Arc::new(frame),
+                            None => return false,
+                        },
+                    },
+                };
                 ppn = frame.ppn;
                 self.data_frames.insert(vpn, frame);
             }
         }
-        let pte_flags = PTEFlags::from_bits(self.map_perm.bits).unwrap();
         page_table.map(vpn, ppn, pte_flags);
+        true
     }
 
+    /// 删除页表项 还没有调入的页不需要处理 换出的页归还交换区
     pub fn unmap_one(&mut self, page_table: &mut PageTable, vpn: VirtPageNum) {
-        if self.map_type == MapType::Framed {
-            self.data_frames.remove(&vpn);
+        if self.swapped.remove(&vpn).is_some() {
+            return;
+        }
+        if self.map_type == MapType::Framed && self.data_frames.remove(&vpn).is_none() {
+            return;
         }
         page_table.unmap(vpn);
     }
 
+    /// 调整逻辑段的结束位置 缩小时拆除超出的页 扩大的部分访问时才分配
+    pub fn set_end(&mut self, page_table: &mut PageTable, end: VirtPageNum) {
+        let start = self.vpn_range.get_start();
+        let old_end = self.vpn_range.get_end();
+        if end < old_end {
+            for vpn in VPNRange::new(end, old_end) {
+                self.unmap_one(page_table, vpn);
+            }
+        }
+        self.vpn_range = VPNRange::new(start, end);
+    }
+
     pub fn map(&mut self, page_table: &mut PageTable) {
+        if self.is_lazy() {
+            return;
+        }
+        // 内核按物理地址访问的页 分配不到页帧时无法继续
         for vpn in self.vpn_range {
-            self.map_one(page_table, vpn);
+            assert!(self.map_one(page_table, vpn), "physical frame none!");
+        }
+    }
+
+    /// 处理这个逻辑段中的缺页 调用者已经检查过访问权限 分配不到页帧时返回false
+    fn handle_page_fault(
+        &mut self,
+        page_table: &mut PageTable,
+        vpn: VirtPageNum,
+        access: MapPermission,
+    ) -> bool {
+        match page_table.translate(vpn) {
+            Some(pte) if pte.is_valid() => {
+                // 只有写入共享的只读页需要处理 其余是快表中过期的项
+                if access == MapPermission::W && !pte.writable() {
+                    self.copy_on_write(page_table, vpn)
+                } else {
+                    true
+                }
+            }
+            _ => match self.swapped.remove(&vpn) {
+                Some(slot) => {
+                    let swapped_in = self.swap_in(page_table, vpn, &slot);
+                    // 没有调入时这一页留在交换区
+                    if !swapped_in {
+                        self.swapped.insert(vpn, slot);
+                    }
+                    swapped_in
+                }
+                None => self.map_one(page_table, vpn),
+            },
+        }
+    }
+
+    /// 把独占的页写到交换区并释放页帧 交换区满时返回false
+    fn swap_out_one(&mut self, page_table: &mut PageTable, vpn: VirtPageNum) -> bool {
+        let slot = match SwapSlot::alloc() {
+            Some(slot) => slot,
+            None => return false,
+        };
+        let frame = self.data_frames.remove(&vpn).unwrap();
+        slot.write(frame.ppn);
+        page_table.unmap(vpn);
+        self.swapped.insert(vpn, slot);
+        true
+    }
+
+    /// 从交换区调入 换出的页都是独占的 按逻辑段的权限映射 分配不到页帧时返回false
+    fn swap_in(&mut self, page_table: &mut PageTable, vpn: VirtPageNum, slot: &SwapSlot) -> bool {
+        let frame = match frame_alloc() {
+            Some(frame) => frame,
+            None => return false,
+        };
+        slot.read(frame.ppn);
+        page_table.map(
+            vpn,
+            frame.ppn,
+            PTEFlags::from_bits(self.map_perm.bits).unwrap(),
+        );
+        self.data_frames.insert(vpn, Arc::new(frame));
+        true
+    }
+
+    /// 写时复制 页帧只剩自己使用时直接改成可写 否则复制出私有的页帧 分配不到页帧时返回false
+    fn copy_on_write(&mut self, page_table: &mut PageTable, vpn: VirtPageNum) -> bool {
+        let pte_flags = PTEFlags::from_bits(self.map_perm.bits).unwrap();
+        let old_frame = &self.data_frames[&vpn];
+        if Arc::strong_count(old_frame) == 1 {
+            page_table.set_flags(vpn, pte_flags);
+            return true;
         }
+        let frame = match frame_alloc() {
+            Some(frame) => frame,
+            None => return false,
+        };
+        frame
+            .ppn
+            .get_bytes_array()
+            .copy_from_slice(old_frame.ppn.get_bytes_array());
+        page_table.unmap(vpn);
+        page_table.map(vpn, frame.ppn, pte_flags);
+        self.data_frames.insert(vpn, Arc::new(frame));
+        true
     }
 
     pub fn unmap(&mut self, page_table: &mut PageTable) {
@@ -367,6 +1077,12 @@ impl MapArea {
         let mut current_vpn = self.vpn_range.get_start();
         let len = data.len();
         loop {
+            if !self.data_frames.contains_key(&current_vpn) {
+                assert!(
+                    self.map_one(page_table, current_vpn),
+                    "physical frame none!"
+                );
+            }
             let src = &data[start..len.min(start + PAGE_SIZE)];
             let dst = &mut page_table
                 .translate(current_vpn)
@@ -386,8 +1102,21 @@ impl MapArea {
         Self {
             vpn_range: VPNRange::new(another.vpn_range.get_start(), another.vpn_range.get_end()),
             data_frames: BTreeMap::new(),
+            swapped: BTreeMap::new(),
             map_type: another.map_type,
             map_perm: another.map_perm,
+            file: another.file.clone(),
+            shm: another.shm.clone(),
+        }
+    }
+}
+
+/// 文件映射拆除后写回脏页 释放不再使用的缓存页
+impl Drop for MapArea {
+    fn drop(&mut self) {
+        if let Some(file) = &self.file {
+            self.data_frames.clear();
+            page_cache::release(file.id);
         }
     }
 }
@@ -2,7 +2,10 @@ mod address;
 mod frame_allocator;
 mod heap_allocator;
 mod memory_set;
+pub mod page_cache;
 mod page_table;
+pub mod shm;
+pub mod swap;
 
 pub use address::*;
 pub use frame_allocator::*;
@@ -13,4 +16,5 @@ pub fn init() {
     heap_allocator::init_heap();
     frame_allocator::init_frame_allocator();
     memory_set::KERNEL_SPACE.exclusive_access().activate();
+    swap::init();
 }
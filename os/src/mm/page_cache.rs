@@ -0,0 +1,173 @@
+use super::frame_allocator::{frame_alloc, FrameTracker};
+use crate::config::PAGE_SIZE;
+use crate::fs::{Inode, InodeId};
+use crate::sync::UPSafeCell;
+use alloc::{collections::BTreeMap, sync::Arc};
+use lazy_static::*;
+
+/// 缓存的一页文件内容
+struct CachedPage {
+    frame: Arc<FrameTracker>,
+    /// 被共享映射写过 需要写回文件
+    dirty: bool,
+}
+
+/// 一个文件的缓存页
+struct CachedFile {
+    /// 写回时使用的索引节点
+    inode: Arc<dyn Inode>,
+    /// 文件页号和缓存页
+    pages: BTreeMap<usize, CachedPage>,
+}
+
+impl CachedFile {
+    /// 把脏页写回文件 文件末尾之后的部分丢弃
+    ///
+    /// 还被映射的页之后可能继续被写 保持为脏页
+    fn flush(&mut self) {
+        let size = self.inode.size();
+        for (page, cached) in self.pages.iter_mut() {
+            if !cached.dirty {
+                continue;
+            }
+            cached.dirty = Arc::strong_count(&cached.frame) > 1;
+            let start = page * PAGE_SIZE;
+            if start >= size {
+                continue;
+            }
+            let len = PAGE_SIZE.min(size - start);
+            // 写回失败时仍然是脏页
+            if self
+                .inode
+                .write_at(start, &cached.frame.ppn.get_bytes_array()[..len])
+                .is_none()
+            {
+                cached.dirty = true;
+            }
+        }
+    }
+}
+
+lazy_static! {
+    /// 页缓存 以索引节点的标识和页号索引 同一个文件的映射共享页帧 不论从哪个路径打开
+    static ref PAGE_CACHE: UPSafeCell<BTreeMap<InodeId, CachedFile>> =
+        unsafe { UPSafeCell::new(BTreeMap::new()) };
+}
+
+/// 文件第page页的页帧 不在缓存中时从文件读入 超出文件的部分为0 分配不到页帧或者读文件出错时返回None
+pub fn get_page(inode: &Arc<dyn Inode>, page: usize) -> Option<Arc<FrameTracker>> {
+    let id = inode.id();
+    let mut cache = PAGE_CACHE.exclusive_access();
+    if let Some(cached) = cache.get(&id).and_then(|file| file.pages.get(&page)) {
+        return Some(cached.frame.clone());
+    }
+    let frame = Arc::new(frame_alloc()?);
+    inode.read_at(page * PAGE_SIZE, frame.ppn.get_bytes_array())?;
+    let file = cache.entry(id).or_insert_with(|| CachedFile {
+        inode: inode.clone(),
+        pages: BTreeMap::new(),
+    });
+    file.pages.insert(
+        page,
+        CachedPage {
+            frame: frame.clone(),
+            dirty: false,
+        },
+    );
+    Some(frame)
+}
+
+/// 共享映射可写的页 之后要写回文件
+pub fn mark_dirty(id: InodeId, page: usize) {
+    if let Some(cached) = PAGE_CACHE
+        .exclusive_access()
+        .get_mut(&id)
+        .and_then(|file| file.pages.get_mut(&page))
+    {
+        cached.dirty = true;
+    }
+}
+
+/// 把文件的脏页写回
+pub fn flush(id: InodeId) {
+    if let Some(file) = PAGE_CACHE.exclusive_access().get_mut(&id) {
+        file.flush();
+    }
+}
+
+/// 从文件读 再用脏页覆盖读到的部分 不写回也能读到共享映射中的修改 读文件出错时返回None
+pub fn read_at(inode: &Arc<dyn Inode>, offset: usize, buf: &mut [u8]) -> Option<usize> {
+    let len = inode.read_at(offset, buf)?;
+    let cache = PAGE_CACHE.exclusive_access();
+    let file = match cache.get(&inode.id()) {
+        Some(file) => file,
+        None => return Some(len),
+    };
+    let end = offset + len;
+    let pages = offset / PAGE_SIZE..(end + PAGE_SIZE - 1) / PAGE_SIZE;
+    for (page, cached) in file.pages.range(pages) {
+        if !cached.dirty {
+            continue;
+        }
+        let start = offset.max(page * PAGE_SIZE);
+        let page_end = end.min((page + 1) * PAGE_SIZE);
+        buf[start - offset..page_end - offset].copy_from_slice(
+            &cached.frame.ppn.get_bytes_array()
+                [start % PAGE_SIZE..start % PAGE_SIZE + page_end - start],
+        );
+    }
+    Some(len)
+}
+
+/// 写回所有文件的脏页
+pub fn flush_all() {
+    for file in PAGE_CACHE.exclusive_access().values_mut() {
+        file.flush();
+    }
+}
+
+/// 通过write写入文件时同步修改已经缓存的页 映射能看到新的内容
+pub fn update(id: InodeId, offset: usize, data: &[u8]) {
+    let mut cache = PAGE_CACHE.exclusive_access();
+    let file = match cache.get_mut(&id) {
+        Some(file) => file,
+        None => return,
+    };
+    let end = offset + data.len();
+    let mut start = offset;
+    while start < end {
+        let page = start / PAGE_SIZE;
+        let page_end = ((page + 1) * PAGE_SIZE).min(end);
+        if let Some(cached) = file.pages.get(&page) {
+            let bytes = cached.frame.ppn.get_bytes_array();
+            bytes[start % PAGE_SIZE..start % PAGE_SIZE + page_end - start]
+                .copy_from_slice(&data[start - offset..page_end - offset]);
+        }
+        start = page_end;
+    }
+}
+
+/// 文件被清空时缓存页也清零
+pub fn clear(id: InodeId) {
+    if let Some(file) = PAGE_CACHE.exclusive_access().get_mut(&id) {
+        for cached in file.pages.values_mut() {
+            cached.frame.ppn.get_bytes_array().fill(0);
+            cached.dirty = false;
+        }
+    }
+}
+
+/// 释放已经没有映射的缓存页 脏页先写回 映射被拆除时调用
+pub fn release(id: InodeId) {
+    let mut cache = PAGE_CACHE.exclusive_access();
+    let file = match cache.get_mut(&id) {
+        Some(file) => file,
+        None => return,
+    };
+    file.flush();
+    file.pages
+        .retain(|_, cached| Arc::strong_count(&cached.frame) > 1);
+    if file.pages.is_empty() {
+        cache.remove(&id);
+    }
+}
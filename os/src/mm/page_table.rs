@@ -1,5 +1,7 @@
 use super::address::*;
 use super::frame_allocator::*;
+use super::{MapPermission, MemorySet};
+use crate::task::current_task;
 use alloc::string::String;
 use alloc::vec;
 use alloc::vec::Vec;
@@ -95,6 +97,17 @@ impl PageTable {
         *pte = PageTableEntry::empty();
     }
 
+    /// 修改已有页表项的权限
+    pub fn set_flags(&mut self, vpn: VirtPageNum, flags: PTEFlags) {
+        let pte = self.find_pte_create(vpn).unwrap();
+        assert!(
+            pte.is_valid(),
+            "vpn {:?} is invaild before setting flags",
+            vpn
+        );
+        *pte = PageTableEntry::new(pte.ppn(), flags | PTEFlags::V);
+    }
+
     fn find_pte_create(&mut self, vpn: VirtPageNum) -> Option<&mut PageTableEntry> {
         // VirtPageNum: 27位 以9位分隔
         let idxs = vpn.indexes();
@@ -167,16 +180,41 @@ impl PageTable {
     }
 }
 
-/// translate a pointer to a mutable u8 Vec through page table
-pub fn translated_byte_buffer(token: usize, ptr: *const u8, len: usize) -> Vec<&'static mut [u8]> {
-    let page_table = PageTable::from_token(token);
+/// 内核要访问的用户页 交给当前进程调入或者完成写时复制 地址不属于任何逻辑段或者没有access权限时返回None
+///
+/// 页帧固定在当前线程上 系统调用返回之前不会被换出
+fn user_ppn(
+    memory_set: &mut MemorySet,
+    vpn: VirtPageNum,
+    access: MapPermission,
+) -> Option<PhysPageNum> {
+    let frame = memory_set.touch(vpn, access)?;
+    let ppn = frame.ppn;
+    current_task().unwrap().pin_frame(frame);
+    Some(ppn)
+}
+
+fn user_pa(memory_set: &mut MemorySet, va: VirtAddr, access: MapPermission) -> Option<PhysAddr> {
+    let aligned_pa: PhysAddr = user_ppn(memory_set, va.floor(), access)?.into();
+    Some((usize::from(aligned_pa) + va.page_offset()).into())
+}
+
+/// translate a pointer to a mutable u8 Vec through the address space
+///
+/// 内核要写入缓冲区时access为W 只读取时为R
+pub fn translated_byte_buffer(
+    memory_set: &mut MemorySet,
+    ptr: *const u8,
+    len: usize,
+    access: MapPermission,
+) -> Option<Vec<&'static mut [u8]>> {
     let mut start = ptr as usize;
-    let end = start + len;
+    let end = start.checked_add(len)?;
     let mut v = Vec::new();
     while start < end {
         let start_va = VirtAddr::from(start);
         let mut vpn = start_va.floor();
-        let ppn = page_table.translate(vpn).unwrap().ppn();
+        let ppn = user_ppn(memory_set, vpn, access)?;
         vpn.step();
         let mut end_va: VirtAddr = vpn.into();
         end_va = end_va.min(VirtAddr::from(end));
@@ -187,45 +225,35 @@ pub fn translated_byte_buffer(token: usize, ptr: *const u8, len: usize) -> Vec<&
         }
         start = end_va.into();
     }
-    v
+    Some(v)
 }
 
-pub fn translated_str(token: usize, ptr: *const u8) -> String {
-    let page_table = PageTable::from_token(token);
+/// 以0结尾的字符串 每页只翻译一次
+pub fn translated_str(memory_set: &mut MemorySet, ptr: *const u8) -> Option<String> {
     let mut string = String::new();
-    let mut va = ptr as usize;
+    let mut va = VirtAddr::from(ptr as usize);
     loop {
-        let ch: u8 = *(page_table
-            .translate_va(VirtAddr::from(va))
-            .unwrap()
-            .get_mut());
-        if ch == 0 {
-            break;
-        } else {
-            string.push(ch as char);
-            va += 1;
+        let ppn = user_ppn(memory_set, va.floor(), MapPermission::R)?;
+        let bytes = &ppn.get_bytes_array()[va.page_offset()..];
+        match bytes.iter().position(|&ch| ch == 0) {
+            Some(len) => {
+                string.extend(bytes[..len].iter().map(|&ch| ch as char));
+                return Some(string);
+            }
+            None => {
+                string.extend(bytes.iter().map(|&ch| ch as char));
+                va = VirtAddr::from(usize::from(va) + bytes.len());
+            }
         }
     }
-    string
 }
 
-pub fn translated_ref<T>(token: usize, ptr: *const T) -> &'static T {
-    let page_table = PageTable::from_token(token);
-    page_table
-        .translate_va(VirtAddr::from(ptr as usize))
-        .unwrap()
-        .get_ref()
+pub fn translated_ref<T>(memory_set: &mut MemorySet, ptr: *const T) -> Option<&'static T> {
+    Some(user_pa(memory_set, VirtAddr::from(ptr as usize), MapPermission::R)?.get_ref())
 }
 
-pub fn translated_refmut<T>(token: usize, ptr: *mut T) -> &'static mut T {
-    //println!("into translated_refmut!");
-    let page_table = PageTable::from_token(token);
-    let va = ptr as usize;
-    //println!("translated_refmut: before translate_va");
-    page_table
-        .translate_va(VirtAddr::from(va))
-        .unwrap()
-        .get_mut()
+pub fn translated_refmut<T>(memory_set: &mut MemorySet, ptr: *mut T) -> Option<&'static mut T> {
+    Some(user_pa(memory_set, VirtAddr::from(ptr as usize), MapPermission::W)?.get_mut())
 }
 
 pub struct UserBuffer {
@@ -237,6 +265,17 @@ impl UserBuffer {
         Self { buffers }
     }
 
+    /// 把内核缓冲区包装成UserBuffer 文件读写直接使用内核中的数据
+    ///
+    /// # Safety
+    ///
+    /// 返回值不能在buf失效之后继续使用
+    pub unsafe fn from_kernel(buf: &mut [u8]) -> Self {
+        Self {
+            buffers: vec![core::slice::from_raw_parts_mut(buf.as_mut_ptr(), buf.len())],
+        }
+    }
+
     pub fn len(&self) -> usize {
         let mut total: usize = 0;
         for b in self.buffers.iter() {
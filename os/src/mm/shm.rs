@@ -0,0 +1,102 @@
+use super::frame_allocator::{frame_alloc, FrameTracker};
+use crate::sync::UPSafeCell;
+use alloc::{collections::BTreeMap, sync::Arc};
+use lazy_static::*;
+
+/// 共享内存段 页帧在第一次访问时分配 之后所有映射共享同一个页帧
+///
+/// 逻辑段持有段的引用 段被删除后已有的映射仍然有效 最后一个映射拆除时释放页帧
+pub struct SharedMemory {
+    pages: usize,
+    frames: UPSafeCell<BTreeMap<usize, Arc<FrameTracker>>>,
+}
+
+impl SharedMemory {
+    pub fn new(pages: usize) -> Arc<Self> {
+        Arc::new(Self {
+            pages,
+            frames: unsafe { UPSafeCell::new(BTreeMap::new()) },
+        })
+    }
+
+    pub fn pages(&self) -> usize {
+        self.pages
+    }
+
+    /// 第page页的页帧 还没有分配时分配一个清零的页帧 分配不到时返回None
+    pub fn frame(&self, page: usize) -> Option<Arc<FrameTracker>> {
+        let mut frames = self.frames.exclusive_access();
+        if let Some(frame) = frames.get(&page) {
+            return Some(frame.clone());
+        }
+        let frame = Arc::new(frame_alloc()?);
+        frames.insert(page, frame.clone());
+        Some(frame)
+    }
+}
+
+/// 映射到逻辑段的共享内存 逻辑段第一页对应段中的第page页
+#[derive(Clone)]
+pub struct ShmMapping {
+    pub segment: Arc<SharedMemory>,
+    pub page: usize,
+}
+
+/// 有标识符的共享内存段
+struct ShmEntry {
+    key: usize,
+    segment: Arc<SharedMemory>,
+}
+
+struct ShmTable {
+    next_id: usize,
+    segments: BTreeMap<usize, ShmEntry>,
+}
+
+lazy_static! {
+    /// 以标识符索引的共享内存段
+    static ref SHM_TABLE: UPSafeCell<ShmTable> = unsafe {
+        UPSafeCell::new(ShmTable {
+            next_id: 1,
+            segments: BTreeMap::new(),
+        })
+    };
+}
+
+/// 键为key的段 返回标识符和段
+pub fn shm_find(key: usize) -> Option<(usize, Arc<SharedMemory>)> {
+    SHM_TABLE
+        .exclusive_access()
+        .segments
+        .iter()
+        .find(|(_, entry)| entry.key == key)
+        .map(|(id, entry)| (*id, entry.segment.clone()))
+}
+
+/// 新建pages页的段 返回标识符
+pub fn shm_create(key: usize, pages: usize) -> usize {
+    let mut table = SHM_TABLE.exclusive_access();
+    let id = table.next_id;
+    table.next_id += 1;
+    table.segments.insert(
+        id,
+        ShmEntry {
+            key,
+            segment: SharedMemory::new(pages),
+        },
+    );
+    id
+}
+
+pub fn shm_segment(id: usize) -> Option<Arc<SharedMemory>> {
+    SHM_TABLE
+        .exclusive_access()
+        .segments
+        .get(&id)
+        .map(|entry| entry.segment.clone())
+}
+
+/// 删除标识符 已经映射的进程还能继续使用
+pub fn shm_remove(id: usize) -> bool {
+    SHM_TABLE.exclusive_access().segments.remove(&id).is_some()
+}
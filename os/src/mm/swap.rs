@@ -0,0 +1,176 @@
+use super::address::{PhysPageNum, VirtPageNum};
+use super::frame_allocator::frame_usage;
+use crate::config::{PAGE_SIZE, SWAP_HIGH_FRAMES, SWAP_LOW_FRAMES};
+use crate::drivers::SWAP_DEVICE;
+use crate::sync::UPSafeCell;
+use crate::task::manager::{pid2process, pids};
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicBool, Ordering};
+use easy_fs::BLOCK_SZ;
+use lazy_static::*;
+
+/// 一页占用的块数
+const BLOCKS_PER_PAGE: usize = PAGE_SIZE / BLOCK_SZ;
+
+/// 交换区按页分配
+struct SwapSpace {
+    /// 总页数
+    total: usize,
+    /// 还没有分配过的第一页
+    current: usize,
+    /// 回收的页
+    recycled: Vec<usize>,
+}
+
+impl SwapSpace {
+    fn new() -> Self {
+        let total = SWAP_DEVICE
+            .as_ref()
+            .and_then(|device| device.num_blocks())
+            .map_or(0, |blocks| blocks / BLOCKS_PER_PAGE);
+        Self {
+            total,
+            current: 0,
+            recycled: Vec::new(),
+        }
+    }
+
+    fn alloc(&mut self) -> Option<usize> {
+        if let Some(slot) = self.recycled.pop() {
+            Some(slot)
+        } else if self.current < self.total {
+            self.current += 1;
+            Some(self.current - 1)
+        } else {
+            None
+        }
+    }
+
+    fn free(&self) -> usize {
+        self.total - self.current + self.recycled.len()
+    }
+}
+
+lazy_static! {
+    static ref SWAP_SPACE: UPSafeCell<SwapSpace> = unsafe { UPSafeCell::new(SwapSpace::new()) };
+    /// 时钟指针 下次从这个进程的这一页开始扫描
+    static ref CLOCK_HAND: UPSafeCell<(usize, VirtPageNum)> =
+        unsafe { UPSafeCell::new((0, VirtPageNum(0))) };
+}
+
+/// 交换区中的一页 释放时归还交换区
+pub struct SwapSlot(usize);
+
+impl SwapSlot {
+    /// 分配一页交换区 交换区已满或者没有交换区时返回None
+    pub fn alloc() -> Option<Self> {
+        SWAP_SPACE.exclusive_access().alloc().map(Self)
+    }
+
+    /// 把页帧的内容写到交换区
+    pub fn write(&self, ppn: PhysPageNum) {
+        let device = SWAP_DEVICE.as_ref().unwrap();
+        for (i, block) in ppn.get_bytes_array().chunks(BLOCK_SZ).enumerate() {
+            device.write_block(self.0 * BLOCKS_PER_PAGE + i, block);
+        }
+    }
+
+    /// 从交换区读回页帧
+    pub fn read(&self, ppn: PhysPageNum) {
+        let device = SWAP_DEVICE.as_ref().unwrap();
+        for (i, block) in ppn.get_bytes_array().chunks_mut(BLOCK_SZ).enumerate() {
+            device.read_block(self.0 * BLOCKS_PER_PAGE + i, block);
+        }
+    }
+
+    /// fork时给子进程复制一份 不占用页帧
+    pub fn duplicate(&self) -> Option<Self> {
+        let slot = Self::alloc()?;
+        let device = SWAP_DEVICE.as_ref().unwrap();
+        let mut block = [0u8; BLOCK_SZ];
+        for i in 0..BLOCKS_PER_PAGE {
+            device.read_block(self.0 * BLOCKS_PER_PAGE + i, &mut block);
+            device.write_block(slot.0 * BLOCKS_PER_PAGE + i, &block);
+        }
+        Some(slot)
+    }
+}
+
+impl Drop for SwapSlot {
+    fn drop(&mut self) {
+        SWAP_SPACE.exclusive_access().recycled.push(self.0);
+    }
+}
+
+/// 返回(交换区总页数, 空闲页数)
+pub fn swap_usage() -> (usize, usize) {
+    let space = SWAP_SPACE.exclusive_access();
+    (space.total, space.free())
+}
+
+/// 交换区探测设备时要分配页帧 在换出之前初始化
+pub fn init() {
+    lazy_static::initialize(&SWAP_SPACE);
+}
+
+/// 上次换出时跳过了正在使用的地址空间 空闲页帧还没有回到SWAP_HIGH_FRAMES
+static BALANCE_PENDING: AtomicBool = AtomicBool::new(false);
+
+/// 空闲页帧不足时用时钟算法换出用户页 分配页帧时调用
+///
+/// 持有锁的进程正在使用自己的地址空间 跳过这些进程 没有换出足够的页时
+/// 留到下一次trap再换出 换出过程中分配页帧时不会再次进入
+pub fn balance() {
+    let mut hand = match CLOCK_HAND.try_exclusive_access() {
+        Some(hand) => hand,
+        None => return,
+    };
+    let free = frame_usage().1;
+    if free >= SWAP_LOW_FRAMES || SWAP_DEVICE.is_none() {
+        BALANCE_PENDING.store(false, Ordering::Relaxed);
+        return;
+    }
+    let mut need = SWAP_HIGH_FRAMES - free;
+    let pids = pids();
+    if pids.is_empty() {
+        return;
+    }
+    let start = pids.iter().position(|pid| *pid >= hand.0).unwrap_or(0);
+    // 转两圈 第一圈清除了访问位的页在第二圈可以换出
+    for i in 0..=2 * pids.len() {
+        if need == 0 {
+            break;
+        }
+        let pid = pids[(start + i) % pids.len()];
+        let from = if pid == hand.0 {
+            hand.1
+        } else {
+            VirtPageNum(0)
+        };
+        let process = match pid2process(pid) {
+            Some(process) => process,
+            None => continue,
+        };
+        let mut inner = match process.try_inner_exclusive_access() {
+            Some(inner) => inner,
+            None => continue,
+        };
+        let (evicted, stop) = inner.memory_set.swap_out(from, need);
+        need -= evicted;
+        match stop {
+            Some(vpn) => *hand = (pid, vpn),
+            None => *hand = (pid + 1, VirtPageNum(0)),
+        }
+    }
+    BALANCE_PENDING.store(need > 0, Ordering::Relaxed);
+}
+
+/// trap入口不持有任何锁 补上分配页帧时没能完成的换出 返回是否换出过
+pub fn balance_pending() -> bool {
+    if BALANCE_PENDING.load(Ordering::Relaxed) {
+        balance();
+        true
+    } else {
+        false
+    }
+}
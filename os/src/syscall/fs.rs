@@ -1,10 +1,16 @@
 #![allow(unused)]
 
-use crate::config::PAGE_SIZE;
-use crate::fs::{make_pipe, open_file, OpenFlags};
+use crate::config::{FD_LIMIT, PAGE_SIZE};
+use crate::fs::{
+    absolute_path, lookup, make_pipe, mkdir, mknod, mount, open_path, set_lock, sync_all, sync_fs,
+    test_lock, umount, unlock, File, FileLock, InodeType, LockOwner, OpenFlags, PollEvents,
+    XattrFlags,
+};
+use crate::mm::shm::SharedMemory;
 use crate::mm::MapPermission;
+use crate::timer::{add_timer, get_time_ms, remove_timer};
 use crate::{mm::*, task::*};
-use alloc::sync::Arc;
+use alloc::{string::String, sync::Arc, vec, vec::Vec};
 use bitflags::bitflags;
 
 const FD_STDOUT: usize = 1;
@@ -50,17 +56,33 @@ bitflags! {
         const DIR   = 0o040000;
         /// ordinary regular file
         const FILE  = 0o100000;
+        /// named pipe
+        const FIFO  = 0o010000;
+        /// mask of the file type bits
+        const TYPE  = 0o170000;
     }
 }
 
 pub fn sys_open(path: *const u8, flags: u32) -> isize {
     let process = current_process();
-    let token = current_user_token();
-    let path = translated_str(token, path);
-    if let Some(inode) = open_file(path.as_str(), OpenFlags::from_bits(flags).unwrap()) {
+    let path = match user_path(&mut process.inner_exclusive_access(), path) {
+        Some(path) => path,
+        None => return -1,
+    };
+    let flags = match OpenFlags::from_bits(flags) {
+        Some(flags) => flags,
+        None => return -1,
+    };
+    if let Some(inode) = open_path(path.as_str(), flags) {
         let mut inner = process.inner_exclusive_access();
-        let fd = inner.alloc_fd();
+        let fd = match inner.alloc_fd() {
+            Some(fd) => fd,
+            None => return -1,
+        };
         inner.fd_table[fd] = Some(inode);
+        if flags.contains(OpenFlags::CLOEXEC) {
+            inner.cloexec_fds.insert(fd);
+        }
         fd as isize
     } else {
         -1
@@ -70,37 +92,43 @@ pub fn sys_open(path: *const u8, flags: u32) -> isize {
 pub fn sys_close(fd: usize) -> isize {
     let process = current_process();
     let mut inner = process.inner_exclusive_access();
-    if fd >= inner.fd_table.len() {
-        return -1;
-    }
-    if inner.fd_table[fd].is_none() {
-        return -1;
+    if inner.close_fd(process.getpid(), fd) {
+        0
+    } else {
+        -1
     }
-    inner.fd_table[fd].take();
-    0
 }
 
 /// write buf of length `len`  to a file with `fd`
 pub fn sys_write(fd: usize, buf: *const u8, len: usize) -> isize {
-    let token = current_user_token();
     let process = current_process();
-    let inner = process.inner_exclusive_access();
+    let mut inner = process.inner_exclusive_access();
     if fd >= inner.fd_table.len() {
         return -1;
     }
     if let Some(file) = &inner.fd_table[fd] {
         let file = file.clone();
+        if !file.writable() {
+            return -1;
+        }
+        let buffers =
+            match translated_byte_buffer(&mut inner.memory_set, buf, len, MapPermission::R) {
+                Some(buffers) => buffers,
+                None => return -1,
+            };
         drop(inner);
-        file.write(UserBuffer::new(translated_byte_buffer(token, buf, len))) as isize
+        match file.write(UserBuffer::new(buffers)) {
+            Some(written) => written as isize,
+            None => -1,
+        }
     } else {
         -1
     }
 }
 
 pub fn sys_read(fd: usize, buf: *const u8, len: usize) -> isize {
-    let token = current_user_token();
     let process = current_process();
-    let inner = process.inner_exclusive_access();
+    let mut inner = process.inner_exclusive_access();
     if fd >= inner.fd_table.len() {
         return -1;
     }
@@ -109,28 +137,492 @@ pub fn sys_read(fd: usize, buf: *const u8, len: usize) -> isize {
         if !file.readable() {
             return -1;
         }
+        let buffers =
+            match translated_byte_buffer(&mut inner.memory_set, buf, len, MapPermission::W) {
+                Some(buffers) => buffers,
+                None => return -1,
+            };
         // release current task TCB manually to avoid multi-borrow
         drop(inner);
-        file.read(UserBuffer::new(translated_byte_buffer(token, buf, len))) as isize
+        match file.read(UserBuffer::new(buffers)) {
+            Some(read) => read as isize,
+            None => -1,
+        }
+    } else {
+        -1
+    }
+}
+
+/// readv和writev的参数 和Linux的struct iovec相同
+#[repr(C)]
+pub struct IoVec {
+    pub base: *mut u8,
+    pub len: usize,
+}
+
+/// 把用户的iovec数组拼成一个UserBuffer 有地址无效时返回None
+fn iovec_buffer(iov: *const IoVec, iovcnt: usize, access: MapPermission) -> Option<UserBuffer> {
+    let process = current_process();
+    let mut inner = process.inner_exclusive_access();
+    let mut buffers = Vec::new();
+    for i in 0..iovcnt {
+        let iov = translated_ref(&mut inner.memory_set, unsafe { iov.add(i) })?;
+        if iov.len > 0 {
+            buffers.extend(translated_byte_buffer(
+                &mut inner.memory_set,
+                iov.base,
+                iov.len,
+                access,
+            )?);
+        }
+    }
+    Some(UserBuffer::new(buffers))
+}
+
+/// 当前进程的文件
+fn get_file(fd: usize) -> Option<Arc<dyn File + Send + Sync>> {
+    let process = current_process();
+    let inner = process.inner_exclusive_access();
+    inner.fd_table.get(fd).cloned().flatten()
+}
+
+pub fn sys_readv(fd: usize, iov: *const IoVec, iovcnt: usize) -> isize {
+    match (get_file(fd), iovec_buffer(iov, iovcnt, MapPermission::W)) {
+        (Some(file), Some(buf)) if file.readable() => {
+            file.read(buf).map_or(-1, |read| read as isize)
+        }
+        _ => -1,
+    }
+}
+
+pub fn sys_writev(fd: usize, iov: *const IoVec, iovcnt: usize) -> isize {
+    match (get_file(fd), iovec_buffer(iov, iovcnt, MapPermission::R)) {
+        (Some(file), Some(buf)) if file.writable() => {
+            file.write(buf).map_or(-1, |written| written as isize)
+        }
+        _ => -1,
+    }
+}
+
+/// 在两个文件之间复制数据 数据只经过内核缓冲区
+///
+/// 给出偏移时按偏移读写 不改变文件自己的偏移
+fn transfer(
+    input: Arc<dyn File + Send + Sync>,
+    mut in_offset: Option<&mut usize>,
+    output: Arc<dyn File + Send + Sync>,
+    mut out_offset: Option<&mut usize>,
+    count: usize,
+) -> isize {
+    if !input.readable() || !output.writable() {
+        return -1;
+    }
+    let mut buffer = vec![0u8; PAGE_SIZE];
+    let mut total = 0;
+    while total < count {
+        let len = (count - total).min(buffer.len());
+        let chunk = unsafe { UserBuffer::from_kernel(&mut buffer[..len]) };
+        let read = match in_offset.as_deref_mut() {
+            Some(offset) => input.pread(*offset, chunk).map(|read| {
+                *offset += read;
+                read
+            }),
+            None => input.read(chunk),
+        };
+        let read = match read {
+            Some(read) => read,
+            None => return -1,
+        };
+        if read == 0 {
+            break;
+        }
+        let chunk = unsafe { UserBuffer::from_kernel(&mut buffer[..read]) };
+        let written = match out_offset.as_deref_mut() {
+            Some(offset) => output.pwrite(*offset, chunk).map(|written| {
+                *offset += written;
+                written
+            }),
+            None => output.write(chunk),
+        };
+        let written = match written {
+            Some(written) => written,
+            None => return -1,
+        };
+        total += written;
+        // 管道的读端已经关闭
+        if written < read {
+            break;
+        }
+    }
+    total as isize
+}
+
+/// 从in_fd复制count字节到out_fd offset不为空时从*offset读并更新它
+pub fn sys_sendfile(out_fd: usize, in_fd: usize, offset: *mut usize, count: usize) -> isize {
+    let (input, output) = match (get_file(in_fd), get_file(out_fd)) {
+        (Some(input), Some(output)) => (input, output),
+        _ => return -1,
+    };
+    let process = current_process();
+    let offset = if offset.is_null() {
+        None
+    } else {
+        match translated_refmut(&mut process.inner_exclusive_access().memory_set, offset) {
+            Some(offset) => Some(offset),
+            None => return -1,
+        }
+    };
+    transfer(input, offset, output, None, count)
+}
+
+/// 和sendfile类似 两端都可以指定偏移 不要求其中一端是管道
+pub fn sys_splice(
+    in_fd: usize,
+    in_offset: *mut usize,
+    out_fd: usize,
+    out_offset: *mut usize,
+    len: usize,
+) -> isize {
+    let (input, output) = match (get_file(in_fd), get_file(out_fd)) {
+        (Some(input), Some(output)) => (input, output),
+        _ => return -1,
+    };
+    let process = current_process();
+    let mut inner = process.inner_exclusive_access();
+    let in_offset = if in_offset.is_null() {
+        None
+    } else {
+        match translated_refmut(&mut inner.memory_set, in_offset) {
+            Some(offset) => Some(offset),
+            None => return -1,
+        }
+    };
+    let out_offset = if out_offset.is_null() {
+        None
+    } else {
+        match translated_refmut(&mut inner.memory_set, out_offset) {
+            Some(offset) => Some(offset),
+            None => return -1,
+        }
+    };
+    drop(inner);
+    transfer(input, in_offset, output, out_offset, len)
+}
+
+/// 有设备不能保证写入已经落盘时返回-1
+pub fn sys_sync() -> isize {
+    if sync_all() {
+        0
     } else {
         -1
     }
 }
 
+pub fn sys_fsync(fd: usize) -> isize {
+    match get_file(fd) {
+        Some(file) if file.sync() => 0,
+        _ => -1,
+    }
+}
+
+/// 写回文件所在的文件系统 没有路径的文件或者不能保证落盘时返回-1
+pub fn sys_syncfs(fd: usize) -> isize {
+    match get_file(fd).and_then(|file| file.path()) {
+        Some(path) if sync_fs(&path) => 0,
+        _ => -1,
+    }
+}
+
 pub fn sys_pipe(pipe: *mut usize) -> isize {
     let process = current_process();
-    let token = current_user_token();
     let mut inner = process.inner_exclusive_access();
+    let fds = match (
+        translated_refmut(&mut inner.memory_set, pipe),
+        translated_refmut(&mut inner.memory_set, unsafe { pipe.add(1) }),
+    ) {
+        (Some(read_end), Some(write_end)) => (read_end, write_end),
+        _ => return -1,
+    };
     let (pipe_read, pipe_write) = make_pipe();
-    let read_fd = inner.alloc_fd();
+    let read_fd = match inner.alloc_fd() {
+        Some(fd) => fd,
+        None => return -1,
+    };
     inner.fd_table[read_fd] = Some(pipe_read);
-    let write_fd = inner.alloc_fd();
+    let write_fd = match inner.alloc_fd() {
+        Some(fd) => fd,
+        None => {
+            inner.fd_table[read_fd] = None;
+            return -1;
+        }
+    };
     inner.fd_table[write_fd] = Some(pipe_write);
-    *translated_refmut(token, pipe) = read_fd;
-    *translated_refmut(token, unsafe { pipe.add(1) }) = write_fd;
+    *fds.0 = read_fd;
+    *fds.1 = write_fd;
     0
 }
 
+/// 读取用户传入的路径 相对路径按当前工作目录补全 地址无效时返回None
+pub fn user_path(inner: &mut ProcessControlBlockInner, ptr: *const u8) -> Option<String> {
+    let path = translated_str(&mut inner.memory_set, ptr)?;
+    Some(absolute_path(&inner.cwd, &path))
+}
+
+/// 把内核中的数据复制到当前进程的用户缓冲区 地址无效时返回false
+fn copy_to_user(buf: *mut u8, data: &[u8]) -> bool {
+    let process = current_process();
+    let mut inner = process.inner_exclusive_access();
+    let buffers =
+        match translated_byte_buffer(&mut inner.memory_set, buf, data.len(), MapPermission::W) {
+            Some(buffers) => buffers,
+            None => return false,
+        };
+    let mut start = 0;
+    for slice in buffers {
+        slice.copy_from_slice(&data[start..start + slice.len()]);
+        start += slice.len();
+    }
+    true
+}
+
+pub fn sys_setxattr(
+    path: *const u8,
+    name: *const u8,
+    value: *const u8,
+    size: usize,
+    flags: u32,
+) -> isize {
+    let process = current_process();
+    let mut inner = process.inner_exclusive_access();
+    let (path, name, value) = match (
+        user_path(&mut inner, path),
+        translated_str(&mut inner.memory_set, name),
+        translated_byte_buffer(&mut inner.memory_set, value, size, MapPermission::R),
+    ) {
+        (Some(path), Some(name), Some(value)) => (path, name, value),
+        _ => return -1,
+    };
+    drop(inner);
+    let flags = match XattrFlags::from_bits(flags) {
+        Some(flags) => flags,
+        None => return -1,
+    };
+    let inode = match lookup(path.as_str()) {
+        Some(inode) => inode,
+        None => return -1,
+    };
+    let mut data = Vec::new();
+    for slice in value {
+        data.extend_from_slice(slice);
+    }
+    if inode.set_xattr(name.as_str(), &data, flags) {
+        0
+    } else {
+        -1
+    }
+}
+
+/// 读取用户传入的路径和扩展属性名
+fn user_path_name(path: *const u8, name: *const u8) -> Option<(String, String)> {
+    let process = current_process();
+    let mut inner = process.inner_exclusive_access();
+    let path = user_path(&mut inner, path)?;
+    let name = translated_str(&mut inner.memory_set, name)?;
+    Some((path, name))
+}
+
+/// size为0时只返回属性值长度
+pub fn sys_getxattr(path: *const u8, name: *const u8, value: *mut u8, size: usize) -> isize {
+    let (path, name) = match user_path_name(path, name) {
+        Some(pair) => pair,
+        None => return -1,
+    };
+    let data = match lookup(path.as_str()).and_then(|inode| inode.get_xattr(name.as_str())) {
+        Some(data) => data,
+        None => return -1,
+    };
+    if size == 0 {
+        return data.len() as isize;
+    }
+    if size < data.len() {
+        return -1;
+    }
+    if !copy_to_user(value, &data) {
+        return -1;
+    }
+    data.len() as isize
+}
+
+/// 属性名以\0分隔 size为0时只返回所需长度
+pub fn sys_listxattr(path: *const u8, list: *mut u8, size: usize) -> isize {
+    let path = match user_path(&mut current_process().inner_exclusive_access(), path) {
+        Some(path) => path,
+        None => return -1,
+    };
+    let names = match lookup(path.as_str()).and_then(|inode| inode.list_xattr()) {
+        Some(names) => names,
+        None => return -1,
+    };
+    let mut data = Vec::new();
+    for name in names {
+        data.extend_from_slice(name.as_bytes());
+        data.push(0);
+    }
+    if size == 0 {
+        return data.len() as isize;
+    }
+    if size < data.len() {
+        return -1;
+    }
+    if !copy_to_user(list, &data) {
+        return -1;
+    }
+    data.len() as isize
+}
+
+pub fn sys_removexattr(path: *const u8, name: *const u8) -> isize {
+    let (path, name) = match user_path_name(path, name) {
+        Some(pair) => pair,
+        None => return -1,
+    };
+    match lookup(path.as_str()) {
+        Some(inode) if inode.remove_xattr(name.as_str()) => 0,
+        _ => -1,
+    }
+}
+
+/// 读取目录项 文件名以\0分隔 一次读出全部
+pub fn sys_getdents(fd: usize, buf: *mut u8, len: usize) -> isize {
+    let process = current_process();
+    let inner = process.inner_exclusive_access();
+    let names = match inner.fd_table.get(fd) {
+        Some(Some(file)) => file.ls(),
+        _ => return -1,
+    };
+    drop(inner);
+    let names = match names {
+        Some(names) => names,
+        None => return -1,
+    };
+    let mut data = Vec::new();
+    for name in names {
+        data.extend_from_slice(name.as_bytes());
+        data.push(0);
+    }
+    if len < data.len() {
+        return -1;
+    }
+    if !copy_to_user(buf, &data) {
+        return -1;
+    }
+    data.len() as isize
+}
+
+pub fn sys_chdir(path: *const u8) -> isize {
+    let path = match user_path(&mut current_process().inner_exclusive_access(), path) {
+        Some(path) => path,
+        None => return -1,
+    };
+    match lookup(path.as_str()) {
+        Some(inode) if inode.itype() == InodeType::Dir => {
+            current_process().inner_exclusive_access().cwd = path;
+            0
+        }
+        _ => -1,
+    }
+}
+
+pub fn sys_fchdir(fd: usize) -> isize {
+    let process = current_process();
+    let inner = process.inner_exclusive_access();
+    let path = match inner.fd_table.get(fd) {
+        Some(Some(file)) => file.path(),
+        _ => return -1,
+    };
+    drop(inner);
+    match path {
+        Some(path) if lookup(path.as_str()).map(|inode| inode.itype()) == Some(InodeType::Dir) => {
+            process.inner_exclusive_access().cwd = path;
+            0
+        }
+        _ => -1,
+    }
+}
+
+/// 返回写入的字节数 包括结尾的\0
+pub fn sys_getcwd(buf: *mut u8, len: usize) -> isize {
+    let mut cwd = current_process().inner_exclusive_access().cwd.clone();
+    cwd.push('\0');
+    if len < cwd.len() {
+        return -1;
+    }
+    if !copy_to_user(buf, cwd.as_bytes()) {
+        return -1;
+    }
+    cwd.len() as isize
+}
+
+pub fn sys_mkdir(path: *const u8) -> isize {
+    let path = match user_path(&mut current_process().inner_exclusive_access(), path) {
+        Some(path) => path,
+        None => return -1,
+    };
+    if mkdir(path.as_str()) {
+        0
+    } else {
+        -1
+    }
+}
+
+/// 创建普通文件或命名管道 设备号用不到
+pub fn sys_mknod(path: *const u8, mode: u32, _dev: usize) -> isize {
+    let path = match user_path(&mut current_process().inner_exclusive_access(), path) {
+        Some(path) => path,
+        None => return -1,
+    };
+    let itype = match StatMode::from_bits_truncate(mode) & StatMode::TYPE {
+        StatMode::FIFO => InodeType::Fifo,
+        StatMode::FILE | StatMode::NULL => InodeType::File,
+        _ => return -1,
+    };
+    if mknod(path.as_str(), itype) {
+        0
+    } else {
+        -1
+    }
+}
+
+pub fn sys_mount(source: *const u8, target: *const u8, fstype: *const u8) -> isize {
+    let process = current_process();
+    let mut inner = process.inner_exclusive_access();
+    let (source, target, fstype) = match (
+        translated_str(&mut inner.memory_set, source),
+        user_path(&mut inner, target),
+        translated_str(&mut inner.memory_set, fstype),
+    ) {
+        (Some(source), Some(target), Some(fstype)) => (source, target, fstype),
+        _ => return -1,
+    };
+    drop(inner);
+    if mount(source.as_str(), target.as_str(), fstype.as_str()) {
+        0
+    } else {
+        -1
+    }
+}
+
+pub fn sys_umount(target: *const u8) -> isize {
+    let target = match user_path(&mut current_process().inner_exclusive_access(), target) {
+        Some(target) => target,
+        None => return -1,
+    };
+    if umount(target.as_str()) {
+        0
+    } else {
+        -1
+    }
+}
+
 pub fn sys_linkat(
     old_dirfd: usize,
     old_path: &str,
@@ -157,66 +649,376 @@ pub fn sys_mail_write(pid: usize, buffer: *const u8, len: usize) -> isize {
     0
 }
 
-pub fn sys_mmap(start: usize, len: usize, prot: usize) -> isize {
-    if start % PAGE_SIZE != 0 {
-        return -1;
-    }
-    if prot & !0x7 != 0 {
-        return -1;
+const PROT_READ: usize = 0x1;
+const PROT_WRITE: usize = 0x2;
+const PROT_EXEC: usize = 0x4;
+
+const MAP_SHARED: usize = 0x01;
+const MAP_PRIVATE: usize = 0x02;
+const MAP_FIXED: usize = 0x10;
+const MAP_ANONYMOUS: usize = 0x20;
+
+/// 把PROT_*转换成用户逻辑段的权限 PROT_NONE的页任何访问都会出错
+fn prot_to_permission(prot: usize) -> Option<MapPermission> {
+    if prot & !(PROT_READ | PROT_WRITE | PROT_EXEC) != 0 {
+        return None;
     }
-    if prot & 0x7 == 0 {
-        return -1;
+    let mut permission = MapPermission::U;
+    if prot & PROT_READ != 0 {
+        permission |= MapPermission::R;
     }
-    let mut mp = MapPermission::empty();
-    if prot & 0x1 != 0 {
-        mp |= MapPermission::R;
+    if prot & PROT_WRITE != 0 {
+        permission |= MapPermission::W;
     }
-    if prot & 0x2 != 0 {
-        mp |= MapPermission::W;
+    if prot & PROT_EXEC != 0 {
+        permission |= MapPermission::X;
     }
-    if prot & 0x4 != 0 {
-        mp |= MapPermission::X;
+    Some(permission)
+}
+
+/// 映射文件或者匿名内存 返回映射的起始地址
+///
+/// 没有MAP_FIXED时start只是建议的地址 和已有映射重叠时由内核选择
+pub fn sys_mmap(
+    start: usize,
+    len: usize,
+    prot: usize,
+    flags: usize,
+    fd: usize,
+    offset: usize,
+) -> isize {
+    if len == 0 || start % PAGE_SIZE != 0 {
+        return -1;
     }
-    let ma = MapArea::new(start.into(), (start + len).into(), MapType::Framed, mp);
-    let pt = PageTable::from_token(current_user_token());
-    for vpn in ma.vpn_range {
-        if pt.translate(vpn).is_none() {
-            return -1;
+    let permission = match prot_to_permission(prot) {
+        Some(permission) => permission,
+        None => return -1,
+    };
+    let shared = match flags & (MAP_SHARED | MAP_PRIVATE) {
+        MAP_SHARED => true,
+        MAP_PRIVATE => false,
+        _ => return -1,
+    };
+    let fixed = flags & MAP_FIXED != 0;
+    let file = if flags & MAP_ANONYMOUS != 0 {
+        // 匿名的共享映射用一个没有标识符的共享内存段 fork之后父子进程共享
+        if shared {
+            let segment = SharedMemory::new((len + PAGE_SIZE - 1) / PAGE_SIZE);
+            return match current_process().mmap_shm(start, fixed, permission, segment) {
+                Some(start) => start as isize,
+                None => -1,
+            };
         }
-    }
-    let process = current_process();
-    process.mmap(start.into(), (start + len).into(), mp);
-    for vpn in ma.vpn_range {
-        if let Some(pte) = pt.translate(vpn) {
-            println!("{:?}: {:?}, {:?}", vpn, pte.ppn(), pte.flags());
+        None
+    } else {
+        match file_mapping(len, permission, shared, fd, offset) {
+            Some(file) => Some(file),
+            None => return -1,
         }
+    };
+    match current_process().mmap(start, len, fixed, permission, file) {
+        Some(start) => start as isize,
+        None => -1,
     }
-    0
 }
 
+/// 映射文件的[offset, offset + len) MAP_SHARED的修改写回文件 MAP_PRIVATE的修改只有自己可见
+fn file_mapping(
+    len: usize,
+    permission: MapPermission,
+    shared: bool,
+    fd: usize,
+    offset: usize,
+) -> Option<FileMapping> {
+    if offset % PAGE_SIZE != 0 {
+        return None;
+    }
+    let file = get_file(fd)?;
+    let (path, inode) = (file.path()?, file.inode()?);
+    // 私有映射的修改不写回 只需要文件可读
+    if !file.readable() || (shared && permission.contains(MapPermission::W) && !file.writable()) {
+        return None;
+    }
+    // 映射范围内整页都来自文件 文件末尾之后读到0
+    let file_len = (len + PAGE_SIZE - 1) / PAGE_SIZE * PAGE_SIZE;
+    Some(FileMapping::new(
+        &path,
+        inode,
+        offset,
+        file_len,
+        shared,
+        file.writable(),
+    ))
+}
+
+/// 拆除[start, start + len)中的映射 只拆掉一部分的逻辑段被切开
 pub fn sys_munmap(start: usize, len: usize) -> isize {
+    if len == 0 || start % PAGE_SIZE != 0 {
+        return -1;
+    }
+    if current_process().munmap(start.into(), (start + len).into()) {
+        0
+    } else {
+        -1
+    }
+}
+
+/// 修改[start, start + len)的访问权限 范围内必须都已经映射
+pub fn sys_mprotect(start: usize, len: usize, prot: usize) -> isize {
     if start % PAGE_SIZE != 0 {
         return -1;
     }
-    let mut ma = MapArea::new(
-        start.into(),
-        (start + len).into(),
-        MapType::Framed,
-        MapPermission::empty(),
+    let permission = match prot_to_permission(prot) {
+        Some(permission) => permission,
+        None => return -1,
+    };
+    if len == 0 {
+        return 0;
+    }
+    if current_process().mprotect(start.into(), (start + len).into(), permission) {
+        0
+    } else {
+        -1
+    }
+}
+
+pub fn sys_spawn(path: &str) -> isize {
+    0
+}
+
+const F_DUPFD: usize = 0;
+const F_GETFD: usize = 1;
+const F_SETFD: usize = 2;
+const F_GETFL: usize = 3;
+const F_SETFL: usize = 4;
+const F_GETLK: usize = 5;
+const F_SETLK: usize = 6;
+const F_SETLKW: usize = 7;
+const F_DUPFD_CLOEXEC: usize = 1030;
+const FD_CLOEXEC: usize = 1;
+
+const F_RDLCK: i16 = 0;
+const F_WRLCK: i16 = 1;
+const F_UNLCK: i16 = 2;
+const SEEK_SET: i16 = 0;
+
+/// 记录锁的参数 和Linux的struct flock相同
+#[repr(C)]
+pub struct Flock {
+    pub l_type: i16,
+    pub l_whence: i16,
+    pub l_start: isize,
+    /// 0表示到文件末尾 负数表示l_start之前的字节
+    pub l_len: isize,
+    pub l_pid: i32,
+}
+
+/// fcntl的F_GETLK F_SETLK和F_SETLKW 只支持从文件开头计算的偏移
+fn record_lock(file: Arc<dyn File + Send + Sync>, cmd: usize, flock: *mut Flock) -> isize {
+    let id = match file.inode_id() {
+        Some(id) => id,
+        None => return -1,
+    };
+    let process = current_process();
+    let (l_type, l_whence, l_start, l_len) =
+        match translated_ref(&mut process.inner_exclusive_access().memory_set, flock) {
+            Some(flock) => (flock.l_type, flock.l_whence, flock.l_start, flock.l_len),
+            None => return -1,
+        };
+    if l_whence != SEEK_SET {
+        return -1;
+    }
+    let (start, end) = match l_len {
+        0 => (l_start, isize::MAX),
+        len if len > 0 => (l_start, l_start.saturating_add(len)),
+        len => (l_start + len, l_start),
+    };
+    if start < 0 {
+        return -1;
+    }
+    let (start, end) = (
+        start as usize,
+        if end == isize::MAX {
+            usize::MAX
+        } else {
+            end as usize
+        },
     );
-    let mut pt = PageTable::from_token(current_user_token());
-    for vpn in ma.vpn_range {
-        if pt.translate(vpn).is_none() {
-            return -1;
+    let owner = LockOwner::Process(current_process().getpid());
+    let lock = FileLock {
+        owner,
+        start,
+        end,
+        exclusive: l_type == F_WRLCK,
+    };
+    match (cmd, l_type) {
+        (_, F_UNLCK) if cmd != F_GETLK => {
+            unlock(id, owner, start, end);
+            0
+        }
+        (F_GETLK, F_RDLCK | F_WRLCK) => {
+            let flock =
+                match translated_refmut(&mut process.inner_exclusive_access().memory_set, flock) {
+                    Some(flock) => flock,
+                    None => return -1,
+                };
+            match test_lock(id, &lock) {
+                Some(other) => {
+                    flock.l_type = if other.exclusive { F_WRLCK } else { F_RDLCK };
+                    flock.l_start = other.start as isize;
+                    flock.l_len = if other.end == usize::MAX {
+                        0
+                    } else {
+                        (other.end - other.start) as isize
+                    };
+                    flock.l_pid = match other.owner {
+                        LockOwner::Process(pid) => pid as i32,
+                        LockOwner::File(_) => -1,
+                    };
+                }
+                None => flock.l_type = F_UNLCK,
+            }
+            0
         }
+        // 读锁要求文件可读 写锁要求可写
+        (_, F_RDLCK) if !file.readable() => -1,
+        (_, F_WRLCK) if !file.writable() => -1,
+        (F_SETLK | F_SETLKW, F_RDLCK | F_WRLCK) => {
+            if set_lock(id, lock, cmd == F_SETLKW) {
+                0
+            } else {
+                -1
+            }
+        }
+        _ => -1,
+    }
+}
+
+const LOCK_SH: usize = 1;
+const LOCK_EX: usize = 2;
+const LOCK_NB: usize = 4;
+const LOCK_UN: usize = 8;
+
+/// 整个文件的锁 属于打开的文件而不是进程
+pub fn sys_flock(fd: usize, operation: usize) -> isize {
+    let file = match get_file(fd) {
+        Some(file) => file,
+        None => return -1,
+    };
+    let id = match file.inode_id() {
+        Some(id) => id,
+        None => return -1,
+    };
+    let owner = LockOwner::File(Arc::as_ptr(&file) as *const u8 as usize);
+    let wait = operation & LOCK_NB == 0;
+    let exclusive = match operation & !LOCK_NB {
+        LOCK_SH => false,
+        LOCK_EX => true,
+        LOCK_UN => {
+            unlock(id, owner, 0, usize::MAX);
+            return 0;
+        }
+        _ => return -1,
+    };
+    let lock = FileLock {
+        owner,
+        start: 0,
+        end: usize::MAX,
+        exclusive,
+    };
+    // 等待期间持有文件 文件描述符被关闭时得到的锁在返回后随文件一起释放
+    let locked = set_lock(id, lock, wait);
+    drop(file);
+    if locked {
+        0
+    } else {
+        -1
     }
+}
+
+/// 把old_fd复制到new_fd 原来打开的new_fd先关闭
+pub fn sys_dup3(old_fd: usize, new_fd: usize, flags: u32) -> isize {
+    let flags = match OpenFlags::from_bits(flags) {
+        Some(flags) if (flags - OpenFlags::CLOEXEC).is_empty() => flags,
+        _ => return -1,
+    };
     let process = current_process();
-    process.munmap(start.into(), (start + len).into());
-    0
+    let mut inner = process.inner_exclusive_access();
+    let file = match inner.fd_table.get(old_fd) {
+        Some(Some(file)) => file.clone(),
+        _ => return -1,
+    };
+    if old_fd == new_fd || new_fd >= FD_LIMIT {
+        return -1;
+    }
+    while inner.fd_table.len() <= new_fd {
+        inner.fd_table.push(None);
+    }
+    inner.close_fd(process.getpid(), new_fd);
+    inner.fd_table[new_fd] = Some(file);
+    if flags.contains(OpenFlags::CLOEXEC) {
+        inner.cloexec_fds.insert(new_fd);
+    }
+    new_fd as isize
 }
 
-pub fn sys_spawn(path: &str) -> isize {
-    0
+pub fn sys_fcntl(fd: usize, cmd: usize, arg: usize) -> isize {
+    let process = current_process();
+    let mut inner = process.inner_exclusive_access();
+    let file = match inner.fd_table.get(fd) {
+        Some(Some(file)) => file.clone(),
+        _ => return -1,
+    };
+    match cmd {
+        F_GETLK | F_SETLK | F_SETLKW => {
+            drop(inner);
+            record_lock(file, cmd, arg as *mut Flock)
+        }
+        F_DUPFD | F_DUPFD_CLOEXEC => {
+            let new_fd = match inner.alloc_fd_from(arg) {
+                Some(fd) => fd,
+                None => return -1,
+            };
+            inner.fd_table[new_fd] = Some(file);
+            if cmd == F_DUPFD_CLOEXEC {
+                inner.cloexec_fds.insert(new_fd);
+            }
+            new_fd as isize
+        }
+        F_GETFD => {
+            if inner.cloexec_fds.contains(&fd) {
+                FD_CLOEXEC as isize
+            } else {
+                0
+            }
+        }
+        F_SETFD => {
+            if arg & FD_CLOEXEC != 0 {
+                inner.cloexec_fds.insert(fd);
+            } else {
+                inner.cloexec_fds.remove(&fd);
+            }
+            0
+        }
+        F_GETFL => {
+            let mode = match (file.readable(), file.writable()) {
+                (true, true) => OpenFlags::RDWR,
+                (false, true) => OpenFlags::WRONLY,
+                _ => OpenFlags::RDONLY,
+            };
+            (mode | file.status()).bits() as isize
+        }
+        // 访问模式和创建标志被忽略 只修改状态标志
+        F_SETFL => {
+            let flags = OpenFlags::from_bits_truncate(arg as u32);
+            if file.set_status(flags & OpenFlags::STATUS) {
+                0
+            } else {
+                -1
+            }
+        }
+        _ => -1,
+    }
 }
 
 pub fn sys_dup(fd: usize) -> isize {
@@ -228,7 +1030,99 @@ pub fn sys_dup(fd: usize) -> isize {
     if inner.fd_table[fd].is_none() {
         return -1;
     }
-    let new_fd = inner.alloc_fd();
+    let new_fd = match inner.alloc_fd() {
+        Some(fd) => fd,
+        None => return -1,
+    };
     inner.fd_table[new_fd] = Some(Arc::clone(inner.fd_table[fd].as_ref().unwrap()));
     new_fd as isize
 }
+
+/// ppoll的参数 和Linux的struct pollfd相同
+#[repr(C)]
+pub struct PollFd {
+    pub fd: i32,
+    pub events: u16,
+    pub revents: u16,
+}
+
+#[repr(C)]
+pub struct TimeSpec {
+    pub sec: usize,
+    pub nsec: usize,
+}
+
+/// 控制台没有输入中断 等待控制台时每次睡眠的时间 之后重新检查
+const POLL_INTERVAL_MS: usize = 10;
+
+/// 等待多个文件描述符就绪 timeout为空时一直等待 返回就绪的个数
+pub fn sys_ppoll(fds: *mut PollFd, nfds: usize, timeout: *const TimeSpec) -> isize {
+    let process = current_process();
+    let deadline = if timeout.is_null() {
+        None
+    } else {
+        match translated_ref(&mut process.inner_exclusive_access().memory_set, timeout) {
+            Some(timeout) => Some(get_time_ms() + timeout.sec * 1000 + timeout.nsec / 1_000_000),
+            None => return -1,
+        }
+    };
+    let task = current_task().unwrap();
+    loop {
+        let mut ready = 0;
+        let mut files = Vec::new();
+        for i in 0..nfds {
+            let mut inner = process.inner_exclusive_access();
+            let poll_fd = match translated_refmut(&mut inner.memory_set, unsafe { fds.add(i) }) {
+                Some(poll_fd) => poll_fd,
+                None => return -1,
+            };
+            // 负数的文件描述符被忽略
+            if poll_fd.fd < 0 {
+                poll_fd.revents = 0;
+                continue;
+            }
+            let file = match inner.fd_table.get(poll_fd.fd as usize) {
+                Some(Some(file)) => Some(file.clone()),
+                _ => None,
+            };
+            drop(inner);
+            let revents = match file {
+                Some(file) => {
+                    let revents = file.poll(PollEvents::from_bits_truncate(poll_fd.events));
+                    files.push(file);
+                    revents
+                }
+                None => PollEvents::NVAL,
+            };
+            poll_fd.revents = revents.bits();
+            if !revents.is_empty() {
+                ready += 1;
+            }
+        }
+        if ready > 0 {
+            return ready;
+        }
+        let now = get_time_ms();
+        if matches!(deadline, Some(deadline) if deadline <= now) {
+            return 0;
+        }
+        // 加入各文件的等待队列 有文件不能唤醒时定时重新检查
+        let mut wakeable = true;
+        for file in files.iter() {
+            wakeable &= file.poll_register(&task);
+        }
+        let expire = match (deadline, wakeable) {
+            (deadline, true) => deadline,
+            (Some(deadline), false) => Some(deadline.min(now + POLL_INTERVAL_MS)),
+            (None, false) => Some(now + POLL_INTERVAL_MS),
+        };
+        if let Some(expire) = expire {
+            add_timer(expire, task.clone());
+        }
+        block_current_and_run_next();
+        for file in files.iter() {
+            file.poll_unregister(&task);
+        }
+        remove_timer(task.clone());
+    }
+}
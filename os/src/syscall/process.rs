@@ -1,5 +1,6 @@
 #![allow(unused)]
 
+use super::fs::user_path;
 use crate::fs::{open_file, OpenFlags};
 use crate::task::manager::*;
 use crate::{mm::*, task::*, timer::get_time_ms};
@@ -42,7 +43,10 @@ pub fn sys_getpid() -> isize {
 
 pub fn sys_fork() -> isize {
     let current_process = current_process();
-    let new_process = current_process.fork();
+    let new_process = match current_process.fork() {
+        Some(process) => process,
+        None => return -1,
+    };
     let new_pid = new_process.getpid();
     let new_process_inner = new_process.inner_exclusive_access();
     let task = new_process_inner.tasks[0].as_ref().unwrap();
@@ -52,25 +56,34 @@ pub fn sys_fork() -> isize {
 }
 
 pub fn sys_exec(path: *const u8, mut args: *const usize) -> isize {
-    let token = current_user_token();
-    let path = translated_str(token, path);
+    let process = current_process();
+    let mut inner = process.inner_exclusive_access();
+    let path = match user_path(&mut inner, path) {
+        Some(path) => path,
+        None => return -1,
+    };
     // 提取参数
     let mut args_vec: Vec<String> = Vec::new();
     loop {
-        let arg_str_ptr = *translated_ref(token, args);
+        let arg_str_ptr = match translated_ref(&mut inner.memory_set, args) {
+            Some(arg_str_ptr) => *arg_str_ptr,
+            None => return -1,
+        };
         if arg_str_ptr == 0 {
             break;
         }
-        args_vec.push(translated_str(token, arg_str_ptr as *const u8));
+        match translated_str(&mut inner.memory_set, arg_str_ptr as *const u8) {
+            Some(arg) => args_vec.push(arg),
+            None => return -1,
+        }
         unsafe {
             args = args.add(1);
         }
     }
+    drop(inner);
     if let Some(app_inode) = open_file(path.as_str(), OpenFlags::RDONLY) {
-        let all_data = app_inode.read_all();
-        let process = current_process();
         let argc = args_vec.len();
-        process.exec(all_data.as_slice(), args_vec);
+        process.exec(&app_inode, args_vec);
         argc as isize
     } else {
         -1
@@ -92,17 +105,30 @@ pub fn sys_waitpid(pid: isize, xstatus: *mut i32) -> isize {
         p.inner_exclusive_access().is_zombie && (pid == -1 || pid as usize == p.getpid())
     });
     if let Some((idx, _)) = pair {
+        // 地址无效时不回收子进程
+        let xstatus = match translated_refmut(&mut inner.memory_set, xstatus) {
+            Some(xstatus) => xstatus,
+            None => return -1,
+        };
         let child = inner.children.remove(idx);
         assert_eq!(Arc::strong_count(&child), 1);
         let fonud_pid = child.getpid();
-        let exit_code = child.inner_exclusive_access().exit_code;
-        *translated_refmut(inner.memory_set.token(), xstatus) = exit_code;
+        *xstatus = child.inner_exclusive_access().exit_code;
         fonud_pid as isize
     } else {
         -2
     }
 }
 
+/// 设置程序断点 brk为0时只查询 返回新的断点 失败时返回原来的断点
+pub fn sys_brk(brk: usize) -> isize {
+    let process = current_process();
+    if brk == 0 {
+        return process.inner_exclusive_access().memory_set.brk() as isize;
+    }
+    process.brk(brk) as isize
+}
+
 pub fn sys_set_priority(prio: isize) -> isize {
     0
 }
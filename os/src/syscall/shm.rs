@@ -0,0 +1,83 @@
+use crate::config::PAGE_SIZE;
+use crate::mm::shm::{shm_create, shm_find, shm_remove, shm_segment};
+use crate::mm::{MapPermission, VirtAddr};
+use crate::task::current_process;
+
+/// 总是新建段
+const IPC_PRIVATE: usize = 0;
+const IPC_CREAT: usize = 0o1000;
+const IPC_EXCL: usize = 0o2000;
+const IPC_RMID: usize = 0;
+const SHM_RDONLY: usize = 0o10000;
+
+/// 取得键为key的共享内存段的标识符 不存在并且有IPC_CREAT时新建size字节的段
+pub fn sys_shmget(key: usize, size: usize, flags: usize) -> isize {
+    if key != IPC_PRIVATE {
+        if let Some((id, segment)) = shm_find(key) {
+            if flags & IPC_CREAT != 0 && flags & IPC_EXCL != 0 {
+                return -1;
+            }
+            if size > segment.pages() * PAGE_SIZE {
+                return -1;
+            }
+            return id as isize;
+        }
+        if flags & IPC_CREAT == 0 {
+            return -1;
+        }
+    }
+    if size == 0 {
+        return -1;
+    }
+    shm_create(key, (size + PAGE_SIZE - 1) / PAGE_SIZE) as isize
+}
+
+/// 把段映射到当前进程 addr为0时由内核选择地址 返回映射的地址
+pub fn sys_shmat(id: usize, addr: usize, flags: usize) -> isize {
+    if addr % PAGE_SIZE != 0 {
+        return -1;
+    }
+    let segment = match shm_segment(id) {
+        Some(segment) => segment,
+        None => return -1,
+    };
+    let mut permission = MapPermission::R | MapPermission::U;
+    if flags & SHM_RDONLY == 0 {
+        permission |= MapPermission::W;
+    }
+    let process = current_process();
+    let end_va = VirtAddr::from(addr + segment.pages() * PAGE_SIZE);
+    // 指定的地址已经被占用时失败 不另选地址
+    if addr != 0
+        && process
+            .inner_exclusive_access()
+            .memory_set
+            .overlaps(VirtAddr::from(addr).floor(), end_va.ceil())
+    {
+        return -1;
+    }
+    match process.mmap_shm(addr, false, permission, segment) {
+        Some(start) => start as isize,
+        None => -1,
+    }
+}
+
+/// 拆除映射在addr处的段
+pub fn sys_shmdt(addr: usize) -> isize {
+    if addr % PAGE_SIZE != 0 {
+        return -1;
+    }
+    if current_process().munmap_shm(addr.into()) {
+        0
+    } else {
+        -1
+    }
+}
+
+/// 只支持IPC_RMID 删除后不能再映射 已有的映射拆除时释放内存
+pub fn sys_shmctl(id: usize, cmd: usize) -> isize {
+    match cmd {
+        IPC_RMID if shm_remove(id) => 0,
+        _ => -1,
+    }
+}
@@ -3,6 +3,7 @@ use crate::sync::UPSafeCell;
 use alloc::{
     collections::{BTreeMap, VecDeque},
     sync::Arc,
+    vec::Vec,
 };
 use lazy_static::lazy_static;
 
@@ -26,6 +27,10 @@ impl TaskManager {
         self.ready_queue.pop_front()
     }
 
+    pub fn ready_count(&self) -> usize {
+        self.ready_queue.len()
+    }
+
     pub fn remove(&mut self, task: Arc<TaskControlBlock>) {
         if let Some((id, _)) = self
             .ready_queue
@@ -62,6 +67,16 @@ pub fn pid2process(pid: usize) -> Option<Arc<ProcessControlBlock>> {
     map.get(&pid).map(Arc::clone)
 }
 
+/// 所有进程的pid
+pub fn pids() -> Vec<usize> {
+    PID2PCB.exclusive_access().keys().cloned().collect()
+}
+
+/// 就绪队列中的线程数
+pub fn ready_task_count() -> usize {
+    TASK_MANAGER.exclusive_access().ready_count()
+}
+
 pub fn insert_into_pid2process(pid: usize, process: Arc<ProcessControlBlock>) {
     PID2PCB.exclusive_access().insert(pid, process);
 }
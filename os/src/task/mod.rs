@@ -7,12 +7,13 @@ mod signal;
 mod switch;
 mod task;
 
-use self::{context::TaskContext, id::TaskUserRes, manager::*, process::ProcessControlBlock};
-use crate::fs::{open_file, OpenFlags};
+use self::{context::TaskContext, id::TaskUserRes, manager::*};
+use crate::fs::{open_file, release_locks, LockOwner, OpenFlags};
 use crate::{board::*, timer::remove_timer};
-use alloc::{sync::Arc, vec::Vec};
+use alloc::{string::String, sync::Arc, vec, vec::Vec};
 use lazy_static::lazy_static;
 
+pub use process::{ProcessControlBlock, ProcessControlBlockInner};
 pub use processor::*;
 pub use signal::*;
 pub use task::*;
@@ -21,8 +22,9 @@ lazy_static! {
     /// 初始进程
     pub static ref INITPROC: Arc<ProcessControlBlock> = {
         let inode = open_file("initproc", OpenFlags::RDONLY).unwrap();
-        let v = inode.read_all();
-        ProcessControlBlock::new(v.as_slice())
+        let process = ProcessControlBlock::new(&inode);
+        process.inner_exclusive_access().cmdline = vec![String::from("initproc")];
+        process
     };
 }
 
@@ -60,6 +62,10 @@ pub fn exit_current_and_run_next(exit_code: i32) {
         let pid = process.getpid();
         if pid == IDLE_PID {
             kernel!("Idle process exit with exit_code {} ...", exit_code);
+            // 关机前写回所有文件系统
+            if !crate::fs::sync_all() {
+                warn!("block device can not flush its write cache");
+            }
             if exit_code != 0 {
                 crate::board::QEMU_EXIT_HANDLE.exit_failure();
             } else {
@@ -67,6 +73,8 @@ pub fn exit_current_and_run_next(exit_code: i32) {
             }
         }
         remove_from_pid2process(pid);
+        // 释放进程持有的记录锁
+        release_locks(LockOwner::Process(pid));
         let mut process_inner = process.inner_exclusive_access();
         process_inner.is_zombie = true;
         process_inner.exit_code = exit_code;
@@ -109,6 +117,17 @@ pub fn block_current_and_run_next() {
     schedule(task_cx_ptr);
 }
 
+/// 唤醒阻塞的线程 同时在多个等待队列中时只加入就绪队列一次
+pub fn wakeup_task(task: Arc<TaskControlBlock>) {
+    let mut task_inner = task.inner_exclusive_access();
+    if task_inner.task_status != TaskStatus::Blocking {
+        return;
+    }
+    task_inner.task_status = TaskStatus::Ready;
+    drop(task_inner);
+    add_task(task);
+}
+
 pub fn current_add_signal(signal: SignalFlags) {
     let process = current_process();
     let mut process_inner = process.inner_exclusive_access();
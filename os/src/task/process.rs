@@ -5,11 +5,14 @@ use super::{
     SignalFlags,
 };
 use crate::{
+    config::FD_LIMIT,
     fs::*,
+    mm::shm::SharedMemory,
     mm::*,
     sync::{Condvar, Mutex, Semaphore, UPSafeCell},
     trap::{context::TrapContext, trap_handler},
 };
+use alloc::collections::BTreeSet;
 use alloc::vec;
 use alloc::vec::Vec;
 use alloc::{
@@ -31,8 +34,14 @@ pub struct ProcessControlBlockInner {
     pub parent: Option<Weak<ProcessControlBlock>>,
     pub children: Vec<Arc<ProcessControlBlock>>,
     pub exit_code: i32,
+    /// 命令行参数
+    pub cmdline: Vec<String>,
+    /// 当前工作目录 规范的绝对路径
+    pub cwd: String,
     /// 文件描述符表
     pub fd_table: Vec<Option<Arc<dyn File + Send + Sync>>>,
+    /// 设置了FD_CLOEXEC的文件描述符 exec时关闭
+    pub cloexec_fds: BTreeSet<usize>,
     pub signals: SignalFlags,
     pub tasks: Vec<Option<Arc<TaskControlBlock>>>,
     pub task_res_allocator: RecycleAllocator,
@@ -46,13 +55,45 @@ impl ProcessControlBlockInner {
         self.task_res_allocator.alloc()
     }
 
-    pub fn alloc_fd(&mut self) -> usize {
-        if let Some(fd) = (0..self.fd_table.len()).find(|fd| self.fd_table[*fd].is_none()) {
-            fd
-        } else {
+    /// 进程的当前目录 打开的文件或映射的文件中是否有满足used的路径
+    pub fn uses_path(&self, used: impl Fn(&str) -> bool) -> bool {
+        used(&self.cwd)
+            || self
+                .fd_table
+                .iter()
+                .flatten()
+                .filter_map(|file| file.path())
+                .any(|path| used(&path))
+            || self.memory_set.mapped_paths().iter().any(|path| used(path))
+    }
+
+    pub fn alloc_fd(&mut self) -> Option<usize> {
+        self.alloc_fd_from(0)
+    }
+
+    /// 分配不小于min的最小空闲文件描述符 超过FD_LIMIT时失败
+    pub fn alloc_fd_from(&mut self, min: usize) -> Option<usize> {
+        let fd = (min..FD_LIMIT).find(|fd| self.fd_table.get(*fd).map_or(true, Option::is_none))?;
+        while self.fd_table.len() <= fd {
             self.fd_table.push(None);
-            self.fd_table.len() - 1
         }
+        self.cloexec_fds.remove(&fd);
+        Some(fd)
+    }
+
+    /// 关闭进程pid的文件描述符 fd无效时返回false
+    ///
+    /// 关闭任何一个文件描述符都会释放进程在这个文件上的记录锁
+    pub fn close_fd(&mut self, pid: usize, fd: usize) -> bool {
+        self.cloexec_fds.remove(&fd);
+        let file = match self.fd_table.get_mut(fd).and_then(Option::take) {
+            Some(file) => file,
+            None => return false,
+        };
+        if let Some(id) = file.inode_id() {
+            unlock(id, LockOwner::Process(pid), 0, usize::MAX);
+        }
+        true
     }
 
     pub fn dealloc_tid(&mut self, tid: usize) {
@@ -73,12 +114,18 @@ impl ProcessControlBlock {
         self.inner.exclusive_access()
     }
 
+    /// 进程的锁正被持有时返回None
+    pub fn try_inner_exclusive_access(&self) -> Option<RefMut<'_, ProcessControlBlockInner>> {
+        self.inner.try_exclusive_access()
+    }
+
     pub fn getpid(&self) -> usize {
         self.pid.0
     }
 
-    pub fn new(elf_data: &[u8]) -> Arc<Self> {
-        let (memory_set, ustack_base, entry_point) = MemorySet::from_elf(elf_data);
+    pub fn new(elf: &OSInode) -> Arc<Self> {
+        let (memory_set, ustack_base, entry_point) =
+            MemorySet::from_elf(&elf.path().unwrap(), elf.inode().unwrap());
         let pid_handle = pid_alloc();
         let process = Arc::new(Self {
             pid: pid_handle,
@@ -89,6 +136,8 @@ impl ProcessControlBlock {
                     parent: None,
                     children: Vec::new(),
                     exit_code: 0,
+                    cmdline: Vec::new(),
+                    cwd: String::from("/"),
                     fd_table: vec![
                         // 0 -> stdin
                         Some(Arc::new(Stdin)),
@@ -97,6 +146,7 @@ impl ProcessControlBlock {
                         // 2 -> stderr
                         Some(Arc::new(Stdout)),
                     ],
+                    cloexec_fds: BTreeSet::new(),
                     signals: SignalFlags::empty(),
                     tasks: Vec::new(),
                     task_res_allocator: RecycleAllocator::new(),
@@ -132,13 +182,20 @@ impl ProcessControlBlock {
     }
 
     /// Only support processes with a single thread.
-    pub fn exec(self: &Arc<Self>, elf_data: &[u8], args: Vec<String>) {
+    pub fn exec(self: &Arc<Self>, elf: &OSInode, args: Vec<String>) {
         assert_eq!(self.inner_exclusive_access().thread_count(), 1);
         // memory_set with elf program headers/trampoline/trap context/user stack
-        let (memory_set, ustack_base, entry_point) = MemorySet::from_elf(elf_data);
-        let new_token = memory_set.token();
+        let (memory_set, ustack_base, entry_point) =
+            MemorySet::from_elf(&elf.path().unwrap(), elf.inode().unwrap());
         // substitute memory_set
         self.inner_exclusive_access().memory_set = memory_set;
+        self.inner_exclusive_access().cmdline = args.clone();
+        // 关闭设置了FD_CLOEXEC的文件
+        let mut inner = self.inner_exclusive_access();
+        for fd in core::mem::take(&mut inner.cloexec_fds) {
+            inner.close_fd(self.getpid(), fd);
+        }
+        drop(inner);
         // then we alloc user resource for main thread again
         // since memory_set has been changed
         let task = self.inner_exclusive_access().get_task(0);
@@ -150,12 +207,16 @@ impl ProcessControlBlock {
         let mut user_sp = task_inner.res.as_mut().unwrap().ustack_top();
         user_sp -= (args.len() + 1) * core::mem::size_of::<usize>();
         let argv_base = user_sp;
+        // 用户栈刚刚分配 地址都有效
+        let mut inner = self.inner_exclusive_access();
+        let memory_set = &mut inner.memory_set;
         let mut argv: Vec<_> = (0..=args.len())
             .map(|arg| {
                 translated_refmut(
-                    new_token,
+                    memory_set,
                     (argv_base + arg * core::mem::size_of::<usize>()) as *mut usize,
                 )
+                .unwrap()
             })
             .collect();
         *argv[args.len()] = 0;
@@ -164,11 +225,12 @@ impl ProcessControlBlock {
             *argv[i] = user_sp;
             let mut p = user_sp;
             for c in args[i].as_bytes() {
-                *translated_refmut(new_token, p as *mut u8) = *c;
+                *translated_refmut(memory_set, p as *mut u8).unwrap() = *c;
                 p += 1;
             }
-            *translated_refmut(new_token, p as *mut u8) = 0;
+            *translated_refmut(memory_set, p as *mut u8).unwrap() = 0;
         }
+        drop(inner);
         // make the user_sp aligned to 8B for k210 platform
         user_sp -= user_sp % core::mem::size_of::<usize>();
         // initialize trap_cx
@@ -185,11 +247,13 @@ impl ProcessControlBlock {
     }
 
     /// Only support processes with a single thread.
-    pub fn fork(self: &Arc<Self>) -> Arc<Self> {
+    ///
+    /// 分配不到页帧时返回None
+    pub fn fork(self: &Arc<Self>) -> Option<Arc<Self>> {
         let mut parent = self.inner_exclusive_access();
         assert_eq!(parent.thread_count(), 1);
         // clone parent's memory_set completely including trampoline/ustacks/trap_cxs
-        let memory_set = MemorySet::from_existed_user(&parent.memory_set);
+        let memory_set = MemorySet::from_existed_user(&mut parent.memory_set)?;
         // alloc a pid
         let pid = pid_alloc();
         // copy fd table
@@ -211,7 +275,10 @@ impl ProcessControlBlock {
                     parent: Some(Arc::downgrade(self)),
                     children: Vec::new(),
                     exit_code: 0,
+                    cmdline: parent.cmdline.clone(),
+                    cwd: parent.cwd.clone(),
                     fd_table: new_fd_table,
+                    cloexec_fds: parent.cloexec_fds.clone(),
                     signals: SignalFlags::empty(),
                     tasks: Vec::new(),
                     task_res_allocator: RecycleAllocator::new(),
@@ -249,20 +316,63 @@ impl ProcessControlBlock {
         insert_into_pid2process(child.getpid(), Arc::clone(&child));
         // add this thread to scheduler
         add_task(task);
-        child
+        Some(child)
+    }
+
+    /// 映射[start, start + len) 返回映射的起始地址 失败时返回None
+    pub fn mmap(
+        &self,
+        start: usize,
+        len: usize,
+        fixed: bool,
+        permission: MapPermission,
+        file: Option<FileMapping>,
+    ) -> Option<usize> {
+        self.inner_exclusive_access()
+            .memory_set
+            .mmap(start, len, fixed, permission, file)
     }
 
-    pub fn mmap(&self, start_va: VirtAddr, end_va: VirtAddr, permission: MapPermission) {
-        self.inner
-            .exclusive_access()
+    /// 映射整个共享内存段 返回映射的起始地址 失败时返回None
+    pub fn mmap_shm(
+        &self,
+        start: usize,
+        fixed: bool,
+        permission: MapPermission,
+        segment: Arc<SharedMemory>,
+    ) -> Option<usize> {
+        self.inner_exclusive_access()
             .memory_set
-            .insert_framed_area(start_va, end_va, permission);
+            .mmap_shm(start, fixed, permission, segment)
     }
 
-    pub fn munmap(&self, start_va: VirtAddr, end_va: VirtAddr) {
-        self.inner
-            .exclusive_access()
+    pub fn munmap_shm(&self, start_va: VirtAddr) -> bool {
+        self.inner_exclusive_access()
             .memory_set
-            .remove_framed_area(start_va, end_va);
+            .munmap_shm(start_va.floor())
+    }
+
+    /// 调整程序断点 返回调整后的断点
+    pub fn brk(&self, brk: usize) -> usize {
+        self.inner_exclusive_access().memory_set.set_brk(brk)
+    }
+
+    pub fn munmap(&self, start_va: VirtAddr, end_va: VirtAddr) -> bool {
+        self.inner_exclusive_access()
+            .memory_set
+            .munmap(start_va.floor(), end_va.ceil())
+    }
+
+    pub fn mprotect(
+        &self,
+        start_va: VirtAddr,
+        end_va: VirtAddr,
+        permission: MapPermission,
+    ) -> bool {
+        self.inner_exclusive_access().memory_set.mprotect(
+            start_va.floor(),
+            end_va.ceil(),
+            permission,
+        )
     }
 }
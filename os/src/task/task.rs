@@ -5,6 +5,7 @@ use crate::mm::*;
 use crate::sync::UPSafeCell;
 use crate::trap::context::TrapContext;
 use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
 use core::cell::RefMut;
 
 #[derive(Clone, Copy, PartialEq, Debug)]
@@ -20,6 +21,10 @@ pub struct TaskControlBlock {
     /// 内核栈
     pub kstack: KernelStack,
     inner: UPSafeCell<TaskControlBlockInner>,
+    /// 系统调用中内核正在访问的用户页 返回用户态之前不换出
+    ///
+    /// 不放在inner中 exec持有inner时还要访问用户栈
+    pinned_frames: UPSafeCell<Vec<Arc<FrameTracker>>>,
 }
 
 pub struct TaskControlBlockInner {
@@ -49,6 +54,19 @@ impl TaskControlBlock {
         self.inner.exclusive_access()
     }
 
+    /// 固定内核将要访问的用户页
+    pub fn pin_frame(&self, frame: Arc<FrameTracker>) {
+        let mut pinned = self.pinned_frames.exclusive_access();
+        if !pinned.iter().any(|pinned| Arc::ptr_eq(pinned, &frame)) {
+            pinned.push(frame);
+        }
+    }
+
+    /// 系统调用返回时解除固定
+    pub fn unpin_frames(&self) {
+        self.pinned_frames.exclusive_access().clear();
+    }
+
     pub fn get_user_token(&self) -> usize {
         let process = self.process.upgrade().unwrap();
         let inner = process.inner_exclusive_access();
@@ -76,6 +94,7 @@ impl TaskControlBlock {
                     exit_code: None,
                 })
             },
+            pinned_frames: unsafe { UPSafeCell::new(Vec::new()) },
         }
     }
 }
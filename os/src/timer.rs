@@ -1,7 +1,6 @@
 use crate::config::CLOCK_FREQ;
 use crate::sbi::set_timer;
 use crate::sync::UPSafeCell;
-use crate::task::manager::add_task;
 use crate::task::*;
 use alloc::collections::BinaryHeap;
 use alloc::sync::Arc;
@@ -78,7 +77,7 @@ pub fn check_timer() {
     let mut timers = TIMERS.exclusive_access();
     while let Some(timer) = timers.peek() {
         if timer.expire_ms <= current_ms {
-            add_task(Arc::clone(&timer.task));
+            wakeup_task(Arc::clone(&timer.task));
             timers.pop();
         } else {
             break;
@@ -1,4 +1,5 @@
 use crate::config::TRAMPOLINE;
+use crate::mm::{swap, MapPermission};
 use crate::task::*;
 use crate::timer::check_timer;
 use crate::{syscall::syscall, timer::set_next_trigger};
@@ -40,20 +41,44 @@ pub fn enable_timer_interrupt() {
 #[no_mangle]
 pub fn trap_handler(_cx: &mut TrapContext) -> ! {
     set_kernel_trap_entry();
+    // 分配页帧时有进程的地址空间正在使用 没能换出足够的页 这时不持有任何锁 补上
+    swap::balance_pending();
     let scause = scause::read();
     let stval = stval::read();
     match scause.cause() {
         Trap::Exception(Exception::UserEnvCall) => {
             let mut cx = current_trap_cx();
             cx.sepc += 4;
-            let result = syscall(cx.x[17], [cx.x[10], cx.x[11], cx.x[12]]);
+            let result = syscall(
+                cx.x[17],
+                [cx.x[10], cx.x[11], cx.x[12], cx.x[13], cx.x[14], cx.x[15]],
+            );
             cx = current_trap_cx();
             cx.x[10] = result as usize;
+            current_task().unwrap().unpin_frames();
+        }
+        Trap::Exception(Exception::StorePageFault)
+        | Trap::Exception(Exception::LoadPageFault)
+        | Trap::Exception(Exception::InstructionPageFault) => {
+            let access = match scause.cause() {
+                Trap::Exception(Exception::StorePageFault) => MapPermission::W,
+                Trap::Exception(Exception::LoadPageFault) => MapPermission::R,
+                _ => MapPermission::X,
+            };
+            // 用户页在第一次访问时才分配或调入 不在逻辑段中或者权限不符时是非法访问
+            let handle_page_fault = || {
+                current_process()
+                    .inner_exclusive_access()
+                    .memory_set
+                    .handle_page_fault(stval.into(), access)
+            };
+            // 分配页帧时换出跳过了这个进程 不持有锁时换出之后再试一次
+            let handled = handle_page_fault() || (swap::balance_pending() && handle_page_fault());
+            if !handled {
+                current_add_signal(SignalFlags::SIGSEGV);
+            }
         }
-        Trap::Exception(Exception::StoreFault)
-        | Trap::Exception(Exception::StorePageFault)
-        | Trap::Exception(Exception::LoadFault)
-        | Trap::Exception(Exception::LoadPageFault) => {
+        Trap::Exception(Exception::StoreFault) | Trap::Exception(Exception::LoadFault) => {
             current_add_signal(SignalFlags::SIGSEGV);
         }
         Trap::Exception(Exception::IllegalInstruction) => {
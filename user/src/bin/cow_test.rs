@@ -0,0 +1,49 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, exit, fork, pipe, read, waitpid, write};
+
+const PAGES: usize = 16;
+const PAGE_SIZE: usize = 0x1000;
+
+static mut DATA: [u8; PAGES * PAGE_SIZE] = [0; PAGES * PAGE_SIZE];
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let data = unsafe { &mut DATA };
+    for (i, byte) in data.iter_mut().enumerate() {
+        *byte = (i / PAGE_SIZE) as u8;
+    }
+    let mut pipe_fd = [0usize; 2];
+    pipe(&mut pipe_fd);
+    let pid = fork();
+    if pid == 0 {
+        close(pipe_fd[1]);
+        // 子进程的修改不影响父进程
+        for page in 0..PAGES / 2 {
+            data[page * PAGE_SIZE] = 0xff;
+        }
+        // 内核写入共享的页时也要先复制
+        let page = PAGES - 1;
+        assert_eq!(read(pipe_fd[0], &mut data[page * PAGE_SIZE..][..4]), 4);
+        assert_eq!(&data[page * PAGE_SIZE..][..4], b"cow!");
+        for page in PAGES / 2..PAGES - 1 {
+            assert_eq!(data[page * PAGE_SIZE], page as u8);
+        }
+        exit(0);
+    }
+    close(pipe_fd[0]);
+    assert_eq!(write(pipe_fd[1], b"cow!"), 4);
+    close(pipe_fd[1]);
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+    for (i, byte) in data.iter().enumerate() {
+        assert_eq!(*byte, (i / PAGE_SIZE) as u8);
+    }
+    println!("cow_test passed!");
+    0
+}
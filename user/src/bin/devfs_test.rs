@@ -0,0 +1,49 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, open, read, write, OpenFlags};
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let mut buffer = [0xffu8; 64];
+
+    let fd = open("/dev/null\0", OpenFlags::RDWR);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    assert_eq!(write(fd, &buffer), buffer.len() as isize);
+    assert_eq!(read(fd, &mut buffer), 0);
+    close(fd);
+
+    let fd = open("/dev/zero\0", OpenFlags::RDONLY);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    assert_eq!(read(fd, &mut buffer), buffer.len() as isize);
+    assert!(buffer.iter().all(|b| *b == 0));
+    close(fd);
+
+    let fd = open("/dev/random\0", OpenFlags::RDONLY);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    assert_eq!(read(fd, &mut buffer), buffer.len() as isize);
+    assert!(buffer.iter().any(|b| *b != 0));
+    close(fd);
+
+    // 根文件系统挂载在/dev/vda上 只能只读打开 第一块是超级块
+    assert!(open("/dev/vda\0", OpenFlags::RDWR) < 0);
+    let fd = open("/dev/vda\0", OpenFlags::RDONLY);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    assert_eq!(read(fd, &mut buffer), buffer.len() as isize);
+    assert_eq!(buffer[..4], 0x3b800001u32.to_le_bytes());
+    close(fd);
+
+    // 关闭标准输出后重新打开控制台
+    close(1);
+    let fd = open("/dev/tty\0", OpenFlags::WRONLY);
+    assert_eq!(fd, 1);
+    println!("devfs_test passed!");
+    0
+}
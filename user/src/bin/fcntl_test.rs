@@ -0,0 +1,45 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close, dup2, dup3, fcntl, open, OpenFlags, FD_CLOEXEC, F_DUPFD, F_DUPFD_CLOEXEC, F_GETFD,
+    F_GETFL, F_SETFD,
+};
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fd = open("/dev/null\0", OpenFlags::RDWR | OpenFlags::CLOEXEC);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    assert_eq!(fcntl(fd, F_GETFD, 0), FD_CLOEXEC as isize);
+    assert_eq!(fcntl(fd, F_GETFL, 0), OpenFlags::RDWR.bits() as isize);
+    assert_eq!(fcntl(fd, F_SETFD, 0), 0);
+    assert_eq!(fcntl(fd, F_GETFD, 0), 0);
+
+    // 指定新的文件描述符 新的描述符不继承CLOEXEC
+    assert_eq!(dup3(fd, 8, OpenFlags::empty()), 8);
+    assert_eq!(fcntl(8, F_GETFD, 0), 0);
+    assert_eq!(dup3(fd, 8, OpenFlags::CLOEXEC), 8);
+    assert_eq!(fcntl(8, F_GETFD, 0), FD_CLOEXEC as isize);
+    assert_eq!(dup3(fd, fd, OpenFlags::empty()), -1);
+    assert_eq!(dup2(fd, fd), fd as isize);
+
+    // 从arg开始找最小的空闲描述符
+    assert_eq!(fcntl(fd, F_DUPFD, 8), 9);
+    assert_eq!(fcntl(fd, F_DUPFD_CLOEXEC, 8), 10);
+    assert_eq!(fcntl(10, F_GETFD, 0), FD_CLOEXEC as isize);
+
+    // 超过文件描述符上限时失败 不会耗尽内核堆
+    assert_eq!(dup3(fd, 1 << 40, OpenFlags::empty()), -1);
+    assert_eq!(fcntl(fd, F_DUPFD, 1 << 40), -1);
+
+    for fd in [fd, 8, 9, 10] {
+        assert_eq!(close(fd), 0);
+    }
+    assert_eq!(fcntl(8, F_GETFD, 0), -1);
+    println!("fcntl_test passed!");
+    0
+}
@@ -0,0 +1,49 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, exit, fork, mkfifo, open, read, sleep, waitpid, write, OpenFlags};
+
+static MSG: &[u8] = b"Hello through a fifo!";
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let path = "/tmp/fifo_test\0";
+    assert_eq!(mkfifo(path), 0);
+    assert_eq!(mkfifo(path), -1);
+
+    // 没有读者时非阻塞的只写打开失败
+    assert_eq!(open(path, OpenFlags::WRONLY | OpenFlags::NONBLOCK), -1);
+
+    let pid = fork();
+    if pid == 0 {
+        // 子进程单独打开路径 阻塞到父进程以读方式打开
+        let fd = open(path, OpenFlags::WRONLY);
+        assert!(fd > 0);
+        assert_eq!(write(fd as usize, MSG), MSG.len() as isize);
+        close(fd as usize);
+        exit(0);
+    }
+    sleep(20);
+    let fd = open(path, OpenFlags::RDONLY);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    let mut buffer = [0u8; 64];
+    let mut len = 0;
+    loop {
+        let size = read(fd, &mut buffer[len..]);
+        if size == 0 {
+            break;
+        }
+        len += size as usize;
+    }
+    assert_eq!(&buffer[..len], MSG);
+    close(fd);
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+    println!("fifo_test passed!");
+    0
+}
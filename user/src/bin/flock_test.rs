@@ -0,0 +1,118 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close, dup2, exit, fcntl_lock, flock, fork, getpid, open, read, sleep, thread_create, waitpid,
+    waittid, write, Flock, OpenFlags, F_GETLK, F_RDLCK, F_SETLK, F_SETLKW, F_UNLCK, F_WRLCK,
+    LOCK_EX, LOCK_NB, LOCK_SH, LOCK_UN,
+};
+
+const PATH: &str = "/tmp/flock_test\0";
+
+fn open_rw() -> usize {
+    let fd = open(PATH, OpenFlags::CREATE | OpenFlags::RDWR);
+    assert!(fd > 0);
+    fd as usize
+}
+
+fn wait_child(pid: isize) {
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+}
+
+/// 在线程中等待flock 参数是文件描述符
+fn flock_waiter(fd: usize) -> ! {
+    assert_eq!(flock(fd, LOCK_EX), 0);
+    exit(0)
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    // flock: 子进程等待父进程释放整个文件的写锁
+    let fd = open_rw();
+    assert_eq!(flock(fd, LOCK_EX), 0);
+    let pid = fork();
+    if pid == 0 {
+        let fd = open_rw();
+        assert_eq!(flock(fd, LOCK_SH | LOCK_NB), -1);
+        assert_eq!(flock(fd, LOCK_EX), 0);
+        let mut buf = [0u8; 6];
+        assert_eq!(read(fd, &mut buf), 6);
+        assert_eq!(&buf, b"parent");
+        exit(0);
+    }
+    sleep(30);
+    assert_eq!(write(fd, b"parent"), 6);
+    assert_eq!(flock(fd, LOCK_UN), 0);
+    wait_child(pid);
+    close(fd);
+
+    // 等待期间文件描述符被关闭 得到的锁随文件一起释放
+    let fd = open_rw();
+    assert_eq!(flock(fd, LOCK_EX), 0);
+    let pid = fork();
+    if pid == 0 {
+        let fd = open_rw();
+        let tid = thread_create(flock_waiter as usize, fd);
+        sleep(10);
+        close(fd);
+        assert_eq!(waittid(tid as usize), 0);
+        exit(0);
+    }
+    sleep(30);
+    assert_eq!(flock(fd, LOCK_UN), 0);
+    wait_child(pid);
+    close(fd);
+    let fd = open_rw();
+    assert_eq!(flock(fd, LOCK_EX | LOCK_NB), 0);
+    close(fd);
+
+    // 记录锁: 只有重叠的范围冲突 进程退出时释放
+    let fd = open_rw();
+    let mut lock = Flock::new(F_WRLCK, 0, 10);
+    assert_eq!(fcntl_lock(fd, F_SETLK, &mut lock), 0);
+    let parent = getpid();
+    let pid = fork();
+    if pid == 0 {
+        let fd = open_rw();
+        let mut lock = Flock::new(F_RDLCK, 5, 10);
+        assert_eq!(fcntl_lock(fd, F_GETLK, &mut lock), 0);
+        assert_eq!(lock.l_type, F_WRLCK);
+        assert_eq!((lock.l_start, lock.l_len), (0, 10));
+        assert_eq!(lock.l_pid as isize, parent);
+        let mut lock = Flock::new(F_WRLCK, 10, 0);
+        assert_eq!(fcntl_lock(fd, F_SETLK, &mut lock), 0);
+        let mut lock = Flock::new(F_RDLCK, 0, 5);
+        assert_eq!(fcntl_lock(fd, F_SETLK, &mut lock), -1);
+        assert_eq!(fcntl_lock(fd, F_SETLKW, &mut lock), 0);
+        // 退出时不解锁
+        exit(0);
+    }
+    sleep(30);
+    let mut lock = Flock::new(F_UNLCK, 0, 10);
+    assert_eq!(fcntl_lock(fd, F_SETLK, &mut lock), 0);
+    wait_child(pid);
+    let mut lock = Flock::new(F_WRLCK, 0, 0);
+    assert_eq!(fcntl_lock(fd, F_SETLK, &mut lock), 0);
+
+    // dup2覆盖文件描述符时和close一样释放记录锁
+    let null = open("/dev/null\0", OpenFlags::RDONLY);
+    assert!(null > 0);
+    assert_eq!(dup2(null as usize, fd), fd as isize);
+    let pid = fork();
+    if pid == 0 {
+        let fd = open_rw();
+        let mut lock = Flock::new(F_WRLCK, 0, 0);
+        assert_eq!(fcntl_lock(fd, F_SETLK, &mut lock), 0);
+        exit(0);
+    }
+    wait_child(pid);
+    close(null as usize);
+    close(fd);
+    println!("flock_test passed!");
+    0
+}
@@ -0,0 +1,46 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+use alloc::vec::Vec;
+use user_lib::{brk, sbrk};
+
+const PAGE_SIZE: usize = 0x1000;
+
+#[no_mangle]
+pub fn main() -> i32 {
+    // 比HEAP_SPACE大得多 分配器通过sbrk扩大堆
+    let len = 1 << 18;
+    let mut v: Vec<u32> = Vec::with_capacity(len);
+    for i in 0..len {
+        v.push(i as u32);
+    }
+    for (i, value) in v.iter().enumerate() {
+        assert_eq!(*value, i as u32);
+    }
+    drop(v);
+
+    let old = sbrk(0);
+    assert!(old > 0);
+    assert_eq!(sbrk(PAGE_SIZE as isize * 2), old);
+    assert_eq!(sbrk(0), old + PAGE_SIZE as isize * 2);
+    let p = old as usize as *mut u8;
+    unsafe {
+        p.write_volatile(1);
+        p.add(PAGE_SIZE * 2 - 1).write_volatile(2);
+        assert_eq!(p.read_volatile(), 1);
+    }
+    // 缩小再扩大 释放过的页重新分配时为0
+    assert_eq!(brk(old as usize), 0);
+    assert_eq!(sbrk(PAGE_SIZE as isize * 2), old);
+    unsafe {
+        assert_eq!(p.add(PAGE_SIZE * 2 - 1).read_volatile(), 0);
+    }
+    // 断点不能低于堆底
+    assert_eq!(brk(1), -1);
+    println!("heap_test passed!");
+    0
+}
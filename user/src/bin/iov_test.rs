@@ -0,0 +1,68 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, open, pipe, read, readv, sendfile, splice, writev, IoVec, OpenFlags};
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let path = "/tmp/iov_test\0";
+    let fd = open(path, OpenFlags::CREATE | OpenFlags::RDWR | OpenFlags::TRUNC);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    let iov = [
+        IoVec::new(b"Hello, "),
+        IoVec::new(b""),
+        IoVec::new(b"world!\n"),
+    ];
+    assert_eq!(writev(fd, &iov), 14);
+    close(fd);
+
+    // 分散读到两个缓冲区
+    let fd = open(path, OpenFlags::RDONLY) as usize;
+    let mut first = [0u8; 5];
+    let mut second = [0u8; 16];
+    let iov = [IoVec::new_mut(&mut first), IoVec::new_mut(&mut second)];
+    assert_eq!(readv(fd, &iov), 14);
+    assert_eq!(&first, b"Hello");
+    assert_eq!(&second[..9], b", world!\n");
+
+    // 文件到管道 指定偏移时不改变文件的偏移
+    let mut pipe_fd = [0usize; 2];
+    pipe(&mut pipe_fd);
+    let mut offset = 7;
+    assert_eq!(sendfile(pipe_fd[1], fd, Some(&mut offset), 100), 7);
+    assert_eq!(offset, 14);
+    close(pipe_fd[1]);
+    let mut buffer = [0u8; 16];
+    assert_eq!(read(pipe_fd[0], &mut buffer), 7);
+    assert_eq!(&buffer[..7], b"world!\n");
+    close(pipe_fd[0]);
+
+    // 管道到文件
+    pipe(&mut pipe_fd);
+    assert_eq!(sendfile(pipe_fd[1], fd, Some(&mut 0), 5), 5);
+    close(pipe_fd[1]);
+    let out = open(path, OpenFlags::WRONLY) as usize;
+    let mut out_offset = 7;
+    assert_eq!(splice(pipe_fd[0], None, out, Some(&mut out_offset), 100), 5);
+    assert_eq!(out_offset, 12);
+    close(out);
+    close(pipe_fd[0]);
+    close(fd);
+
+    let fd = open(path, OpenFlags::RDONLY) as usize;
+    assert_eq!(read(fd, &mut buffer), 14);
+    assert_eq!(&buffer[..14], b"Hello, Hello!\n");
+
+    // 地址不在任何逻辑段中时返回-1
+    let bad = unsafe { core::slice::from_raw_parts_mut(0x8 as *mut u8, 16) };
+    assert_eq!(readv(fd, &[IoVec::new_mut(bad)]), -1);
+    assert_eq!(writev(1, &[IoVec::new(bad)]), -1);
+    assert_eq!(read(fd, bad), -1);
+    close(fd);
+    println!("iov_test passed!");
+    0
+}
@@ -0,0 +1,45 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, open, read, OpenFlags};
+
+const PAGES: usize = 256;
+const PAGE_SIZE: usize = 0x1000;
+
+static mut BIG: [u8; PAGES * PAGE_SIZE] = [0; PAGES * PAGE_SIZE];
+
+/// /proc/self/status中的VmRSS 单位kB
+fn rss_kb() -> usize {
+    let fd = open("/proc/self/status\0", OpenFlags::RDONLY);
+    assert!(fd > 0);
+    let mut buf = [0u8; 512];
+    let len = read(fd as usize, &mut buf) as usize;
+    close(fd as usize);
+    let status = core::str::from_utf8(&buf[..len]).unwrap();
+    let line = status
+        .lines()
+        .find(|line| line.starts_with("VmRSS:"))
+        .unwrap();
+    line.split_whitespace().nth(1).unwrap().parse().unwrap()
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let big = unsafe { &mut BIG };
+    let before = rss_kb();
+    // .bss在访问之前不占用物理页
+    assert!(before < PAGES * PAGE_SIZE / 1024);
+    let touched = PAGES / 8;
+    for page in 0..touched {
+        assert_eq!(big[page * 8 * PAGE_SIZE], 0);
+        big[page * 8 * PAGE_SIZE] = 1;
+    }
+    let after = rss_kb();
+    assert!(after >= before + touched * PAGE_SIZE / 1024);
+    assert!(after < before + PAGES * PAGE_SIZE / 1024);
+    println!("lazy_test passed! rss {} kB -> {} kB", before, after);
+    0
+}
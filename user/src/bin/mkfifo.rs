@@ -0,0 +1,22 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::mkfifo;
+
+#[no_mangle]
+pub fn main(argc: usize, argv: &[&str]) -> i32 {
+    if argc < 2 {
+        println!("usage: mkfifo PATH...");
+        return -1;
+    }
+    for path in &argv[1..] {
+        if mkfifo(path) != 0 {
+            println!("mkfifo: cannot create {}", path);
+            return -1;
+        }
+    }
+    0
+}
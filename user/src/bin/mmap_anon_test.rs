@@ -0,0 +1,98 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    exit, fork, mmap, mprotect, munmap, waitpid, MAP_ANONYMOUS, MAP_FIXED, MAP_PRIVATE, PROT_READ,
+    PROT_WRITE,
+};
+
+const PAGE_SIZE: usize = 0x1000;
+const MAP_START: usize = 0x2000_0000;
+const PAGES: usize = 4;
+
+fn page(start: usize, index: usize) -> *mut u8 {
+    (start + index * PAGE_SIZE) as *mut u8
+}
+
+/// 子进程写入地址 返回子进程的退出码
+fn child_write(addr: *mut u8) -> i32 {
+    let pid = fork();
+    if pid == 0 {
+        unsafe { addr.write_volatile(1) };
+        exit(0);
+    }
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    exit_code
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let prot = PROT_READ | PROT_WRITE;
+    let flags = MAP_PRIVATE | MAP_ANONYMOUS;
+    // 长度不是整页时按整页映射
+    let start = mmap(MAP_START, PAGES * PAGE_SIZE - 1, prot, flags, 0, 0);
+    assert_eq!(start, MAP_START as isize);
+    for i in 0..PAGES {
+        unsafe {
+            assert_eq!(page(MAP_START, i).read_volatile(), 0);
+            page(MAP_START, i).write_volatile(i as u8 + 1);
+        }
+    }
+    // 重叠时建议的地址不可用 由内核选择
+    let other = mmap(MAP_START, PAGE_SIZE, prot, flags, 0, 0);
+    assert!(other > 0 && other as usize != MAP_START);
+    let other = other as usize;
+    unsafe {
+        page(other, 0).write_volatile(9);
+        assert_eq!(page(MAP_START, 0).read_volatile(), 1);
+    }
+    assert_eq!(munmap(other, PAGE_SIZE), 0);
+    // 不指定地址
+    let any = mmap(0, PAGE_SIZE, prot, flags, 0, 0);
+    assert!(any > 0);
+    assert_eq!(munmap(any as usize, PAGE_SIZE), 0);
+
+    // 拆掉中间一页 逻辑段被切成两段 两边的内容不变
+    assert_eq!(munmap(page(MAP_START, 1) as usize, PAGE_SIZE), 0);
+    assert_eq!(munmap(page(MAP_START, 1) as usize, PAGE_SIZE), -1);
+    assert_ne!(child_write(page(MAP_START, 1)), 0);
+    unsafe {
+        assert_eq!(page(MAP_START, 0).read_volatile(), 1);
+        assert_eq!(page(MAP_START, 2).read_volatile(), 3);
+    }
+    // MAP_FIXED替换已有的映射 新映射的页为0
+    let fixed = mmap(MAP_START, 2 * PAGE_SIZE, prot, flags | MAP_FIXED, 0, 0);
+    assert_eq!(fixed, MAP_START as isize);
+    unsafe {
+        assert_eq!(page(MAP_START, 0).read_volatile(), 0);
+        assert_eq!(page(MAP_START, 1).read_volatile(), 0);
+        assert_eq!(page(MAP_START, 2).read_volatile(), 3);
+    }
+
+    // 改成只读后写入出错 读取正常 改回可写后可以写
+    assert_eq!(
+        mprotect(page(MAP_START, 2) as usize, PAGE_SIZE, PROT_READ),
+        0
+    );
+    assert_ne!(child_write(page(MAP_START, 2)), 0);
+    assert_eq!(child_write(page(MAP_START, 3)), 0);
+    unsafe {
+        assert_eq!(page(MAP_START, 2).read_volatile(), 3);
+    }
+    assert_eq!(mprotect(page(MAP_START, 2) as usize, PAGE_SIZE, prot), 0);
+    unsafe {
+        page(MAP_START, 2).write_volatile(5);
+        assert_eq!(page(MAP_START, 2).read_volatile(), 5);
+    }
+    // 范围内有没映射的页
+    assert_eq!(mprotect(MAP_START, 8 * PAGE_SIZE, PROT_READ), -1);
+
+    assert_eq!(munmap(MAP_START, PAGES * PAGE_SIZE), 0);
+    assert_ne!(child_write(page(MAP_START, 0)), 0);
+    println!("mmap_anon_test passed!");
+    0
+}
@@ -0,0 +1,120 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close, exit, fork, mmap, munmap, open, pipe, read, waitpid, write, OpenFlags, MAP_PRIVATE,
+    MAP_SHARED, PROT_READ, PROT_WRITE,
+};
+
+const MAP_START: usize = 0x1000_0000;
+const MAP_LEN: usize = 0x1000;
+
+fn mapped() -> &'static mut [u8] {
+    unsafe { core::slice::from_raw_parts_mut(MAP_START as *mut u8, MAP_LEN) }
+}
+
+/// 重新打开文件读开头的内容
+fn read_file(buf: &mut [u8]) {
+    let fd = open("/mmap_test\0", OpenFlags::RDONLY);
+    assert!(fd > 0);
+    assert_eq!(read(fd as usize, buf), buf.len() as isize);
+    close(fd as usize);
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fd = open(
+        "/mmap_test\0",
+        OpenFlags::CREATE | OpenFlags::RDWR | OpenFlags::TRUNC,
+    );
+    assert!(fd > 0);
+    let fd = fd as usize;
+    assert_eq!(write(fd, b"hello mmap"), 10);
+    let mut buf = [0u8; 10];
+
+    // 共享映射的修改写回文件 write的内容映射中也能看到
+    let prot = PROT_READ | PROT_WRITE;
+    assert_eq!(
+        mmap(MAP_START, MAP_LEN, prot, MAP_SHARED, fd, 0),
+        MAP_START as isize
+    );
+    assert_eq!(&mapped()[..10], b"hello mmap");
+    // 文件末尾之后的部分为0
+    assert_eq!(mapped()[10], 0);
+    mapped()[0] = b'H';
+    read_file(&mut buf);
+    assert_eq!(&buf, b"Hello mmap");
+    let fd2 = open("/mmap_test\0", OpenFlags::WRONLY) as usize;
+    assert_eq!(write(fd2, b"J"), 1);
+    close(fd2);
+    assert_eq!(mapped()[0], b'J');
+
+    // fork之后共享映射仍然是同一块内存
+    let pid = fork();
+    if pid == 0 {
+        mapped()[1] = b'E';
+        exit(0);
+    }
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(&mapped()[..2], b"JE");
+    assert_eq!(munmap(MAP_START, MAP_LEN), 0);
+    read_file(&mut buf);
+    assert_eq!(&buf, b"JEllo mmap");
+
+    // 私有映射的修改不写回文件
+    assert_eq!(
+        mmap(MAP_START, MAP_LEN, prot, MAP_PRIVATE, fd, 0),
+        MAP_START as isize
+    );
+    assert_eq!(&mapped()[..10], b"JEllo mmap");
+    mapped()[0] = b'X';
+    read_file(&mut buf);
+    assert_eq!(&buf, b"JEllo mmap");
+    assert_eq!(mapped()[0], b'X');
+    assert_eq!(munmap(MAP_START, MAP_LEN), 0);
+
+    // 内核写入私有映射时先复制 不会改到页缓存
+    assert_eq!(
+        mmap(MAP_START, MAP_LEN, prot, MAP_PRIVATE, fd, 0),
+        MAP_START as isize
+    );
+    let mut pipe_fd = [0usize; 2];
+    assert_eq!(pipe(&mut pipe_fd), 0);
+    assert_eq!(write(pipe_fd[1], b"abc"), 3);
+    assert_eq!(read(pipe_fd[0], &mut mapped()[..3]), 3);
+    assert_eq!(&mapped()[..10], b"abclo mmap");
+    read_file(&mut buf);
+    assert_eq!(&buf, b"JEllo mmap");
+    assert_eq!(munmap(MAP_START, MAP_LEN), 0);
+
+    // 只读的映射 内核也不能写入
+    assert_eq!(
+        mmap(MAP_START, MAP_LEN, PROT_READ, MAP_PRIVATE, fd, 0),
+        MAP_START as isize
+    );
+    assert_eq!(write(pipe_fd[1], b"xyz"), 3);
+    assert_eq!(read(pipe_fd[0], &mut mapped()[..3]), -1);
+    assert_eq!(&mapped()[..10], b"JEllo mmap");
+    read_file(&mut buf);
+    assert_eq!(&buf, b"JEllo mmap");
+    assert_eq!(munmap(MAP_START, MAP_LEN), 0);
+    close(pipe_fd[0]);
+    close(pipe_fd[1]);
+
+    // 只读打开的文件不能建立可写的共享映射
+    let fd_ro = open("/mmap_test\0", OpenFlags::RDONLY) as usize;
+    assert_eq!(mmap(MAP_START, MAP_LEN, prot, MAP_SHARED, fd_ro, 0), -1);
+    assert_eq!(
+        mmap(MAP_START, MAP_LEN, prot, MAP_PRIVATE, fd_ro, 0),
+        MAP_START as isize
+    );
+    assert_eq!(munmap(MAP_START, MAP_LEN), 0);
+    close(fd_ro);
+    close(fd);
+    println!("mmap_test passed!");
+    0
+}
@@ -0,0 +1,43 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{chdir, close, mkdir, mount, open, umount, write, OpenFlags};
+
+/// tmpfs单个文件的最大字节数
+const TMPFS_FILE_MAX: usize = 0x4_0000;
+
+#[no_mangle]
+pub fn main() -> i32 {
+    assert_eq!(mkdir("/tmp/mnt\0"), 0);
+    assert_eq!(mount("tmpfs\0", "/tmp/mnt\0", "tmpfs\0"), 0);
+    let fd = open("/tmp/mnt/file\0", OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    // 有打开的文件时不能卸载
+    assert_eq!(umount("/tmp/mnt\0"), -1);
+    // 文件写满后只会短写 不会耗尽内核堆
+    let buf = [0x5au8; 4096];
+    let mut total = 0;
+    loop {
+        let len = write(fd as usize, &buf);
+        assert!(len >= 0);
+        total += len as usize;
+        if (len as usize) < buf.len() {
+            break;
+        }
+    }
+    assert_eq!(total, TMPFS_FILE_MAX);
+    assert_eq!(write(fd as usize, &buf), 0);
+    close(fd as usize);
+    // 当前目录在挂载点下时也不能卸载
+    assert_eq!(chdir("/tmp/mnt\0"), 0);
+    assert_eq!(umount("/tmp/mnt\0"), -1);
+    assert_eq!(chdir("/\0"), 0);
+    assert_eq!(umount("/tmp/mnt\0"), 0);
+    // 卸载后看到的是原来的空目录
+    assert!(open("/tmp/mnt/file\0", OpenFlags::RDONLY) < 0);
+    println!("mount_test passed!");
+    0
+}
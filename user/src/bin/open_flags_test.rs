@@ -0,0 +1,79 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, dup, fcntl, open, read, write, OpenFlags, F_GETFL, F_SETFL};
+
+fn read_file(path: &str, buf: &mut [u8]) -> usize {
+    let fd = open(path, OpenFlags::RDONLY);
+    assert!(fd > 0);
+    let len = read(fd as usize, buf);
+    close(fd as usize);
+    len as usize
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let path = "/tmp/open_flags\0";
+    let mut buffer = [0u8; 32];
+
+    let fd = open(
+        path,
+        OpenFlags::CREATE | OpenFlags::WRONLY | OpenFlags::TRUNC,
+    );
+    assert!(fd > 0);
+    let fd = fd as usize;
+    write(fd, b"hello");
+    // 追加写总是写到文件末尾 不受自己的偏移影响
+    let append_fd = open(path, OpenFlags::WRONLY | OpenFlags::APPEND);
+    assert!(append_fd > 0);
+    let append_fd = append_fd as usize;
+    write(append_fd, b" world");
+    write(fd, b"HELLO");
+    write(append_fd, b"!");
+    let len = read_file(path, &mut buffer);
+    assert_eq!(&buffer[..len], b"HELLO world!");
+
+    // 状态标志可以用fcntl查看和修改 访问模式不变
+    let flags = fcntl(append_fd, F_GETFL, 0);
+    assert_eq!(flags as u32, (OpenFlags::WRONLY | OpenFlags::APPEND).bits());
+    assert_eq!(
+        fcntl(append_fd, F_SETFL, OpenFlags::RDWR.bits() as usize),
+        0
+    );
+    assert_eq!(
+        fcntl(append_fd, F_GETFL, 0) as u32,
+        OpenFlags::WRONLY.bits()
+    );
+    close(append_fd);
+    close(fd);
+
+    // 没有TRUNC时CREATE不会清空已有文件
+    let fd = open(path, OpenFlags::CREATE | OpenFlags::RDONLY);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    // dup得到的文件描述符共享偏移
+    let dup_fd = dup(fd) as usize;
+    assert_eq!(read(fd, &mut buffer[..6]), 6);
+    assert_eq!(read(dup_fd, &mut buffer[..6]), 6);
+    assert_eq!(&buffer[..6], b"world!");
+    close(dup_fd);
+    close(fd);
+
+    assert_eq!(open(path, OpenFlags::CREATE | OpenFlags::EXCL), -1);
+    assert_eq!(open(path, OpenFlags::RDONLY | OpenFlags::DIRECTORY), -1);
+    let fd = open("/tmp\0", OpenFlags::RDONLY | OpenFlags::DIRECTORY);
+    assert!(fd > 0);
+    close(fd as usize);
+    let fd = open(
+        "/tmp/open_flags_excl\0",
+        OpenFlags::CREATE | OpenFlags::EXCL,
+    );
+    assert!(fd > 0);
+    close(fd as usize);
+
+    println!("open_flags_test passed!");
+    0
+}
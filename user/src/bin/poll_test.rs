@@ -0,0 +1,75 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+use user_lib::{
+    close, exit, fork, get_time, pipe, poll, read, sleep, waitpid, write, PollEvents, PollFd,
+};
+
+const CHILDREN: usize = 3;
+
+#[no_mangle]
+pub fn main() -> i32 {
+    // 没有数据时超时返回0
+    let mut pipe_fd = [0usize; 2];
+    pipe(&mut pipe_fd);
+    let mut fds = [PollFd::new(pipe_fd[0], PollEvents::IN)];
+    assert_eq!(poll(&mut fds, 0), 0);
+    let start = get_time();
+    assert_eq!(poll(&mut fds, 50), 0);
+    assert!(get_time() - start >= 50);
+    let mut out = [PollFd::new(pipe_fd[1], PollEvents::OUT)];
+    assert_eq!(poll(&mut out, 0), 1);
+    assert_eq!(out[0].revents, PollEvents::OUT);
+    close(pipe_fd[0]);
+    close(pipe_fd[1]);
+
+    // 同时等待几个子进程的管道 子进程按相反的顺序写入
+    let mut fds = Vec::new();
+    let mut pids = Vec::new();
+    for i in 0..CHILDREN {
+        let mut pipe_fd = [0usize; 2];
+        pipe(&mut pipe_fd);
+        let pid = fork();
+        if pid == 0 {
+            close(pipe_fd[0]);
+            sleep((CHILDREN - i) * 20);
+            write(pipe_fd[1], &[b'0' + i as u8]);
+            close(pipe_fd[1]);
+            exit(0);
+        }
+        close(pipe_fd[1]);
+        fds.push(PollFd::new(pipe_fd[0], PollEvents::IN));
+        pids.push(pid);
+    }
+    let mut order = Vec::new();
+    let mut open = CHILDREN;
+    while open > 0 {
+        assert!(poll(&mut fds, -1) > 0);
+        for poll_fd in fds.iter_mut() {
+            if poll_fd.revents.contains(PollEvents::IN) {
+                let mut buf = [0u8; 1];
+                assert_eq!(read(poll_fd.fd as usize, &mut buf), 1);
+                order.push(buf[0]);
+            } else if poll_fd.revents.contains(PollEvents::HUP) {
+                // 写端已经关闭 以后忽略这个管道
+                close(poll_fd.fd as usize);
+                poll_fd.fd = -1;
+                open -= 1;
+            }
+        }
+    }
+    assert_eq!(order, b"210");
+    for pid in pids {
+        let mut exit_code = 0;
+        assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+        assert_eq!(exit_code, 0);
+    }
+    println!("poll_test passed!");
+    0
+}
@@ -0,0 +1,79 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+use alloc::format;
+use alloc::string::String;
+use user_lib::{close, getdents, open, read, OpenFlags};
+
+/// 读出整个文件
+fn read_file(path: &str) -> Option<String> {
+    let fd = open(path, OpenFlags::RDONLY);
+    if fd < 0 {
+        return None;
+    }
+    let fd = fd as usize;
+    let mut content = String::new();
+    let mut buf = [0u8; 256];
+    loop {
+        let size = read(fd, &mut buf);
+        if size <= 0 {
+            break;
+        }
+        content.push_str(core::str::from_utf8(&buf[..size as usize]).unwrap_or(""));
+    }
+    close(fd);
+    Some(content)
+}
+
+/// 取出status中某一项的值
+fn field<'a>(status: &'a str, key: &str) -> &'a str {
+    status
+        .lines()
+        .find(|line| line.starts_with(key))
+        .map_or("", |line| line[key.len()..].trim())
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fd = open("/proc\0", OpenFlags::RDONLY);
+    if fd < 0 {
+        println!("ps: cannot open /proc");
+        return -1;
+    }
+    let fd = fd as usize;
+    let mut names = [0u8; 1024];
+    let len = getdents(fd, &mut names);
+    close(fd);
+    if len < 0 {
+        println!("ps: cannot read /proc");
+        return -1;
+    }
+    println!(
+        "{:>5} {:>7} {:>8} {:<7} NAME",
+        "PID", "THREADS", "RSS", "STATE"
+    );
+    for name in names[..len as usize].split(|b| *b == 0) {
+        let pid = core::str::from_utf8(name).unwrap_or("");
+        if pid.is_empty() || !pid.bytes().all(|b| b.is_ascii_digit()) {
+            continue;
+        }
+        // 读取期间进程可能已经退出
+        let status = match read_file(&format!("/proc/{}/status\0", pid)) {
+            Some(status) if !status.is_empty() => status,
+            _ => continue,
+        };
+        println!(
+            "{:>5} {:>7} {:>8} {:<7} {}",
+            pid,
+            field(&status, "Threads:"),
+            field(&status, "VmRSS:"),
+            field(&status, "State:"),
+            field(&status, "Name:")
+        );
+    }
+    0
+}
@@ -0,0 +1,104 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+use user_lib::{
+    exit, fork, mmap, shmat, shmctl, shmdt, shmget, waitpid, yield_, IPC_CREAT, IPC_EXCL, IPC_RMID,
+    MAP_ANONYMOUS, MAP_SHARED, PROT_READ, PROT_WRITE, SHM_RDONLY,
+};
+
+const KEY: usize = 0x5348;
+const PAGE_SIZE: usize = 0x1000;
+const SIZE: usize = 2 * PAGE_SIZE;
+const ROUNDS: usize = 100;
+
+/// 生产者和消费者轮流写共享的计数器 奇数由子进程写 偶数由父进程写
+fn ping_pong(counter: &AtomicUsize) {
+    let pid = fork();
+    let parity = if pid == 0 { 1 } else { 0 };
+    for round in 0..ROUNDS {
+        let value = round * 2 + parity;
+        while counter.load(Ordering::Acquire) != value {
+            yield_();
+        }
+        counter.store(value + 1, Ordering::Release);
+    }
+    if pid == 0 {
+        exit(0);
+    }
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+    assert_eq!(counter.load(Ordering::Acquire), ROUNDS * 2);
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let id = shmget(KEY, SIZE, IPC_CREAT | IPC_EXCL);
+    assert!(id > 0);
+    let id = id as usize;
+    assert_eq!(shmget(KEY, SIZE, IPC_CREAT | IPC_EXCL), -1);
+    assert_eq!(shmget(KEY, 0, 0), id as isize);
+    assert_eq!(shmget(KEY, SIZE + 1, 0), -1);
+
+    // 同一个段映射两次 看到相同的内容
+    let a = shmat(id, 0, 0);
+    let b = shmat(id, 0, SHM_RDONLY);
+    assert!(a > 0 && b > 0 && a != b);
+    let a = a as usize as *mut u8;
+    let b = b as usize as *const u8;
+    unsafe {
+        assert_eq!(b.add(PAGE_SIZE).read_volatile(), 0);
+        a.add(PAGE_SIZE).write_volatile(42);
+        assert_eq!(b.add(PAGE_SIZE).read_volatile(), 42);
+    }
+    assert_eq!(shmdt(b as usize), 0);
+    assert_eq!(shmdt(b as usize), -1);
+
+    // fork之后父子进程共享段中的页 子进程通过键重新映射也能看到
+    let counter = unsafe { &*(a as *const AtomicUsize) };
+    counter.store(0, Ordering::Release);
+    ping_pong(counter);
+    let pid = fork();
+    if pid == 0 {
+        let id = shmget(KEY, 0, 0) as usize;
+        let c = shmat(id, 0, 0) as usize as *mut u8;
+        unsafe {
+            assert_eq!(c.add(PAGE_SIZE).read_volatile(), 42);
+            c.add(PAGE_SIZE).write_volatile(43);
+        }
+        exit(0);
+    }
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+    unsafe {
+        assert_eq!(a.add(PAGE_SIZE).read_volatile(), 43);
+    }
+
+    // 删除后不能再取得 已有的映射继续有效
+    assert_eq!(shmctl(id, IPC_RMID), 0);
+    assert_eq!(shmget(KEY, 0, 0), -1);
+    assert_eq!(shmat(id, 0, 0), -1);
+    unsafe {
+        assert_eq!(a.add(PAGE_SIZE).read_volatile(), 43);
+    }
+    assert_eq!(shmdt(a as usize), 0);
+
+    // 匿名的共享映射在fork之后共享
+    let shared = mmap(
+        0,
+        PAGE_SIZE,
+        PROT_READ | PROT_WRITE,
+        MAP_SHARED | MAP_ANONYMOUS,
+        0,
+        0,
+    );
+    assert!(shared > 0);
+    ping_pong(unsafe { &*(shared as usize as *const AtomicUsize) });
+    println!("shm_test passed!");
+    0
+}
@@ -0,0 +1,63 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, open, pipe, read, write, OpenFlags};
+
+/// 比物理内存大 必须换出一部分页才能放下
+const PAGES: usize = 2048;
+const PAGE_SIZE: usize = 0x1000;
+
+static mut BIG: [u8; PAGES * PAGE_SIZE] = [0; PAGES * PAGE_SIZE];
+
+/// /proc/meminfo中的一项 单位kB
+fn meminfo_kb(key: &str) -> usize {
+    let fd = open("/proc/meminfo\0", OpenFlags::RDONLY);
+    assert!(fd > 0);
+    let mut buf = [0u8; 512];
+    let len = read(fd as usize, &mut buf) as usize;
+    close(fd as usize);
+    let meminfo = core::str::from_utf8(&buf[..len]).unwrap();
+    let line = meminfo.lines().find(|line| line.starts_with(key)).unwrap();
+    line.split_whitespace().nth(1).unwrap().parse().unwrap()
+}
+
+fn pattern(page: usize, offset: usize) -> u8 {
+    (page * 7 + offset) as u8
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    if meminfo_kb("SwapTotal:") < PAGES * PAGE_SIZE / 1024 {
+        println!("swap_test skipped: swap device too small or missing");
+        return 0;
+    }
+    let big = unsafe { &mut BIG };
+    for page in 0..PAGES {
+        big[page * PAGE_SIZE] = pattern(page, 0);
+        big[page * PAGE_SIZE + PAGE_SIZE - 1] = pattern(page, PAGE_SIZE - 1);
+    }
+    assert!(meminfo_kb("SwapFree:") < meminfo_kb("SwapTotal:"));
+    for page in 0..PAGES {
+        assert_eq!(big[page * PAGE_SIZE], pattern(page, 0));
+        assert_eq!(
+            big[page * PAGE_SIZE + PAGE_SIZE - 1],
+            pattern(page, PAGE_SIZE - 1)
+        );
+    }
+    // 内核读写换出的页时先调入
+    let mut pipe_fd = [0usize; 2];
+    assert_eq!(pipe(&mut pipe_fd), 0);
+    assert_eq!(write(pipe_fd[1], &big[..1]), 1);
+    assert_eq!(
+        read(pipe_fd[0], &mut big[PAGE_SIZE * 2..PAGE_SIZE * 2 + 1]),
+        1
+    );
+    assert_eq!(big[PAGE_SIZE * 2], pattern(0, 0));
+    close(pipe_fd[0]);
+    close(pipe_fd[1]);
+    println!("swap_test passed!");
+    0
+}
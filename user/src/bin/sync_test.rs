@@ -0,0 +1,49 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, fsync, open, pipe, sync, syncfs, write, OpenFlags};
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fd = open(
+        "/sync_test\0",
+        OpenFlags::CREATE | OpenFlags::WRONLY | OpenFlags::TRUNC,
+    );
+    assert!(fd > 0);
+    let fd = fd as usize;
+    assert_eq!(write(fd, b"durable"), 7);
+    // 写回磁盘之后返回0
+    assert_eq!(fsync(fd), 0);
+    assert_eq!(syncfs(fd), 0);
+    close(fd);
+
+    // 内存中的文件系统没有需要落盘的数据
+    let tmp_fd = open("/tmp/sync_test\0", OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(tmp_fd > 0);
+    let tmp_fd = tmp_fd as usize;
+    assert_eq!(write(tmp_fd, b"volatile"), 8);
+    assert_eq!(fsync(tmp_fd), 0);
+    assert_eq!(syncfs(tmp_fd), 0);
+    close(tmp_fd);
+
+    // 管道没有可以写回的设备
+    let mut pipe_fd = [0usize; 2];
+    pipe(&mut pipe_fd);
+    assert_eq!(fsync(pipe_fd[1]), -1);
+    assert_eq!(syncfs(pipe_fd[1]), -1);
+    close(pipe_fd[0]);
+    close(pipe_fd[1]);
+
+    // 重新打开的文件没有修改 也能写回
+    let fd = open("/sync_test\0", OpenFlags::RDONLY);
+    assert!(fd > 0);
+    assert_eq!(fsync(fd as usize), 0);
+    close(fd as usize);
+
+    assert_eq!(sync(), 0);
+    println!("sync_test passed!");
+    0
+}
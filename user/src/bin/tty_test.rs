@@ -0,0 +1,46 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close, exit, fork, kill, open, read, readv, sleep, waitpid, IoVec, OpenFlags, SignalFlags,
+};
+
+#[no_mangle]
+pub fn main() -> i32 {
+    // 只写打开的控制台不能读
+    let fd = open("/dev/tty\0", OpenFlags::WRONLY);
+    assert!(fd > 0);
+    let mut buffer = [0u8; 64];
+    assert_eq!(read(fd as usize, &mut buffer), -1);
+    close(fd as usize);
+
+    // 一次读多个字符 没有输入时一直等待 直到被终止
+    for vectored in [false, true] {
+        let pid = fork();
+        if pid == 0 {
+            let fd = open("/dev/tty\0", OpenFlags::RDONLY) as usize;
+            let mut first = [0u8; 1];
+            let mut second = [0u8; 63];
+            let read = if vectored {
+                readv(
+                    fd,
+                    &[IoVec::new_mut(&mut first), IoVec::new_mut(&mut second)],
+                )
+            } else {
+                read(fd, &mut buffer)
+            };
+            exit(read as i32);
+        }
+        sleep(50);
+        kill(pid as usize, SignalFlags::SIGINT.bits());
+        let mut exit_code = 0;
+        assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+        // 测试时有输入则读到已经到达的字符
+        assert!(exit_code == -2 || (1..=64).contains(&exit_code));
+    }
+    println!("tty_test passed!");
+    0
+}
@@ -16,7 +16,10 @@ const LINE_START: &str = ">> ";
 use alloc::string::String;
 use alloc::vec::Vec;
 use user_lib::console::getchar;
-use user_lib::{close, dup, exec, fork, open, pipe, waitpid, OpenFlags};
+use user_lib::{
+    chdir, close, dup3, exec, fcntl, fork, getcwd, open, pipe, waitpid, OpenFlags, FD_CLOEXEC,
+    F_SETFD,
+};
 
 #[derive(Debug)]
 struct ProcessArguments {
@@ -74,17 +77,49 @@ impl ProcessArguments {
     }
 }
 
+/// cd是内置命令 不带参数时回到根目录
+fn cd_target(line: &str) -> Option<String> {
+    let mut args = line.split(' ').filter(|arg| !arg.is_empty());
+    if args.next() != Some("cd") {
+        return None;
+    }
+    let mut dir = String::from(args.next().unwrap_or("/"));
+    dir.push('\0');
+    Some(dir)
+}
+
+/// 提示符中显示当前工作目录
+fn print_prompt() {
+    let mut cwd = [0u8; 128];
+    let len = getcwd(&mut cwd);
+    if len > 0 {
+        print!(
+            "{}",
+            core::str::from_utf8(&cwd[..len as usize - 1]).unwrap_or("")
+        );
+    }
+    print!("{}", LINE_START);
+}
+
 #[no_mangle]
 pub fn main() -> i32 {
     println!("Rust user shell");
     let mut line: String = String::new();
-    print!("{}", LINE_START);
+    print_prompt();
     loop {
         let c = getchar();
         match c {
             LF | CR => {
                 println!("");
-                if !line.is_empty() {
+                if let Some(dir) = cd_target(line.as_str()) {
+                    if chdir(dir.as_str()) == -1 {
+                        println!(
+                            "cd: cannot change directory to {}",
+                            dir.trim_end_matches('\0')
+                        );
+                    }
+                    line.clear();
+                } else if !line.is_empty() {
                     let splited: Vec<_> = line.as_str().split('|').collect();
                     let process_arguments_list: Vec<_> = splited
                         .iter()
@@ -117,6 +152,9 @@ pub fn main() -> i32 {
                             for _ in 0..process_arguments_list.len() - 1 {
                                 let mut pipe_fd = [0usize; 2];
                                 pipe(&mut pipe_fd);
+                                // 子进程exec时自动关闭 不会一直占着管道的写端
+                                fcntl(pipe_fd[0], F_SETFD, FD_CLOEXEC);
+                                fcntl(pipe_fd[1], F_SETFD, FD_CLOEXEC);
                                 pipes_fd.push(pipe_fd);
                             }
                         }
@@ -136,44 +174,42 @@ pub fn main() -> i32 {
                                         return -4;
                                     }
                                     let input_fd = input_fd as usize;
-                                    close(0);
-                                    assert_eq!(dup(input_fd), 0);
+                                    assert_eq!(dup3(input_fd, 0, OpenFlags::empty()), 0);
                                     close(input_fd);
                                 }
                                 // redirect output
                                 if !output.is_empty() {
                                     let output_fd = open(
                                         output.as_str(),
-                                        OpenFlags::CREATE | OpenFlags::WRONLY,
+                                        OpenFlags::CREATE | OpenFlags::WRONLY | OpenFlags::TRUNC,
                                     );
                                     if output_fd == -1 {
                                         println!("Error when opening file {}", output);
                                         return -4;
                                     }
                                     let output_fd = output_fd as usize;
-                                    close(1);
-                                    assert_eq!(dup(output_fd), 1);
+                                    assert_eq!(dup3(output_fd, 1, OpenFlags::empty()), 1);
                                     close(output_fd);
                                 }
                                 // receive input from the previous process
                                 if i > 0 {
-                                    close(0);
                                     let read_end = pipes_fd.get(i - 1).unwrap()[0];
-                                    assert_eq!(dup(read_end), 0);
+                                    assert_eq!(dup3(read_end, 0, OpenFlags::empty()), 0);
                                 }
                                 // send output to the next process
                                 if i < process_arguments_list.len() - 1 {
-                                    close(1);
                                     let write_end = pipes_fd.get(i).unwrap()[1];
-                                    assert_eq!(dup(write_end), 1);
-                                }
-                                // close all pipe ends inherited from the parent process
-                                for pipe_fd in pipes_fd.iter() {
-                                    close(pipe_fd[0]);
-                                    close(pipe_fd[1]);
+                                    assert_eq!(dup3(write_end, 1, OpenFlags::empty()), 1);
                                 }
+                                // pipe ends inherited from the parent are closed on exec
                                 // execute new application
-                                if exec(args_copy[0].as_str(), args_addr.as_slice()) == -1 {
+                                // 不含/的命令名在根目录中查找 不受工作目录影响
+                                let mut app = String::new();
+                                if !args_copy[0].contains('/') {
+                                    app.push('/');
+                                }
+                                app.push_str(args_copy[0].as_str());
+                                if exec(app.as_str(), args_addr.as_slice()) == -1 {
                                     println!("Error when executing!");
                                     return -4;
                                 }
@@ -195,7 +231,7 @@ pub fn main() -> i32 {
                     }
                     line.clear();
                 }
-                print!("{}", LINE_START);
+                print_prompt();
             }
             BS | DL => {
                 if !line.is_empty() {
@@ -4,57 +4,107 @@
 #[macro_use]
 extern crate user_lib;
 
-// not in SUCC_TESTS & FAIL_TESTS
+// not in TESTS
 // count_lines, infloop, user_shell, usertests
 
-// item of TESTS : app_name(argv_0), argv_1, argv_2, argv_3, exit_code
-static SUCC_TESTS: &[(&str, &str, &str, &str, i32)] = &[
-    ("filetest_simple\0", "\0", "\0", "\0", 0),
-    ("cat\0", "filea\0", "\0", "\0", 0),
-    ("cmdline_args\0", "1\0", "2\0", "3\0", 0),
-    ("exit\0", "\0", "\0", "\0", 0),
-    ("fantastic_text\0", "\0", "\0", "\0", 0),
-    ("forktest_simple\0", "\0", "\0", "\0", 0),
-    ("forktest\0", "\0", "\0", "\0", 0),
-    ("forktest2\0", "\0", "\0", "\0", 0),
-    ("forktree\0", "\0", "\0", "\0", 0),
-    ("hello_world\0", "\0", "\0", "\0", 0),
-    ("huge_write\0", "\0", "\0", "\0", 0),
-    ("matrix\0", "\0", "\0", "\0", 0),
-    ("mpsc_sem\0", "\0", "\0", "\0", 0),
-    ("phil_din_mutex\0", "\0", "\0", "\0", 0),
-    ("pipe_large_test\0", "\0", "\0", "\0", 0),
-    ("pipetest\0", "\0", "\0", "\0", 0),
-    ("adder_peterson_spin\0", "\0", "\0", "\0", 0),
-    ("adder_peterson_yield\0", "\0", "\0", "\0", 0),
-    ("adder_mutex_blocking\0", "\0", "\0", "\0", 0),
-    ("adder_mutex_spin\0", "\0", "\0", "\0", 0),
-    ("run_pipe_test\0", "\0", "\0", "\0", 0),
-    ("sleep_simple\0", "\0", "\0", "\0", 0),
-    ("sleep\0", "\0", "\0", "\0", 0),
-    ("sleep_simple\0", "\0", "\0", "\0", 0),
-    ("sync_sem\0", "\0", "\0", "\0", 0),
-    ("test_condvar\0", "\0", "\0", "\0", 0),
-    ("threads_arg\0", "\0", "\0", "\0", 0),
-    ("threads\0", "\0", "\0", "\0", 0),
-    ("yield\0", "\0", "\0", "\0", 0),
+// item of TESTS : app_name(argv_0), argv_1, argv_2, argv_3
+// 期望的退出码和超时时间保存在应用的扩展属性中 见user/usertests.xattr
+static TESTS: &[(&str, &str, &str, &str)] = &[
+    ("filetest_simple\0", "\0", "\0", "\0"),
+    ("cat\0", "/tmp/filea\0", "\0", "\0"),
+    ("devfs_test\0", "\0", "\0", "\0"),
+    ("tty_test\0", "\0", "\0", "\0"),
+    ("mount_test\0", "\0", "\0", "\0"),
+    ("fcntl_test\0", "\0", "\0", "\0"),
+    ("open_flags_test\0", "\0", "\0", "\0"),
+    ("poll_test\0", "\0", "\0", "\0"),
+    ("fifo_test\0", "\0", "\0", "\0"),
+    ("iov_test\0", "\0", "\0", "\0"),
+    ("sync_test\0", "\0", "\0", "\0"),
+    ("flock_test\0", "\0", "\0", "\0"),
+    ("mmap_test\0", "\0", "\0", "\0"),
+    ("mmap_anon_test\0", "\0", "\0", "\0"),
+    ("shm_test\0", "\0", "\0", "\0"),
+    ("cow_test\0", "\0", "\0", "\0"),
+    ("lazy_test\0", "\0", "\0", "\0"),
+    ("swap_test\0", "\0", "\0", "\0"),
+    ("heap_test\0", "\0", "\0", "\0"),
+    ("ps\0", "\0", "\0", "\0"),
+    ("cmdline_args\0", "1\0", "2\0", "3\0"),
+    ("exit\0", "\0", "\0", "\0"),
+    ("fantastic_text\0", "\0", "\0", "\0"),
+    ("forktest_simple\0", "\0", "\0", "\0"),
+    ("forktest\0", "\0", "\0", "\0"),
+    ("forktest2\0", "\0", "\0", "\0"),
+    ("forktree\0", "\0", "\0", "\0"),
+    ("hello_world\0", "\0", "\0", "\0"),
+    ("huge_write\0", "\0", "\0", "\0"),
+    ("matrix\0", "\0", "\0", "\0"),
+    ("mpsc_sem\0", "\0", "\0", "\0"),
+    ("phil_din_mutex\0", "\0", "\0", "\0"),
+    ("pipe_large_test\0", "\0", "\0", "\0"),
+    ("pipetest\0", "\0", "\0", "\0"),
+    ("adder_peterson_spin\0", "\0", "\0", "\0"),
+    ("adder_peterson_yield\0", "\0", "\0", "\0"),
+    ("adder_mutex_blocking\0", "\0", "\0", "\0"),
+    ("adder_mutex_spin\0", "\0", "\0", "\0"),
+    ("run_pipe_test\0", "\0", "\0", "\0"),
+    ("sleep_simple\0", "\0", "\0", "\0"),
+    ("sleep\0", "\0", "\0", "\0"),
+    ("sleep_simple\0", "\0", "\0", "\0"),
+    ("sync_sem\0", "\0", "\0", "\0"),
+    ("test_condvar\0", "\0", "\0", "\0"),
+    ("threads_arg\0", "\0", "\0", "\0"),
+    ("threads\0", "\0", "\0", "\0"),
+    ("yield\0", "\0", "\0", "\0"),
+    ("stack_overflow\0", "\0", "\0", "\0"),
+    ("race_adder_loop\0", "\0", "\0", "\0"),
+    ("priv_csr\0", "\0", "\0", "\0"),
+    ("priv_inst\0", "\0", "\0", "\0"),
+    ("store_fault\0", "\0", "\0", "\0"),
+    ("until_timeout\0", "\0", "\0", "\0"),
+    ("adder\0", "\0", "\0", "\0"),
+    ("adder_simple_spin\0", "\0", "\0", "\0"),
+    ("adder_simple_yield\0", "\0", "\0", "\0"),
 ];
 
-static FAIL_TESTS: &[(&str, &str, &str, &str, i32)] = &[
-    ("stack_overflow\0", "\0", "\0", "\0", -11),
-    ("race_adder_loop\0", "\0", "\0", "\0", -6),
-    ("priv_csr\0", "\0", "\0", "\0", -4),
-    ("priv_inst\0", "\0", "\0", "\0", -4),
-    ("store_fault\0", "\0", "\0", "\0", -11),
-    ("until_timeout\0", "\0", "\0", "\0", -6),
-    ("adder\0", "\0", "\0", "\0", -6),
-    ("adder_simple_spin\0", "\0", "\0", "\0", -6),
-    ("adder_simple_yield\0", "\0", "\0", "\0", -6),
-];
+use user_lib::{exec, fork, get_time, getxattr, kill, waitpid, waitpid_nb, yield_, SignalFlags};
 
-use user_lib::{exec, fork, waitpid};
+/// 读取应用的整数扩展属性
+fn xattr_i32(app: &str, name: &str) -> Option<i32> {
+    let mut value = [0u8; 16];
+    let len = getxattr(app, name, &mut value);
+    if len < 0 {
+        return None;
+    }
+    core::str::from_utf8(&value[..len as usize])
+        .ok()
+        .and_then(|value| value.parse().ok())
+}
+
+/// 等待子进程退出 超时后发送SIGINT
+fn wait_with_timeout(pid: usize, timeout_ms: Option<i32>, exit_code: &mut i32) -> isize {
+    let timeout_ms = match timeout_ms {
+        Some(timeout_ms) => timeout_ms as isize,
+        None => return waitpid(pid, exit_code),
+    };
+    let start_time = get_time();
+    let mut killed = false;
+    loop {
+        let wait_pid = waitpid_nb(pid, exit_code);
+        if wait_pid != -2 {
+            return wait_pid;
+        }
+        if !killed && get_time() - start_time > timeout_ms {
+            println!("Usertests: pid {} timed out after {}ms", pid, timeout_ms);
+            kill(pid, SignalFlags::SIGINT.bits());
+            killed = true;
+        }
+        yield_();
+    }
+}
 
-fn run_tests(tests: &[(&str, &str, &str, &str, i32)]) -> i32 {
+fn run_tests(tests: &[(&str, &str, &str, &str)]) -> i32 {
     let mut pass_num = 0;
     let mut arr: [*const u8; 4] = [
         core::ptr::null::<u8>(),
@@ -93,10 +143,12 @@ fn run_tests(tests: &[(&str, &str, &str, &str, i32)]) -> i32 {
             exec(test.0, &arr[..]);
             panic!("unreachable!");
         } else {
+            let expected = xattr_i32(test.0, "user.exit_code\0").unwrap_or(0);
+            let timeout_ms = xattr_i32(test.0, "user.timeout\0");
             let mut exit_code: i32 = Default::default();
-            let wait_pid = waitpid(pid as usize, &mut exit_code);
+            let wait_pid = wait_with_timeout(pid as usize, timeout_ms, &mut exit_code);
             assert_eq!(pid, wait_pid);
-            if exit_code == test.4 {
+            if exit_code == expected {
                 // summary apps with  exit_code
                 pass_num = pass_num + 1;
             }
@@ -111,30 +163,16 @@ fn run_tests(tests: &[(&str, &str, &str, &str, i32)]) -> i32 {
 
 #[no_mangle]
 pub fn main() -> i32 {
-    let succ_num = run_tests(SUCC_TESTS);
-    let err_num = run_tests(FAIL_TESTS);
-    if succ_num == SUCC_TESTS.len() as i32 && err_num == FAIL_TESTS.len() as i32 {
-        println!(
-            "{} of sueecssed apps, {} of failed apps run correctly. \nUsertests passed!",
-            SUCC_TESTS.len(),
-            FAIL_TESTS.len()
-        );
+    let pass_num = run_tests(TESTS);
+    if pass_num == TESTS.len() as i32 {
+        println!("{} of apps run correctly. \nUsertests passed!", TESTS.len());
         return 0;
     }
-    if succ_num != SUCC_TESTS.len() as i32 {
-        println!(
-            "all successed app_num is  {} , but only  passed {}",
-            SUCC_TESTS.len(),
-            succ_num
-        );
-    }
-    if err_num != FAIL_TESTS.len() as i32 {
-        println!(
-            "all failed app_num is  {} , but only  passed {}",
-            FAIL_TESTS.len(),
-            err_num
-        );
-    }
+    println!(
+        "all app_num is  {} , but only  passed {}",
+        TESTS.len(),
+        pass_num
+    );
     println!(" Usertests failed!");
     return -1;
 }
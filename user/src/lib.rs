@@ -14,14 +14,48 @@ extern crate bitflags;
 
 use alloc::vec::Vec;
 use buddy_system_allocator::LockedHeap;
+use core::alloc::{GlobalAlloc, Layout};
 use syscall::*;
 
 const USER_HEAP_SIZE: usize = 32768;
+/// 堆不够时每次至少通过sbrk扩大这么多
+const HEAP_GROW_SIZE: usize = 0x1_0000;
 
 static mut HEAP_SPACE: [u8; USER_HEAP_SIZE] = [0; USER_HEAP_SIZE];
 
+/// 先使用HEAP_SPACE 分配失败时通过sbrk扩大堆
+struct GrowableHeap(LockedHeap<32>);
+
+unsafe impl GlobalAlloc for GrowableHeap {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = self.0.alloc(layout);
+        if !ptr.is_null() {
+            return ptr;
+        }
+        // 伙伴系统只能从对齐的区间中分出整块 扩大两倍保证能放下
+        let size = layout
+            .size()
+            .max(layout.align())
+            .next_power_of_two()
+            .max(HEAP_GROW_SIZE)
+            * 2;
+        let start = sbrk(size as isize);
+        if start < 0 {
+            return ptr;
+        }
+        self.0
+            .lock()
+            .add_to_heap(start as usize, start as usize + size);
+        self.0.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        self.0.dealloc(ptr, layout)
+    }
+}
+
 #[global_allocator]
-static HEAP: LockedHeap<32> = LockedHeap::empty();
+static HEAP: GrowableHeap = GrowableHeap(LockedHeap::empty());
 
 #[alloc_error_handler]
 pub fn handle_alloc_error(layout: core::alloc::Layout) -> ! {
@@ -32,7 +66,8 @@ pub fn handle_alloc_error(layout: core::alloc::Layout) -> ! {
 #[link_section = ".text.entry"]
 pub extern "C" fn _start(argc: usize, argv: usize) -> ! {
     unsafe {
-        HEAP.lock()
+        HEAP.0
+            .lock()
             .init(HEAP_SPACE.as_ptr() as usize, USER_HEAP_SIZE);
     }
     let mut v: Vec<&'static str> = Vec::new();
@@ -64,13 +99,193 @@ bitflags! {
         const WRONLY = 1 << 0;
         const RDWR = 1 << 1;
         const CREATE = 1 << 9;
+        const EXCL = 1 << 7;
         const TRUNC = 1 << 10;
+        const NONBLOCK = 1 << 11;
+        const APPEND = 1 << 12;
+        const DIRECTORY = 1 << 16;
+        const CLOEXEC = 1 << 19;
+    }
+}
+
+bitflags! {
+    #[repr(transparent)]
+    pub struct PollEvents: u16 {
+        const IN = 1 << 0;
+        const PRI = 1 << 1;
+        const OUT = 1 << 2;
+        const ERR = 1 << 3;
+        const HUP = 1 << 4;
+        const NVAL = 1 << 5;
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct PollFd {
+    pub fd: i32,
+    pub events: PollEvents,
+    pub revents: PollEvents,
+}
+
+impl PollFd {
+    pub fn new(fd: usize, events: PollEvents) -> Self {
+        Self {
+            fd: fd as i32,
+            events,
+            revents: PollEvents::empty(),
+        }
+    }
+}
+
+/// readv和writev使用的缓冲区
+#[repr(C)]
+pub struct IoVec {
+    pub base: *mut u8,
+    pub len: usize,
+}
+
+impl IoVec {
+    pub fn new(buf: &[u8]) -> Self {
+        Self {
+            base: buf.as_ptr() as *mut u8,
+            len: buf.len(),
+        }
+    }
+
+    /// readv写入的缓冲区
+    pub fn new_mut(buf: &mut [u8]) -> Self {
+        Self {
+            base: buf.as_mut_ptr(),
+            len: buf.len(),
+        }
+    }
+}
+
+#[repr(C)]
+pub struct TimeSpec {
+    pub sec: usize,
+    pub nsec: usize,
+}
+
+pub const F_DUPFD: usize = 0;
+pub const F_GETFD: usize = 1;
+pub const F_SETFD: usize = 2;
+pub const F_GETFL: usize = 3;
+pub const F_SETFL: usize = 4;
+pub const F_GETLK: usize = 5;
+pub const F_SETLK: usize = 6;
+pub const F_SETLKW: usize = 7;
+pub const F_DUPFD_CLOEXEC: usize = 1030;
+pub const FD_CLOEXEC: usize = 1;
+
+pub const F_RDLCK: i16 = 0;
+pub const F_WRLCK: i16 = 1;
+pub const F_UNLCK: i16 = 2;
+
+/// fcntl记录锁的参数 偏移从文件开头计算
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct Flock {
+    pub l_type: i16,
+    pub l_whence: i16,
+    pub l_start: isize,
+    pub l_len: isize,
+    pub l_pid: i32,
+}
+
+impl Flock {
+    pub fn new(l_type: i16, l_start: isize, l_len: isize) -> Self {
+        Self {
+            l_type,
+            l_whence: 0,
+            l_start,
+            l_len,
+            l_pid: 0,
+        }
+    }
+}
+
+pub const LOCK_SH: usize = 1;
+pub const LOCK_EX: usize = 2;
+pub const LOCK_NB: usize = 4;
+pub const LOCK_UN: usize = 8;
+
+pub const PROT_READ: usize = 1;
+pub const PROT_WRITE: usize = 2;
+pub const PROT_EXEC: usize = 4;
+
+pub const MAP_SHARED: usize = 0x01;
+pub const MAP_PRIVATE: usize = 0x02;
+pub const MAP_FIXED: usize = 0x10;
+pub const MAP_ANONYMOUS: usize = 0x20;
+
+/// shmget总是新建段
+pub const IPC_PRIVATE: usize = 0;
+pub const IPC_CREAT: usize = 0o1000;
+pub const IPC_EXCL: usize = 0o2000;
+pub const IPC_RMID: usize = 0;
+pub const SHM_RDONLY: usize = 0o10000;
+
+bitflags! {
+    pub struct XattrFlags: u32 {
+        const CREATE = 1;
+        const REPLACE = 2;
     }
 }
 
 pub fn dup(fd: usize) -> isize {
     sys_dup(fd)
 }
+pub fn dup3(old_fd: usize, new_fd: usize, flags: OpenFlags) -> isize {
+    sys_dup3(old_fd, new_fd, flags.bits)
+}
+/// dup3不允许old_fd和new_fd相同 这时只检查old_fd是否有效
+pub fn dup2(old_fd: usize, new_fd: usize) -> isize {
+    if old_fd == new_fd {
+        if fcntl(old_fd, F_GETFD, 0) < 0 {
+            return -1;
+        }
+        return new_fd as isize;
+    }
+    dup3(old_fd, new_fd, OpenFlags::empty())
+}
+pub fn flock(fd: usize, operation: usize) -> isize {
+    sys_flock(fd, operation)
+}
+/// F_GETLK F_SETLK和F_SETLKW
+pub fn fcntl_lock(fd: usize, cmd: usize, lock: &mut Flock) -> isize {
+    fcntl(fd, cmd, lock as *mut Flock as usize)
+}
+pub fn fcntl(fd: usize, cmd: usize, arg: usize) -> isize {
+    sys_fcntl(fd, cmd, arg)
+}
+pub fn mkdir(path: &str) -> isize {
+    sys_mkdir(path)
+}
+pub const S_IFIFO: u32 = 0o010000;
+pub const S_IFREG: u32 = 0o100000;
+pub fn mknod(path: &str, mode: u32, dev: usize) -> isize {
+    sys_mknod(path, mode, dev)
+}
+pub fn mkfifo(path: &str) -> isize {
+    mknod(path, S_IFIFO, 0)
+}
+pub fn mount(source: &str, target: &str, fstype: &str) -> isize {
+    sys_mount(source, target, fstype)
+}
+pub fn umount(target: &str) -> isize {
+    sys_umount(target)
+}
+pub fn getcwd(buf: &mut [u8]) -> isize {
+    sys_getcwd(buf)
+}
+pub fn chdir(path: &str) -> isize {
+    sys_chdir(path)
+}
+pub fn fchdir(fd: usize) -> isize {
+    sys_fchdir(fd)
+}
 pub fn open(path: &str, flags: OpenFlags) -> isize {
     sys_open(path, flags.bits)
 }
@@ -80,12 +295,122 @@ pub fn close(fd: usize) -> isize {
 pub fn pipe(pipe_fd: &mut [usize]) -> isize {
     sys_pipe(pipe_fd)
 }
+pub fn ppoll(fds: &mut [PollFd], timeout: Option<&TimeSpec>) -> isize {
+    sys_ppoll(fds, timeout)
+}
+/// timeout_ms为负数时一直等待
+pub fn poll(fds: &mut [PollFd], timeout_ms: isize) -> isize {
+    if timeout_ms < 0 {
+        return ppoll(fds, None);
+    }
+    let timeout = TimeSpec {
+        sec: timeout_ms as usize / 1000,
+        nsec: timeout_ms as usize % 1000 * 1_000_000,
+    };
+    ppoll(fds, Some(&timeout))
+}
+pub fn getdents(fd: usize, buf: &mut [u8]) -> isize {
+    sys_getdents(fd, buf)
+}
 pub fn read(fd: usize, buf: &mut [u8]) -> isize {
     sys_read(fd, buf)
 }
 pub fn write(fd: usize, buf: &[u8]) -> isize {
     sys_write(fd, buf)
 }
+pub fn sync() -> isize {
+    sys_sync()
+}
+pub fn fsync(fd: usize) -> isize {
+    sys_fsync(fd)
+}
+pub fn syncfs(fd: usize) -> isize {
+    sys_syncfs(fd)
+}
+/// 把文件映射到start附近 匿名映射需要MAP_ANONYMOUS 返回映射的地址
+///
+/// start为0或者和已有映射重叠时由内核选择地址 MAP_FIXED时替换start处原有的映射
+pub fn mmap(
+    start: usize,
+    len: usize,
+    prot: usize,
+    flags: usize,
+    fd: usize,
+    offset: usize,
+) -> isize {
+    sys_mmap(start, len, prot, flags, fd, offset)
+}
+/// 设置程序断点 成功返回0
+pub fn brk(addr: usize) -> isize {
+    if sys_brk(addr) as usize == addr {
+        0
+    } else {
+        -1
+    }
+}
+/// 程序断点增加increment 返回原来的断点 失败返回-1
+pub fn sbrk(increment: isize) -> isize {
+    let old = sys_brk(0);
+    if increment == 0 {
+        return old;
+    }
+    let new = (old + increment) as usize;
+    if sys_brk(new) as usize == new {
+        old
+    } else {
+        -1
+    }
+}
+pub fn munmap(start: usize, len: usize) -> isize {
+    sys_munmap(start, len)
+}
+pub fn mprotect(start: usize, len: usize, prot: usize) -> isize {
+    sys_mprotect(start, len, prot)
+}
+/// 取得键为key的共享内存段 返回标识符
+pub fn shmget(key: usize, size: usize, flags: usize) -> isize {
+    sys_shmget(key, size, flags)
+}
+/// 映射共享内存段 addr为0时由内核选择地址 返回映射的地址
+pub fn shmat(id: usize, addr: usize, flags: usize) -> isize {
+    sys_shmat(id, addr, flags)
+}
+pub fn shmdt(addr: usize) -> isize {
+    sys_shmdt(addr)
+}
+pub fn shmctl(id: usize, cmd: usize) -> isize {
+    sys_shmctl(id, cmd)
+}
+pub fn readv(fd: usize, iov: &[IoVec]) -> isize {
+    sys_readv(fd, iov)
+}
+pub fn writev(fd: usize, iov: &[IoVec]) -> isize {
+    sys_writev(fd, iov)
+}
+pub fn sendfile(out_fd: usize, in_fd: usize, offset: Option<&mut usize>, count: usize) -> isize {
+    sys_sendfile(out_fd, in_fd, offset, count)
+}
+pub fn splice(
+    in_fd: usize,
+    in_offset: Option<&mut usize>,
+    out_fd: usize,
+    out_offset: Option<&mut usize>,
+    len: usize,
+) -> isize {
+    sys_splice(in_fd, in_offset, out_fd, out_offset, len)
+}
+pub fn setxattr(path: &str, name: &str, value: &[u8], flags: XattrFlags) -> isize {
+    sys_setxattr(path, name, value, flags.bits)
+}
+pub fn getxattr(path: &str, name: &str, value: &mut [u8]) -> isize {
+    sys_getxattr(path, name, value)
+}
+pub fn listxattr(path: &str, list: &mut [u8]) -> isize {
+    sys_listxattr(path, list)
+}
+pub fn removexattr(path: &str, name: &str) -> isize {
+    sys_removexattr(path, name)
+}
 pub fn exit(exit_code: i32) -> ! {
     sys_exit(exit_code);
 }